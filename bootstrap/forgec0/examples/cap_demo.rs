@@ -1,4 +1,4 @@
-use forgec0::{Parser, Stmt, lower_module};
+use forgec0::{Parser, StmtKind, SourceFile, RenderStyle, check_effects, check_ownership, lower_module};
 use std::fs;
 
 fn main() {
@@ -13,25 +13,12 @@ fn main() {
     println!("{}", input);
     println!();
     
-    // Parse module declaration
+    // Parse the whole file
+    let source = SourceFile::new("examples/cap_demo.fg", input.as_str());
     let mut parser = Parser::new(&input);
-    let mut module = parser.parse_module().expect("Failed to parse module");
-    
-    // Parse remaining function declarations
-    let lines = input.lines().skip(1); // Skip module declaration
-    for line in lines {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-        
-        let mut parser = Parser::new(line);
-        if line.starts_with("fn") {
-            match parser.parse_function() {
-                Ok(func) => module.statements.push(func),
-                Err(e) => eprintln!("Parse error: {:?}", e),
-            }
-        }
+    let (module, errors) = parser.parse_program();
+    for error in &errors {
+        eprint!("{}", error.to_diagnostic().render(&source, RenderStyle::Rich));
     }
     
     println!("=== Parsed AST ===");
@@ -41,14 +28,22 @@ fn main() {
     }
     println!("  Functions: {}", module.statements.len());
     for stmt in &module.statements {
-        if let Stmt::Function { name, capability, .. } = stmt {
+        if let StmtKind::Function { name, capability, .. } = &stmt.kind {
             println!("    - {} {:?}", name, capability);
         }
     }
     println!();
     
+    // Check declared capabilities
+    for diagnostic in check_effects(&module) {
+        eprint!("{}", diagnostic.render(&source, RenderStyle::Rich));
+    }
+    
     // Lower to IR
     let ir_module = lower_module(&module);
+    for diagnostic in check_ownership(&ir_module) {
+        eprint!("{}", diagnostic.render(&source, RenderStyle::Rich));
+    }
     
     println!("=== Forge IR ===");
     print!("{}", ir_module.debug_print());
@@ -1,43 +1,280 @@
-/// AST module for Forge Lang - Phase α
-/// 
-/// Core AST nodes with capability annotations support
+//! AST module for Forge Lang - Phase α
+//!
+//! Core AST nodes with capability annotations support
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
 
-/// Effect types that can be declared in capabilities
-#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
-pub enum Effect {
-    Pure = 0,
-    Alloc = 1,
-    Io = 2,
-    Net = 3,
+use crate::span::Span;
+
+/// Set of effects, as allowed by a capability; the empty set is `pure`.
+/// A set stands for its members and every effect below them in the
+/// [`EffectLattice`], so comparing sets needs the lattice.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Effect {
+    names: BTreeSet<String>,
 }
 
 impl Effect {
-    /// Check if self is subsumed by other (self ⊆ other)
-    pub fn subsumes(&self, other: &Effect) -> bool {
-        self <= other
+    pub const fn pure() -> Effect {
+        Effect { names: BTreeSet::new() }
     }
-    
-    /// Join two effects (least upper bound)
-    pub fn join(&self, other: &Effect) -> Effect {
-        use std::cmp::max;
-        max(self, other).clone()
+
+    /// The single effect `name`; `pure` names the empty set
+    pub fn named(name: &str) -> Effect {
+        let mut effect = Effect::pure();
+        if name != "pure" {
+            effect.names.insert(name.to_string());
+        }
+        effect
     }
-    
-    /// Meet two effects (greatest lower bound)
-    pub fn meet(&self, other: &Effect) -> Effect {
-        use std::cmp::min;
-        min(self, other).clone()
+
+    pub fn is_pure(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Member effects, in name order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    /// Both sets together, without dropping members the lattice makes
+    /// redundant; see [`EffectLattice::join`]
+    pub fn union(&self, other: &Effect) -> Effect {
+        Effect { names: self.names.union(&other.names).cloned().collect() }
+    }
+
+    /// Replace every effect variable bound in `bindings` by its set
+    pub fn substitute(&self, bindings: &HashMap<String, Effect>) -> Effect {
+        self.names.iter().fold(Effect::pure(), |acc, name| match bindings.get(name) {
+            Some(bound) => acc.union(bound),
+            None => acc.union(&Effect::named(name)),
+        })
     }
 }
 
-/// Resource constraints in capability annotations
-#[derive(Debug, Clone)]
+/// Instantiate effect variables `vars` for one call: each `(parameter,
+/// argument)` pair binds every variable the parameter's effect mentions to
+/// the argument's effect, joined over all the pairs that mention it, so
+/// every argument fits its parameter
+pub fn bind_effect_vars(vars: &[String], pairs: impl IntoIterator<Item = (Effect, Effect)>) -> HashMap<String, Effect> {
+    let mut bindings: HashMap<String, Effect> = HashMap::new();
+    for (param, arg) in pairs {
+        for var in vars.iter().filter(|var| param.names.contains(*var)) {
+            let bound = bindings.entry(var.clone()).or_default();
+            *bound = bound.union(&arg);
+        }
+    }
+    bindings
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_pure() {
+            return f.write_str("pure");
+        }
+        let names: Vec<&str> = self.names().collect();
+        f.write_str(&names.join(", "))
+    }
+}
+
+/// Built-in effects, each with the effect directly above it
+pub const BUILTIN_EFFECTS: [(&str, Option<&str>); 3] = [("net", None), ("io", Some("net")), ("alloc", Some("io"))];
+
+/// Effect hierarchy: the built-in `alloc <: io <: net`, extended by
+/// `effect fs <: io;` declarations. An effect has at most one effect
+/// directly above it, so the hierarchy is a forest and two effects are
+/// either ordered or share nothing below them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectLattice {
+    /// Every known effect, with the effect directly above it
+    parents: HashMap<String, Option<String>>,
+}
+
+impl Default for EffectLattice {
+    fn default() -> Self {
+        let parents = BUILTIN_EFFECTS.iter()
+            .map(|(name, parent)| (name.to_string(), parent.map(str::to_string)))
+            .collect();
+        EffectLattice { parents }
+    }
+}
+
+impl EffectLattice {
+    /// Built-in effects plus `declarations`, skipping invalid ones; the
+    /// effect checker reports those
+    pub fn new(declarations: &[EffectDecl]) -> Self {
+        let mut lattice = EffectLattice::default();
+        for decl in declarations {
+            let _ = lattice.declare(&decl.name, decl.parent.as_deref());
+        }
+        lattice
+    }
+
+    /// Add effect `name` below `parent`, which must already be known
+    pub fn declare(&mut self, name: &str, parent: Option<&str>) -> Result<(), String> {
+        if name == "pure" {
+            return Err("`pure` is the empty set of effects and cannot be declared".to_string());
+        }
+        if self.contains(name) {
+            return Err(format!("effect `{}` is already declared", name));
+        }
+        if let Some(parent) = parent.filter(|parent| !self.contains(parent)) {
+            return Err(format!("`{}` must be declared before `{}` can extend it", parent, name));
+        }
+        self.parents.insert(name.to_string(), parent.map(str::to_string));
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.parents.contains_key(name)
+    }
+
+    /// Whether effect `name` is `above` or somewhere below it
+    fn below(&self, name: &str, above: &str) -> bool {
+        let mut current = Some(name);
+        while let Some(effect) = current {
+            if effect == above {
+                return true;
+            }
+            current = self.parents.get(effect).and_then(|parent| parent.as_deref());
+        }
+        false
+    }
+
+    /// Whether everything `a` allows, `b` allows too (a ⊆ b)
+    pub fn subsumes(&self, a: &Effect, b: &Effect) -> bool {
+        a.names().all(|x| b.names().any(|y| self.below(x, y)))
+    }
+
+    /// `effect` without members that another member already covers
+    pub fn normalize(&self, effect: &Effect) -> Effect {
+        let names = effect.names()
+            .filter(|x| !effect.names().any(|y| x != &y && self.below(x, y)))
+            .map(str::to_string)
+            .collect();
+        Effect { names }
+    }
+
+    /// Least upper bound
+    pub fn join(&self, a: &Effect, b: &Effect) -> Effect {
+        self.normalize(&a.union(b))
+    }
+
+    /// Greatest lower bound: the members of each side that the other allows
+    pub fn meet(&self, a: &Effect, b: &Effect) -> Effect {
+        let names = a.names().filter(|x| b.names().any(|y| self.below(x, y)))
+            .chain(b.names().filter(|y| a.names().any(|x| self.below(y, x))))
+            .map(str::to_string)
+            .collect();
+        self.normalize(&Effect { names })
+    }
+
+    /// Every known effect: what a context without a capability allows
+    pub fn top(&self) -> Effect {
+        let names = self.parents.iter()
+            .filter(|(_, parent)| parent.is_none())
+            .map(|(name, _)| name.clone())
+            .collect();
+        Effect { names }
+    }
+}
+
+/// `effect fs <: io;` declaration
+#[derive(Debug, Clone, PartialEq)]
+pub struct EffectDecl {
+    pub name: String,
+    /// Effect directly above this one, if any
+    pub parent: Option<String>,
+    pub span: Span,
+}
+
+/// Resource constraints in capability annotations, in canonical units
+/// whatever unit the source wrote them in
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ResourceBudget {
-    pub tokens: Option<u32>,
-    pub latency_ms: Option<u32>,
-    pub energy_mj: Option<u32>,
+    pub tokens: Option<u64>,
+    /// Microseconds
+    pub latency_us: Option<u64>,
+    /// Microjoules
+    pub energy_uj: Option<u64>,
+}
+
+/// Resource a capability can bound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Tokens,
+    Latency,
+    Energy,
+}
+
+impl Resource {
+    pub const ALL: [Resource; 3] = [Resource::Tokens, Resource::Latency, Resource::Energy];
+
+    /// Resource a capability names, as in `latency <= 50ms`
+    pub fn from_name(name: &str) -> Option<Resource> {
+        Resource::ALL.into_iter().find(|resource| resource.to_string() == name)
+    }
+
+    /// Declared bound on this resource, if any, in canonical units
+    pub fn limit(self, budget: &ResourceBudget) -> Option<u64> {
+        match self {
+            Resource::Tokens => budget.tokens,
+            Resource::Latency => budget.latency_us,
+            Resource::Energy => budget.energy_uj,
+        }
+    }
+
+    /// Unit suffixes a bound may carry, smallest first, with the number of
+    /// canonical units (tokens, microseconds, microjoules) each stands for
+    pub fn units(self) -> &'static [(&'static str, u64)] {
+        match self {
+            Resource::Tokens => &[("k", 1_000), ("M", 1_000_000)],
+            Resource::Latency => &[("us", 1), ("ms", 1_000), ("s", 1_000_000)],
+            Resource::Energy => &[("uJ", 1), ("mJ", 1_000), ("J", 1_000_000)],
+        }
+    }
+
+    /// Canonical units in a bound written without a suffix: a plain count
+    /// of tokens, and for compatibility milliseconds and millijoules
+    pub fn default_scale(self) -> u64 {
+        match self {
+            Resource::Tokens => 1,
+            Resource::Latency | Resource::Energy => 1_000,
+        }
+    }
+
+    /// `amount` as a bound is written, in the largest unit that represents
+    /// it exactly: `50ms`, `1500us`. Token counts have no suffix.
+    pub fn quantity(self, amount: u64) -> String {
+        if self == Resource::Tokens {
+            return amount.to_string();
+        }
+        let (unit, scale) = self.units().iter().rev()
+            .find(|(_, scale)| amount.is_multiple_of(*scale))
+            .copied()
+            .unwrap_or(self.units()[0]);
+        format!("{}{}", amount / scale, unit)
+    }
+
+    /// `amount` of this resource, with its unit
+    pub fn amount(self, amount: u64) -> String {
+        match self {
+            Resource::Tokens => format!("{} tokens", amount),
+            Resource::Latency => format!("{} of latency", self.quantity(amount)),
+            Resource::Energy => format!("{} of energy", self.quantity(amount)),
+        }
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resource::Tokens => write!(f, "tokens"),
+            Resource::Latency => write!(f, "latency"),
+            Resource::Energy => write!(f, "energy"),
+        }
+    }
 }
 
 /// Capability annotation: !{effects, resource budgets}
@@ -45,11 +282,34 @@ pub struct ResourceBudget {
 pub struct Capability {
     pub effects: Vec<Effect>,
     pub budgets: ResourceBudget,
+    pub span: Span,
+}
+
+impl Capability {
+    /// Union of the declared effects; an empty list means `pure`
+    pub fn effect(&self) -> Effect {
+        self.effects.iter().fold(Effect::pure(), |acc, e| acc.union(e))
+    }
+}
+
+/// Type annotation with its source span
+#[derive(Debug, Clone)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub span: Span,
+}
+
+impl Type {
+    pub fn new(kind: TypeKind, span: Span) -> Self {
+        Type { kind, span }
+    }
 }
 
 /// Type representations
 #[derive(Debug, Clone)]
-pub enum Type {
+pub enum TypeKind {
+    /// Type of functions and blocks without a value
+    Unit,
     Int,
     Text,
     Bool,
@@ -60,27 +320,208 @@ pub enum Type {
         capability: Option<Capability>,
     },
     Custom(String),
+    /// Placeholder for a type that failed to parse
+    Error,
 }
 
-/// Expression nodes
+/// Expression node with its source span
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+}
+
+/// Binary operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinOp {
+    /// Binding strength; higher binds tighter
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinOp::Or => 1,
+            BinOp::And => 2,
+            BinOp::Eq | BinOp::Ne => 3,
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => 4,
+            BinOp::Add | BinOp::Sub => 5,
+            BinOp::Mul | BinOp::Div | BinOp::Rem => 6,
+        }
+    }
+    
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Rem => "%",
+            BinOp::Eq => "==",
+            BinOp::Ne => "!=",
+            BinOp::Lt => "<",
+            BinOp::Le => "<=",
+            BinOp::Gt => ">",
+            BinOp::Ge => ">=",
+            BinOp::And => "&&",
+            BinOp::Or => "||",
+        }
+    }
+}
+
+/// Unary operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+impl UnaryOp {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Not => "!",
+        }
+    }
+}
+
+/// Expression kinds
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     Ident(String),
     Number(i64),
     String(String),
+    Bool(bool),
+    Binary {
+        op: BinOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
     Call {
         func: Box<Expr>,
         args: Vec<Expr>,
     },
+    /// Array literal: [a, b, c]
+    Array(Vec<Expr>),
+    /// Indexing: xs[i]
+    Index {
+        base: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// Enum variant: Color::Red
+    Variant {
+        enum_name: String,
+        variant: String,
+    },
+    Block(Block),
+    /// `else_branch` is either a `Block` or another `If`
+    If {
+        cond: Box<Expr>,
+        then_branch: Block,
+        else_branch: Option<Box<Expr>>,
+    },
+    While {
+        cond: Box<Expr>,
+        body: Block,
+    },
+    For {
+        var: String,
+        iter: Box<Expr>,
+        body: Block,
+    },
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
     IntentBlock {
         intent: String,
         constraints: HashMap<String, String>,
     },
+    /// Placeholder for an expression that failed to parse
+    Error,
 }
 
-/// Statement nodes
+impl ExprKind {
+    /// Block-like expressions may end a statement without a `;`
+    pub fn is_block_like(&self) -> bool {
+        matches!(
+            self,
+            ExprKind::Block(_)
+                | ExprKind::If { .. }
+                | ExprKind::While { .. }
+                | ExprKind::For { .. }
+                | ExprKind::Match { .. }
+        )
+    }
+}
+
+/// One arm of a match expression: pattern => body
 #[derive(Debug, Clone)]
-pub enum Stmt {
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+/// Match pattern with its source span
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub span: Span,
+}
+
+/// Pattern kinds: literals, enum variants, bindings and `_`
+#[derive(Debug, Clone)]
+pub enum PatternKind {
+    Wildcard,
+    Binding(String),
+    Number(i64),
+    String(String),
+    Bool(bool),
+    Variant {
+        enum_name: String,
+        variant: String,
+    },
+}
+
+/// Statement node with its source span
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Stmt { kind, span }
+    }
+}
+
+/// Statement kinds
+#[derive(Debug, Clone)]
+pub enum StmtKind {
     Let {
         name: String,
         ty: Option<Type>,
@@ -88,12 +529,37 @@ pub enum Stmt {
     },
     Function {
         name: String,
+        /// Effect variables, as in `fn map<e>`, which may stand in for any
+        /// effect set in the function's capabilities
+        effect_params: Vec<String>,
         params: Vec<(String, Type)>,
         returns: Type,
         capability: Option<Capability>,
-        body: Vec<Stmt>,
+        /// `None` for declarations without a body (e.g. host functions)
+        body: Option<Block>,
+    },
+    /// Assignment to an existing binding: name = value
+    Assign {
+        name: String,
+        value: Expr,
+    },
+    /// Enum declaration: enum Color { Red, Green, Blue }
+    Enum {
+        name: String,
+        variants: Vec<String>,
     },
     Expression(Expr),
+    /// Placeholder for a statement that failed to parse
+    Error,
+}
+
+/// Braced block: statements followed by an optional trailing expression
+/// whose value is the value of the block
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub stmts: Vec<Stmt>,
+    pub tail: Option<Box<Expr>>,
+    pub span: Span,
 }
 
 /// Module definition
@@ -102,7 +568,16 @@ pub struct Module {
     pub name: String,
     pub capability: Option<Capability>,
     pub imports: Vec<String>,
+    /// Effects the module declares, in source order
+    pub effects: Vec<EffectDecl>,
     pub statements: Vec<Stmt>,
 }
 
+impl Module {
+    /// Built-in effects plus the ones this module declares
+    pub fn effect_lattice(&self) -> EffectLattice {
+        EffectLattice::new(&self.effects)
+    }
+}
+
 // TODO: Implement visitor pattern for AST traversal in LC-01
\ No newline at end of file
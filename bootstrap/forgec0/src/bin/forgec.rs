@@ -0,0 +1,301 @@
+//! `forgec` - command-line driver for the bootstrap compiler
+//!
+//! Every command takes one or more `.fg` files and handles them in order.
+//! Diagnostics go to stderr; emitted text goes to stdout unless `-o` names
+//! a file.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use forgec0::{
+    check_budgets, check_effects, check_ownership, check_types, emit_wasm, emit_wat, lower_module_with_types, print_module,
+    tokenize_spanned, Diagnostic, Interpreter, IrModule, Module, Parser, RenderStyle, SourceFile, StdHost, Value,
+    INTERPRETER_STACK_SIZE,
+};
+
+const USAGE: &str = "\
+usage: forgec <command> [options] <file>...
+
+commands:
+  check         parse and check, reporting diagnostics
+  build         compile to WebAssembly (`<file>.wasm`, or `.wat` with --wat)
+  run           check, then interpret the entry function
+  emit-ir       print the IR after checking
+  emit-tokens   print one token per line
+  emit-ast      print the parsed syntax tree
+
+options:
+  -o <path>         write output here instead of stdout; for `build`, the
+                    module, or with several inputs a directory for them
+  --wat             `build`: write WebAssembly text instead of binary
+  --entry <name>    `run`: function to call (default `main`)
+  --plain           one line per diagnostic
+  -h, --help        show this message
+
+exit status:
+  0  success
+  1  a source file has errors
+  2  bad usage, or a file could not be read or written
+  3  `run` failed at runtime";
+
+/// Why the driver stopped; each maps to an exit status
+enum Failure {
+    /// Diagnostics were reported
+    Errors,
+    /// Bad arguments
+    Usage(String),
+    /// A file could not be read or written
+    Io(String),
+    Runtime,
+}
+
+impl Failure {
+    fn exit_code(&self) -> u8 {
+        match self {
+            Failure::Errors => 1,
+            Failure::Usage(_) | Failure::Io(_) => 2,
+            Failure::Runtime => 3,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Check,
+    Build,
+    Run,
+    EmitIr,
+    EmitTokens,
+    EmitAst,
+}
+
+struct Options {
+    command: Command,
+    inputs: Vec<PathBuf>,
+    output: Option<PathBuf>,
+    wat: bool,
+    entry: String,
+    style: RenderStyle,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, Failure> {
+    let usage = |message: String| Failure::Usage(message);
+    let (command, rest) = args.split_first().ok_or_else(|| usage("no command given".to_string()))?;
+    let command = match command.as_str() {
+        "check" => Command::Check,
+        "build" => Command::Build,
+        "run" => Command::Run,
+        "emit-ir" => Command::EmitIr,
+        "emit-tokens" => Command::EmitTokens,
+        "emit-ast" => Command::EmitAst,
+        other => return Err(usage(format!("unknown command `{}`", other))),
+    };
+
+    let mut options = Options {
+        command,
+        inputs: Vec::new(),
+        output: None,
+        wat: false,
+        entry: "main".to_string(),
+        style: RenderStyle::Rich,
+    };
+    let mut rest = rest.iter();
+    while let Some(arg) = rest.next() {
+        let mut value = |flag: &str| rest.next().cloned()
+            .ok_or_else(|| usage(format!("`{}` needs a value", flag)));
+        match arg.as_str() {
+            "-o" => options.output = Some(PathBuf::from(value("-o")?)),
+            "--entry" => options.entry = value("--entry")?,
+            "--wat" if command == Command::Build => options.wat = true,
+            "--plain" => options.style = RenderStyle::Plain,
+            flag if flag.starts_with('-') => {
+                return Err(usage(format!("unknown option `{}`", flag)));
+            }
+            input => options.inputs.push(PathBuf::from(input)),
+        }
+    }
+    if options.output.is_some() && command == Command::Run {
+        return Err(usage("`run` writes to stdout and takes no `-o`".to_string()));
+    }
+    if options.inputs.is_empty() {
+        return Err(usage("no input files".to_string()));
+    }
+    Ok(options)
+}
+
+/// Source file being compiled, and where its diagnostics go
+struct Unit {
+    path: PathBuf,
+    source: SourceFile,
+    style: RenderStyle,
+}
+
+impl Unit {
+    fn read(path: &Path, style: RenderStyle) -> Result<Self, Failure> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| Failure::Io(format!("cannot read `{}`: {}", path.display(), e)))?;
+        let source = SourceFile::new(path.display().to_string(), text);
+        Ok(Unit { path: path.to_path_buf(), source, style })
+    }
+
+    /// Print diagnostics, failing if any is an error
+    fn report(&self, diagnostics: &[Diagnostic]) -> Result<(), Failure> {
+        for diagnostic in diagnostics {
+            eprint!("{}", diagnostic.render(&self.source, self.style));
+        }
+        if diagnostics.iter().any(Diagnostic::is_error) {
+            Err(Failure::Errors)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn parse(&self) -> Result<Module, Failure> {
+        let (module, errors) = Parser::new(&self.source.text).parse_program();
+        let diagnostics: Vec<Diagnostic> = errors.iter().map(|e| e.to_diagnostic()).collect();
+        self.report(&diagnostics)?;
+        Ok(module)
+    }
+
+    /// Parse, run the effect, budget and type checkers, then lower and
+    /// check ownership, so every command reports the same errors
+    fn check(&self) -> Result<(Module, IrModule), Failure> {
+        let module = self.parse()?;
+        let mut diagnostics = check_effects(&module);
+        diagnostics.extend(check_budgets(&module));
+        let (types, type_diagnostics) = check_types(&module);
+        diagnostics.extend(type_diagnostics);
+        self.report(&diagnostics)?;
+
+        let (ir_module, mut diagnostics) = lower_module_with_types(&module, &types);
+        diagnostics.extend(check_ownership(&ir_module));
+        self.report(&diagnostics)?;
+        Ok((module, ir_module))
+    }
+}
+
+/// Destination for text output: stdout, or the `-o` file
+fn open_output(output: &Option<PathBuf>) -> Result<Box<dyn Write>, Failure> {
+    match output {
+        Some(path) => fs::File::create(path)
+            .map(|file| Box::new(file) as Box<dyn Write>)
+            .map_err(|e| Failure::Io(format!("cannot write `{}`: {}", path.display(), e))),
+        None => Ok(Box::new(io::stdout())),
+    }
+}
+
+/// Where `build` puts the module compiled from `input`
+fn build_path(options: &Options, input: &Path) -> Result<PathBuf, Failure> {
+    let file_name = input.with_extension(if options.wat { "wat" } else { "wasm" });
+    match &options.output {
+        Some(path) if options.inputs.len() == 1 => Ok(path.clone()),
+        Some(dir) => {
+            fs::create_dir_all(dir)
+                .map_err(|e| Failure::Io(format!("cannot create `{}`: {}", dir.display(), e)))?;
+            Ok(dir.join(file_name.file_name().unwrap_or_default()))
+        }
+        None => Ok(file_name),
+    }
+}
+
+fn build(options: &Options, unit: &Unit) -> Result<(), Failure> {
+    let (_, ir_module) = unit.check()?;
+    let compiled = if options.wat {
+        emit_wat(&ir_module).map(String::into_bytes)
+    } else {
+        emit_wasm(&ir_module)
+    };
+    let bytes = compiled.map_err(|e| {
+        let _ = unit.report(&[e.to_diagnostic()]);
+        Failure::Errors
+    })?;
+    let path = build_path(options, &unit.path)?;
+    fs::write(&path, bytes).map_err(|e| Failure::Io(format!("cannot write `{}`: {}", path.display(), e)))
+}
+
+/// Host output and the entry function's result both go to stdout
+fn run(options: &Options, unit: &Unit) -> Result<(), Failure> {
+    let (module, _) = unit.check()?;
+    // Deep recursion needs more stack than the main thread has
+    let result = std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(INTERPRETER_STACK_SIZE)
+            .spawn_scoped(scope, || Interpreter::new(&module, StdHost).call(&options.entry, Vec::new()))
+            .map_err(|e| Failure::Io(format!("cannot start the interpreter: {}", e)))?
+            .join()
+            .map_err(|_| Failure::Runtime)
+    })?;
+    match result {
+        Ok(Value::Unit) => Ok(()),
+        Ok(value) => {
+            println!("{}", value);
+            Ok(())
+        }
+        Err(error) => {
+            let _ = unit.report(&[error.to_diagnostic()]);
+            Err(Failure::Runtime)
+        }
+    }
+}
+
+/// Run the command on every input, carrying on past inputs with errors so
+/// each gets its diagnostics; the worst failure decides the exit status
+fn drive(options: &Options) -> Result<(), Failure> {
+    let mut out = match options.command {
+        Command::Check | Command::Build | Command::Run => None,
+        _ => Some(open_output(&options.output).inspect_err(complain)?),
+    };
+    let mut worst: Option<Failure> = None;
+    for input in &options.inputs {
+        let result = Unit::read(input, options.style).and_then(|unit| {
+            let written = match (options.command, out.as_mut()) {
+                (Command::Check, _) => return unit.check().map(|_| ()),
+                (Command::Build, _) => return build(options, &unit),
+                (Command::Run, _) => return run(options, &unit),
+                (Command::EmitIr, Some(out)) => write!(out, "{}", print_module(&unit.check()?.1)),
+                (Command::EmitTokens, Some(out)) => tokenize_spanned(&unit.source.text).iter()
+                    .try_for_each(|t| writeln!(out, "{}:{}: {}", t.span.line, t.span.col, t.token)),
+                (Command::EmitAst, Some(out)) => writeln!(out, "{:#?}", unit.parse()?),
+                (_, None) => unreachable!("text commands always have an output"),
+            };
+            written.map_err(|e| Failure::Io(e.to_string()))
+        });
+        if let Err(failure) = result {
+            complain(&failure);
+            if worst.as_ref().is_none_or(|w| failure.exit_code() > w.exit_code()) {
+                worst = Some(failure);
+            }
+        }
+    }
+    worst.map_or(Ok(()), Err)
+}
+
+/// Print what went wrong if no diagnostic already said so
+fn complain(failure: &Failure) {
+    match failure {
+        Failure::Usage(message) => eprintln!("forgec: {}\n\n{}", message, USAGE),
+        Failure::Io(message) => eprintln!("forgec: {}", message),
+        Failure::Errors | Failure::Runtime => {}
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(failure) => {
+            complain(&failure);
+            return ExitCode::from(failure.exit_code());
+        }
+    };
+    match drive(&options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => ExitCode::from(failure.exit_code()),
+    }
+}
@@ -0,0 +1,744 @@
+//! Budget checker - enforces `tokens`, `latency` and `energy` bounds
+//!
+//! A call costs what its callee declares, or, for resources the callee
+//! declares nothing about, what the callee's body adds up to. Costs add up
+//! along a path, branches take the most expensive arm, and loops multiply
+//! their body by the iteration count where it is known statically: `for`
+//! over an array literal, or a `while i < n` whose counter starts at a
+//! known value and steps by a constant, and whose body neither changes `n`
+//! nor shadows either name with a `let`. Loops with unknown bounds count
+//! once and recursive calls add nothing they do not declare, so the result
+//! is the worst case of the statically known costs.
+//!
+//! A function body whose worst case exceeds the function's budget is an
+//! error (E0401), as is any function whose worst case exceeds the module's
+//! budget (E0402). A function passed for a parameter whose type declares a
+//! budget must fit it (E0403), since calls through the parameter are
+//! charged what the type declares.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+
+/// Worst-case use of each resource; sums saturate
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cost {
+    pub tokens: u64,
+    pub latency_us: u64,
+    pub energy_uj: u64,
+}
+
+impl Cost {
+    pub fn get(&self, resource: Resource) -> u64 {
+        match resource {
+            Resource::Tokens => self.tokens,
+            Resource::Latency => self.latency_us,
+            Resource::Energy => self.energy_uj,
+        }
+    }
+
+    fn map2(self, other: Cost, f: impl Fn(u64, u64) -> u64) -> Cost {
+        Cost {
+            tokens: f(self.tokens, other.tokens),
+            latency_us: f(self.latency_us, other.latency_us),
+            energy_uj: f(self.energy_uj, other.energy_uj),
+        }
+    }
+
+    /// Cost of doing `self`, then `other`
+    fn then(self, other: Cost) -> Cost {
+        self.map2(other, u64::saturating_add)
+    }
+
+    /// Cost of doing whichever of `self` and `other` is dearer
+    fn max(self, other: Cost) -> Cost {
+        self.map2(other, u64::max)
+    }
+
+    fn times(self, n: u64) -> Cost {
+        self.map2(Cost::default(), |cost, _| cost.saturating_mul(n))
+    }
+
+    /// Declared bounds, with `computed` for the resources left undeclared
+    fn declared_or(budget: &ResourceBudget, computed: Cost) -> Cost {
+        Cost {
+            tokens: Resource::Tokens.limit(budget).unwrap_or(computed.tokens),
+            latency_us: Resource::Latency.limit(budget).unwrap_or(computed.latency_us),
+            energy_uj: Resource::Energy.limit(budget).unwrap_or(computed.energy_uj),
+        }
+    }
+}
+
+/// A call found in a function body, with the cost of one call
+#[derive(Debug, Clone)]
+struct CallCost {
+    callee: String,
+    cost: Cost,
+    span: Span,
+}
+
+/// What is known statically about a local
+#[derive(Debug, Clone, Default)]
+struct Local {
+    /// Cost of calling it, for callables
+    callee: Option<Cost>,
+    /// Value of an integer that has not changed since it was set
+    value: Option<i64>,
+    /// Length of an array literal
+    length: Option<u64>,
+}
+
+struct Signature<'a> {
+    params: &'a [(String, Type)],
+    budget: Option<&'a ResourceBudget>,
+    body: Option<&'a Block>,
+}
+
+struct BudgetChecker<'a> {
+    signatures: HashMap<&'a str, Signature<'a>>,
+    /// Worst-case cost of calling each function worked out so far
+    costs: HashMap<&'a str, Cost>,
+    /// Functions whose bodies are being added up, outermost first
+    in_progress: Vec<&'a str>,
+    /// Shallowest entry of `in_progress` called back into since it was last
+    /// reset, or `usize::MAX`
+    reentered: usize,
+    locals: HashMap<String, Local>,
+    calls: Vec<CallCost>,
+    /// Function arguments over their parameter's budget
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Cost of calling a function of this type, as its capability declares
+fn function_type_cost(ty: &Type) -> Option<Cost> {
+    match &ty.kind {
+        TypeKind::Function { capability, .. } => Some(capability.as_ref()
+            .map_or(Cost::default(), |cap| Cost::declared_or(&cap.budgets, Cost::default()))),
+        _ => None,
+    }
+}
+
+/// Names assigned anywhere inside a block
+fn assigned_names(block: &Block, names: &mut HashSet<String>) {
+    for stmt in &block.stmts {
+        match &stmt.kind {
+            StmtKind::Assign { name, value } => {
+                names.insert(name.clone());
+                assigned_in_expr(value, names);
+            }
+            StmtKind::Let { value: expr, .. } | StmtKind::Expression(expr) => assigned_in_expr(expr, names),
+            _ => {}
+        }
+    }
+    if let Some(tail) = &block.tail {
+        assigned_in_expr(tail, names);
+    }
+}
+
+/// Names assigned inside the blocks of an expression
+fn assigned_in_expr(expr: &Expr, names: &mut HashSet<String>) {
+    for_each_block(expr, &mut |block| assigned_names(block, names));
+}
+
+/// Names bound with `let` anywhere inside a block
+fn let_names(block: &Block, names: &mut HashSet<String>) {
+    for stmt in &block.stmts {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } => {
+                names.insert(name.clone());
+                for_each_block(value, &mut |block| let_names(block, names));
+            }
+            StmtKind::Expression(expr) => for_each_block(expr, &mut |block| let_names(block, names)),
+            _ => {}
+        }
+    }
+    if let Some(tail) = &block.tail {
+        for_each_block(tail, &mut |block| let_names(block, names));
+    }
+}
+
+/// Call `f` on the blocks an expression runs: bodies, branches and arms
+fn for_each_block(expr: &Expr, f: &mut dyn FnMut(&Block)) {
+    match &expr.kind {
+        ExprKind::Block(block) | ExprKind::While { body: block, .. } | ExprKind::For { body: block, .. } => f(block),
+        ExprKind::If { then_branch, else_branch, .. } => {
+            f(then_branch);
+            if let Some(else_branch) = else_branch {
+                for_each_block(else_branch, f);
+            }
+        }
+        ExprKind::Match { arms, .. } => arms.iter().for_each(|arm| for_each_block(&arm.body, f)),
+        _ => {}
+    }
+}
+
+impl<'a> BudgetChecker<'a> {
+    fn new(module: &'a Module) -> Self {
+        let mut signatures = HashMap::new();
+        for stmt in &module.statements {
+            if let StmtKind::Function { name, params, capability, body, .. } = &stmt.kind {
+                signatures.insert(name.as_str(), Signature {
+                    params,
+                    budget: capability.as_ref().map(|cap| &cap.budgets),
+                    body: body.as_ref(),
+                });
+            }
+        }
+        BudgetChecker {
+            signatures,
+            costs: HashMap::new(),
+            in_progress: Vec::new(),
+            reentered: usize::MAX,
+            locals: HashMap::new(),
+            calls: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Worst-case cost of calling a module-level function
+    fn cost_of(&mut self, name: &str) -> Cost {
+        if let Some(&cost) = self.costs.get(name) {
+            return cost;
+        }
+        let Some((&name, sig)) = self.signatures.get_key_value(name) else {
+            return Cost::default();
+        };
+        let (params, budget, body) = (sig.params, sig.budget, sig.body);
+        let fully_declared = budget.is_some_and(|budget| Resource::ALL.iter().all(|r| r.limit(budget).is_some()));
+        let mut cacheable = true;
+        let computed = match body {
+            Some(body) if !fully_declared => match self.in_progress.iter().position(|&f| f == name) {
+                // A recursive call adds nothing it does not declare
+                Some(depth) => {
+                    self.reentered = self.reentered.min(depth);
+                    cacheable = false;
+                    Cost::default()
+                }
+                None => {
+                    let depth = self.in_progress.len();
+                    let outer = std::mem::replace(&mut self.reentered, usize::MAX);
+                    self.in_progress.push(name);
+                    let (cost, ..) = self.body_cost(params, body);
+                    self.in_progress.pop();
+                    // What a cycle adds up to depends on where it was
+                    // entered, so its members' costs are not kept
+                    cacheable = self.reentered > depth;
+                    self.reentered = self.reentered.min(outer);
+                    cost
+                }
+            },
+            _ => Cost::default(),
+        };
+        let cost = budget.map_or(computed, |budget| Cost::declared_or(budget, computed));
+        if cacheable {
+            self.costs.insert(name, cost);
+        }
+        cost
+    }
+
+    /// Worst-case cost of a function body, the calls it makes, and the
+    /// function arguments it passes over budget
+    fn body_cost(&mut self, params: &[(String, Type)], body: &Block) -> (Cost, Vec<CallCost>, Vec<Diagnostic>) {
+        let locals = params.iter()
+            .map(|(name, ty)| (name.clone(), Local { callee: function_type_cost(ty), ..Local::default() }))
+            .collect();
+        let saved_locals = std::mem::replace(&mut self.locals, locals);
+        let saved_calls = std::mem::take(&mut self.calls);
+        let saved_diagnostics = std::mem::take(&mut self.diagnostics);
+        let cost = self.block(body);
+        self.locals = saved_locals;
+        let calls = std::mem::replace(&mut self.calls, saved_calls);
+        (cost, calls, std::mem::replace(&mut self.diagnostics, saved_diagnostics))
+    }
+
+    /// What calling `name` costs, when it names a local or module-level
+    /// callable
+    fn callable(&mut self, name: &str) -> Option<Cost> {
+        match self.locals.get(name) {
+            Some(local) => local.callee,
+            None if self.signatures.contains_key(name) => Some(self.cost_of(name)),
+            None => None,
+        }
+    }
+
+    /// Functions passed as arguments must fit the parameter's budget
+    fn check_function_arguments(&mut self, callee: &str, args: &[Expr]) {
+        if self.locals.contains_key(callee) {
+            return;
+        }
+        let Some(params) = self.signatures.get(callee).map(|sig| sig.params) else {
+            return;
+        };
+        for ((_, param_ty), arg) in params.iter().zip(args) {
+            let ExprKind::Ident(arg_name) = &arg.kind else {
+                continue;
+            };
+            let TypeKind::Function { capability: Some(cap), .. } = &param_ty.kind else {
+                continue;
+            };
+            let Some(actual) = self.callable(arg_name) else {
+                continue;
+            };
+            for resource in Resource::ALL {
+                let Some(limit) = resource.limit(&cap.budgets) else {
+                    continue;
+                };
+                if actual.get(resource) > limit {
+                    self.diagnostics.push(
+                        Diagnostic::error(format!(
+                            "`{}` may need {}, but `{}` only accepts functions within {} here",
+                            arg_name, resource.amount(actual.get(resource)), callee, resource.amount(limit)
+                        ))
+                        .with_code("E0403")
+                        .with_primary(arg.span, format!("may need {}", resource.amount(actual.get(resource))))
+                        .with_secondary(param_ty.span, format!("parameter allows {}", resource.amount(limit))),
+                    );
+                }
+            }
+        }
+    }
+
+    /// What a `let` or assignment of `value` tells about the variable
+    fn local_of(&mut self, value: &Expr) -> Local {
+        match &value.kind {
+            ExprKind::Number(n) => Local { value: Some(*n), ..Local::default() },
+            ExprKind::Array(items) => Local { length: Some(items.len() as u64), ..Local::default() },
+            ExprKind::Ident(alias) => match self.locals.get(alias) {
+                Some(local) => local.clone(),
+                None if self.signatures.contains_key(alias.as_str()) => {
+                    Local { callee: Some(self.cost_of(alias)), ..Local::default() }
+                }
+                None => Local::default(),
+            },
+            _ => Local::default(),
+        }
+    }
+
+    fn block(&mut self, block: &Block) -> Cost {
+        let saved = self.locals.clone();
+        let mut cost = Cost::default();
+        for stmt in &block.stmts {
+            cost = cost.then(self.stmt(stmt));
+        }
+        if let Some(tail) = &block.tail {
+            cost = cost.then(self.expr(tail));
+        }
+        // Outer variables assigned in here are no longer known afterwards
+        self.locals = saved;
+        let mut assigned = HashSet::new();
+        assigned_names(block, &mut assigned);
+        for name in assigned {
+            if let Some(local) = self.locals.get_mut(&name) {
+                local.value = None;
+                local.length = None;
+            }
+        }
+        cost
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Cost {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } => {
+                let cost = self.expr(value);
+                let local = self.local_of(value);
+                self.locals.insert(name.clone(), local);
+                cost
+            }
+            StmtKind::Assign { name, value } => {
+                let cost = self.expr(value);
+                let local = self.local_of(value);
+                self.locals.insert(name.clone(), local);
+                cost
+            }
+            StmtKind::Expression(expr) => self.expr(expr),
+            StmtKind::Function { .. } | StmtKind::Enum { .. } | StmtKind::Error => Cost::default(),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Cost {
+        match &expr.kind {
+            ExprKind::Call { func, args } => {
+                let mut cost = args.iter().fold(Cost::default(), |cost, arg| cost.then(self.expr(arg)));
+                let (callee, call) = match &func.kind {
+                    ExprKind::Ident(name) => {
+                        self.check_function_arguments(name, args);
+                        match self.locals.get(name) {
+                            Some(local) => (name.clone(), local.callee.unwrap_or_default()),
+                            None => (name.clone(), self.cost_of(name)),
+                        }
+                    }
+                    _ => {
+                        cost = cost.then(self.expr(func));
+                        (String::new(), Cost::default())
+                    }
+                };
+                if call != Cost::default() {
+                    self.calls.push(CallCost { callee, cost: call, span: expr.span });
+                }
+                cost.then(call)
+            }
+            ExprKind::Binary { lhs, rhs, .. } => self.expr(lhs).then(self.expr(rhs)),
+            ExprKind::Unary { operand, .. } => self.expr(operand),
+            ExprKind::Array(items) => items.iter().fold(Cost::default(), |cost, item| cost.then(self.expr(item))),
+            ExprKind::Index { base, index } => self.expr(base).then(self.expr(index)),
+            ExprKind::Block(block) => self.block(block),
+            ExprKind::If { cond, then_branch, else_branch } => {
+                let cond = self.expr(cond);
+                let then_cost = self.block(then_branch);
+                let else_cost = else_branch.as_ref().map_or(Cost::default(), |e| self.expr(e));
+                cond.then(then_cost.max(else_cost))
+            }
+            ExprKind::While { cond, body } => {
+                let bound = self.while_bound(cond, body);
+                let cond = self.expr(cond);
+                let body = self.block(body);
+                match bound {
+                    // The condition is checked once more on the way out
+                    Some(n) => cond.then(cond.then(body).times(n)),
+                    None => cond.then(body),
+                }
+            }
+            ExprKind::For { var, iter, body } => {
+                let length = match &iter.kind {
+                    ExprKind::Array(items) => Some(items.len() as u64),
+                    ExprKind::Ident(name) => self.locals.get(name).and_then(|local| local.length),
+                    _ => None,
+                };
+                let iter = self.expr(iter);
+                let saved = self.locals.insert(var.clone(), Local::default());
+                let body = self.block(body);
+                match saved {
+                    Some(saved) => self.locals.insert(var.clone(), saved),
+                    None => self.locals.remove(var),
+                };
+                iter.then(body.times(length.unwrap_or(1)))
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee = self.expr(scrutinee);
+                let mut worst = Cost::default();
+                for arm in arms {
+                    let saved = self.locals.clone();
+                    if let PatternKind::Binding(name) = &arm.pattern.kind {
+                        self.locals.insert(name.clone(), Local::default());
+                    }
+                    worst = worst.max(self.expr(&arm.body));
+                    self.locals = saved;
+                }
+                scrutinee.then(worst)
+            }
+            ExprKind::Ident(_)
+            | ExprKind::Number(_)
+            | ExprKind::String(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Variant { .. }
+            | ExprKind::IntentBlock { .. }
+            | ExprKind::Error => Cost::default(),
+        }
+    }
+
+    /// Iterations of `while i < n` (or `<=`), where `i` starts at a known
+    /// value, the body's only assignment to it is `i = i + k`, and neither
+    /// name is reassigned or re-bound otherwise
+    fn while_bound(&self, cond: &Expr, body: &Block) -> Option<u64> {
+        let ExprKind::Binary { op, lhs, rhs } = &cond.kind else {
+            return None;
+        };
+        let ExprKind::Ident(counter) = &lhs.kind else {
+            return None;
+        };
+        let start = self.locals.get(counter)?.value?;
+        let end_name = match &rhs.kind {
+            ExprKind::Ident(name) if name != counter => Some(name),
+            _ => None,
+        };
+        let end = match (&rhs.kind, end_name) {
+            (ExprKind::Number(n), _) => *n,
+            (_, Some(name)) => self.locals.get(name)?.value?,
+            _ => return None,
+        };
+        let end = match op {
+            BinOp::Lt => end,
+            BinOp::Le => end.checked_add(1)?,
+            _ => return None,
+        };
+
+        // The step must be a statement of the body itself, not inside a
+        // branch or inner loop, so it runs exactly once every iteration
+        let mut steps = body.stmts.iter().filter_map(|stmt| match &stmt.kind {
+            StmtKind::Assign { name, value } if name == counter => Some(value),
+            _ => None,
+        });
+        let step = steps.next()?;
+        if steps.next().is_some() {
+            return None;
+        }
+        // Nor may the body step the counter anywhere else, move the end,
+        // or shadow either with a `let`
+        let mut nested = HashSet::new();
+        for stmt in &body.stmts {
+            match &stmt.kind {
+                StmtKind::Assign { value: expr, .. } | StmtKind::Let { value: expr, .. }
+                | StmtKind::Expression(expr) => assigned_in_expr(expr, &mut nested),
+                _ => {}
+            }
+        }
+        if let Some(tail) = &body.tail {
+            assigned_in_expr(tail, &mut nested);
+        }
+        let mut assigned = HashSet::new();
+        assigned_names(body, &mut assigned);
+        let mut shadowed = HashSet::new();
+        let_names(body, &mut shadowed);
+        if nested.contains(counter)
+            || shadowed.contains(counter)
+            || end_name.is_some_and(|end| assigned.contains(end) || shadowed.contains(end))
+        {
+            return None;
+        }
+        let ExprKind::Binary { op: BinOp::Add, lhs: step_lhs, rhs: step_rhs } = &step.kind else {
+            return None;
+        };
+        let k = match (&step_lhs.kind, &step_rhs.kind) {
+            (ExprKind::Ident(name), ExprKind::Number(k)) | (ExprKind::Number(k), ExprKind::Ident(name))
+                if name == counter && *k > 0 => *k,
+            _ => return None,
+        };
+        if start >= end {
+            return Some(0);
+        }
+        let distance = (end as i128 - start as i128) as u128;
+        Some(distance.div_ceil(k as u128).min(u64::MAX as u128) as u64)
+    }
+}
+
+/// Worst-case cost of calling every function in the module: declared
+/// bounds, or what the body adds up to for resources it does not declare
+pub fn infer_costs(module: &Module) -> HashMap<String, Cost> {
+    let mut checker = BudgetChecker::new(module);
+    let names: Vec<&str> = checker.signatures.keys().copied().collect();
+    names.into_iter().map(|name| (name.to_string(), checker.cost_of(name))).collect()
+}
+
+/// Labels pointing at the calls that spend `resource`
+fn with_calls(mut diagnostic: Diagnostic, calls: &[CallCost], resource: Resource) -> Diagnostic {
+    for call in calls.iter().filter(|call| call.cost.get(resource) > 0) {
+        diagnostic = diagnostic.with_secondary(call.span,
+            format!("`{}` may need {}", call.callee, resource.amount(call.cost.get(resource))));
+    }
+    diagnostic
+}
+
+/// Check every function's worst-case cost against its own budget and the
+/// module's
+pub fn check_budgets(module: &Module) -> Vec<Diagnostic> {
+    let mut checker = BudgetChecker::new(module);
+    let mut diagnostics = Vec::new();
+    let module_cap = module.capability.as_ref();
+
+    for stmt in &module.statements {
+        let StmtKind::Function { name, params, capability, body, .. } = &stmt.kind else {
+            continue;
+        };
+        let (computed, calls, argument_errors) = match body {
+            Some(body) => {
+                // Add the body up as `cost_of` would, recursion included
+                checker.in_progress.push(name);
+                let result = checker.body_cost(params, body);
+                checker.in_progress.pop();
+                checker.reentered = usize::MAX;
+                result
+            }
+            None => (Cost::default(), Vec::new(), Vec::new()),
+        };
+        diagnostics.extend(argument_errors);
+
+        if let Some(cap) = capability {
+            for resource in Resource::ALL {
+                let Some(limit) = resource.limit(&cap.budgets) else {
+                    continue;
+                };
+                let worst = computed.get(resource);
+                if worst > limit {
+                    let diagnostic = Diagnostic::error(format!(
+                        "`{}` may need {}, but its budget is {}",
+                        name, resource.amount(worst), resource.amount(limit)
+                    ))
+                    .with_code("E0401")
+                    .with_primary(cap.span, "budget declared here");
+                    diagnostics.push(with_calls(diagnostic, &calls, resource));
+                }
+            }
+        }
+
+        let Some(module_cap) = module_cap else {
+            continue;
+        };
+        let worst = capability.as_ref().map_or(computed, |cap| Cost::declared_or(&cap.budgets, computed));
+        for resource in Resource::ALL {
+            let Some(limit) = resource.limit(&module_cap.budgets) else {
+                continue;
+            };
+            if worst.get(resource) <= limit {
+                continue;
+            }
+            let message = format!(
+                "`{}` may need {}, but module `{}` allows {}",
+                name, resource.amount(worst.get(resource)), module.name, resource.amount(limit)
+            );
+            let mut diagnostic = Diagnostic::error(message).with_code("E0402");
+            match capability.as_ref().filter(|cap| resource.limit(&cap.budgets).is_some()) {
+                Some(cap) => {
+                    diagnostic = diagnostic.with_primary(cap.span, "budget declared here");
+                }
+                None => {
+                    diagnostic = diagnostic.with_primary(stmt.span, "");
+                    diagnostic = with_calls(diagnostic, &calls, resource);
+                }
+            }
+            diagnostics.push(diagnostic.with_secondary(module_cap.span, "module budget declared here"));
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn check(src: &str) -> Vec<Diagnostic> {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        check_budgets(&module)
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().filter_map(|d| d.code.as_deref()).collect()
+    }
+
+    fn costs(src: &str) -> HashMap<String, Cost> {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        infer_costs(&module)
+    }
+
+    #[test]
+    fn test_calls_add_up_within_budget() {
+        let diagnostics = check("module m
+fn ask(q: Text) -> Text !{net, tokens <= 40, latency <= 100ms}
+fn twice() -> Text !{net, tokens <= 80, latency <= 200ms} { ask(\"a\"); ask(\"b\") }");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_over_budget() {
+        let diagnostics = check("module m
+fn ask(q: Text) -> Text !{net, tokens <= 40}
+fn thrice() -> Text !{net, tokens <= 100} { ask(\"a\"); ask(\"b\"); ask(\"c\") }");
+        assert_eq!(codes(&diagnostics), vec!["E0401"]);
+        assert_eq!(diagnostics[0].message, "`thrice` may need 120 tokens, but its budget is 100 tokens");
+        assert_eq!(diagnostics[0].labels.len(), 4);
+    }
+
+    #[test]
+    fn test_branches_take_the_maximum() {
+        let costs = costs("module m
+fn cheap() !{latency <= 5ms}
+fn dear() !{latency <= 50ms}
+fn pick(b: Bool) { if b { cheap(); } else { dear(); cheap(); } }
+fn choose(n: Int) { match n { 0 => cheap(), _ => dear() } }");
+        assert_eq!(costs["pick"].latency_us, 55_000);
+        assert_eq!(costs["choose"].latency_us, 50_000);
+    }
+
+    #[test]
+    fn test_loop_bounds() {
+        let costs = costs("module m
+fn step() !{energy <= 3mJ}
+fn over_literal() { for x in [1, 2, 3, 4] { step(); } }
+fn over_local() { let xs = [1, 2]; for x in xs { step(); } }
+fn counted() { let i = 0; while i < 10 { i = i + 2; step(); } }
+fn inclusive() { let i = 1; while i <= 10 { i = i + 1; step(); } }
+fn unknown(n: Int) { let i = 0; while i < n { i = i + 1; step(); } }
+fn twice() { let i = 0; while i < 3 { i = i + 1; step(); } while i < 3 { i = i + 1; step(); } }");
+        assert_eq!(costs["over_literal"].energy_uj, 12_000);
+        assert_eq!(costs["over_local"].energy_uj, 6_000);
+        assert_eq!(costs["counted"].energy_uj, 15_000);
+        assert_eq!(costs["inclusive"].energy_uj, 30_000);
+        assert_eq!(costs["unknown"].energy_uj, 3_000);
+        // `i` is unknown after the first loop
+        assert_eq!(costs["twice"].energy_uj, 12_000);
+    }
+
+    #[test]
+    fn test_loop_bounds_need_fixed_end_and_counter() {
+        let costs = costs("module m
+fn ask() !{tokens <= 10}
+fn fixed() { let i = 0; let n = 3; while i < n { ask(); i = i + 1; } }
+fn moving_end() { let i = 0; let n = 3; while i < n { ask(); n = n + 1; i = i + 1; } }
+fn nested_end() { let i = 0; let n = 3; while i < n { if i == 1 { n = 9; } ask(); i = i + 1; } }
+fn shadowed_counter() { let i = 0; while i < 3 { let i = 0; ask(); i = i + 1; } }
+fn shadowed_end() { let i = 0; let n = 3; while i < n { let n = 9; ask(); i = i + 1; } }");
+        assert_eq!(costs["fixed"].tokens, 30);
+        // None of these has a static bound, so each counts its body once
+        for name in ["moving_end", "nested_end", "shadowed_counter", "shadowed_end"] {
+            assert_eq!(costs[name].tokens, 10, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_undeclared_callees_use_their_bodies() {
+        let costs = costs("module m
+fn ask() !{tokens <= 10}
+fn helper() { ask(); ask(); }
+fn partly() !{tokens <= 5, latency <= 7ms} { helper(); }
+fn recursive(n: Int) { ask(); recursive(n - 1); }
+fn apply(f: fn() !{tokens <= 4}) { f(); f(); }");
+        assert_eq!(costs["helper"].tokens, 20);
+        assert_eq!(costs["partly"], Cost { tokens: 5, latency_us: 7_000, energy_uj: 0 });
+        assert_eq!(costs["recursive"].tokens, 10);
+        assert_eq!(costs["apply"].tokens, 8);
+    }
+
+    #[test]
+    fn test_mutual_recursion_is_independent_of_order() {
+        let cycle = "fn a(n: Int) { if n > 0 { b(n - 1); } ask(); ask(); ask(); }
+fn b(n: Int) { if n > 0 { a(n - 1); } }";
+        let callers = ["fn d() !{tokens <= 5} { a(3) }", "fn c() !{tokens <= 5} { b(3) }"];
+        for order in [[0, 1], [1, 0]] {
+            let src = format!("module m\nfn ask() !{{tokens <= 10}}\n{}\n{}\n{}",
+                callers[order[0]], callers[order[1]], cycle);
+            let diagnostics = check(&src);
+            assert_eq!(codes(&diagnostics), vec!["E0401", "E0401"], "{}", src);
+            let costs = costs(&src);
+            assert_eq!((costs["a"].tokens, costs["b"].tokens), (30, 30));
+        }
+    }
+
+    #[test]
+    fn test_function_arguments_fit_parameter_budgets() {
+        let diagnostics = check("module m
+fn cheap() !{tokens <= 3}
+fn expensive() !{tokens <= 1000}
+fn apply(f: fn() !{tokens <= 4}) !{tokens <= 8} { f(); f(); }
+fn ok() { apply(cheap) }
+fn bad() { apply(expensive) }");
+        assert_eq!(codes(&diagnostics), vec!["E0403"]);
+        assert_eq!(diagnostics[0].message, "`expensive` may need 1000 tokens, but `apply` only accepts functions within 4 tokens here");
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 6);
+    }
+
+    #[test]
+    fn test_module_budget() {
+        let diagnostics = check("module m !{tokens <= 50}
+fn ask() !{tokens <= 30}
+fn big() !{tokens <= 60}
+fn ok() { ask(); }
+fn loop_over() { for x in [1, 2] { ask(); } }");
+        assert_eq!(codes(&diagnostics), vec!["E0402", "E0402"]);
+        assert!(diagnostics[0].message.starts_with("`big` may need 60 tokens"));
+        assert!(diagnostics[1].message.starts_with("`loop_over` may need 60 tokens"));
+    }
+}
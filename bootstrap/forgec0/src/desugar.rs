@@ -0,0 +1,405 @@
+//! Desugaring pass - rewrites surface control flow into a smaller core
+//!
+//! `match` becomes an `if`/`else` chain of `==` tests, `for` becomes a
+//! `while` loop over an index, and enum variants become their integer
+//! discriminants, so lowering to IR only has to handle `if` and `while`.
+//! Temporaries are named with a `$`, which user code cannot spell.
+
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::span::Span;
+
+/// Builtin returning the length of an array
+pub const LEN_BUILTIN: &str = "len";
+
+/// Desugaring context: enum declarations and a temporary counter
+#[derive(Debug, Default)]
+pub struct Desugarer {
+    enums: HashMap<String, Vec<String>>,
+    next_temp: usize,
+}
+
+impl Desugarer {
+    /// Collect the enum declarations of a module
+    pub fn new(module: &Module) -> Self {
+        let mut enums = HashMap::new();
+        for stmt in &module.statements {
+            if let StmtKind::Enum { name, variants } = &stmt.kind {
+                enums.insert(name.clone(), variants.clone());
+            }
+        }
+        Desugarer { enums, next_temp: 0 }
+    }
+
+    /// Integer discriminant of `enum_name::variant`, in declaration order
+    pub fn discriminant(&self, enum_name: &str, variant: &str) -> Option<i64> {
+        self.enums
+            .get(enum_name)?
+            .iter()
+            .position(|v| v == variant)
+            .map(|i| i as i64)
+    }
+
+    fn fresh(&mut self, prefix: &str) -> String {
+        let name = format!("{}${}", prefix, self.next_temp);
+        self.next_temp += 1;
+        name
+    }
+
+    pub fn desugar_block(&mut self, block: &Block) -> Block {
+        Block {
+            stmts: block.stmts.iter().map(|s| self.desugar_stmt(s)).collect(),
+            tail: block.tail.as_ref().map(|e| Box::new(self.desugar_expr(e))),
+            span: block.span,
+        }
+    }
+
+    pub fn desugar_stmt(&mut self, stmt: &Stmt) -> Stmt {
+        let kind = match &stmt.kind {
+            StmtKind::Let { name, ty, value } => StmtKind::Let {
+                name: name.clone(),
+                ty: ty.clone(),
+                value: self.desugar_expr(value),
+            },
+            StmtKind::Assign { name, value } => StmtKind::Assign {
+                name: name.clone(),
+                value: self.desugar_expr(value),
+            },
+            StmtKind::Function { name, effect_params, params, returns, capability, body } => StmtKind::Function {
+                name: name.clone(),
+                effect_params: effect_params.clone(),
+                params: params.clone(),
+                returns: returns.clone(),
+                capability: capability.clone(),
+                body: body.as_ref().map(|b| self.desugar_block(b)),
+            },
+            StmtKind::Expression(expr) => StmtKind::Expression(self.desugar_expr(expr)),
+            StmtKind::Enum { .. } | StmtKind::Error => stmt.kind.clone(),
+        };
+        Stmt::new(kind, stmt.span)
+    }
+
+    pub fn desugar_expr(&mut self, expr: &Expr) -> Expr {
+        let span = expr.span;
+        let kind = match &expr.kind {
+            ExprKind::Variant { enum_name, variant } => {
+                match self.discriminant(enum_name, variant) {
+                    Some(value) => ExprKind::Number(value),
+                    // Left for the checker to report
+                    None => expr.kind.clone(),
+                }
+            }
+            ExprKind::Binary { op, lhs, rhs } => ExprKind::Binary {
+                op: *op,
+                lhs: Box::new(self.desugar_expr(lhs)),
+                rhs: Box::new(self.desugar_expr(rhs)),
+            },
+            ExprKind::Unary { op, operand } => ExprKind::Unary {
+                op: *op,
+                operand: Box::new(self.desugar_expr(operand)),
+            },
+            ExprKind::Call { func, args } => ExprKind::Call {
+                func: Box::new(self.desugar_expr(func)),
+                args: args.iter().map(|a| self.desugar_expr(a)).collect(),
+            },
+            ExprKind::Array(items) => {
+                ExprKind::Array(items.iter().map(|e| self.desugar_expr(e)).collect())
+            }
+            ExprKind::Index { base, index } => ExprKind::Index {
+                base: Box::new(self.desugar_expr(base)),
+                index: Box::new(self.desugar_expr(index)),
+            },
+            ExprKind::Block(block) => ExprKind::Block(self.desugar_block(block)),
+            ExprKind::If { cond, then_branch, else_branch } => ExprKind::If {
+                cond: Box::new(self.desugar_expr(cond)),
+                then_branch: self.desugar_block(then_branch),
+                else_branch: else_branch.as_ref().map(|e| Box::new(self.desugar_expr(e))),
+            },
+            ExprKind::While { cond, body } => ExprKind::While {
+                cond: Box::new(self.desugar_expr(cond)),
+                body: self.desugar_block(body),
+            },
+            ExprKind::For { var, iter, body } => return self.desugar_for(var, iter, body, span),
+            ExprKind::Match { scrutinee, arms } => return self.desugar_match(scrutinee, arms, span),
+            ExprKind::Ident(_)
+            | ExprKind::Number(_)
+            | ExprKind::String(_)
+            | ExprKind::Bool(_)
+            | ExprKind::IntentBlock { .. }
+            | ExprKind::Error => expr.kind.clone(),
+        };
+        Expr::new(kind, span)
+    }
+
+    /// for var in iter { body }
+    ///
+    /// becomes
+    ///
+    /// { let xs = iter; let i = 0;
+    ///   while i < len(xs) { let var = xs[i]; i = i + 1; body } }
+    fn desugar_for(&mut self, var: &str, iter: &Expr, body: &Block, span: Span) -> Expr {
+        let items = self.fresh("for_items");
+        let index = self.fresh("for_index");
+        let ident = |name: &str| Expr::new(ExprKind::Ident(name.to_string()), span);
+        let binary = |op, lhs, rhs| Expr::new(
+            ExprKind::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) },
+            span,
+        );
+
+        let len_call = Expr::new(ExprKind::Call {
+            func: Box::new(ident(LEN_BUILTIN)),
+            args: vec![ident(&items)],
+        }, span);
+        let element = Expr::new(ExprKind::Index {
+            base: Box::new(ident(&items)),
+            index: Box::new(ident(&index)),
+        }, span);
+
+        let mut loop_stmts = vec![
+            let_stmt(var, element, span),
+            Stmt::new(StmtKind::Assign {
+                name: index.clone(),
+                value: binary(BinOp::Add, ident(&index), Expr::new(ExprKind::Number(1), span)),
+            }, span),
+        ];
+        let body = self.desugar_block(body);
+        loop_stmts.extend(body.stmts);
+        if let Some(tail) = body.tail {
+            let tail_span = tail.span;
+            loop_stmts.push(Stmt::new(StmtKind::Expression(*tail), tail_span));
+        }
+
+        let while_loop = Expr::new(ExprKind::While {
+            cond: Box::new(binary(BinOp::Lt, ident(&index), len_call)),
+            body: Block { stmts: loop_stmts, tail: None, span: body.span },
+        }, span);
+
+        Expr::new(ExprKind::Block(Block {
+            stmts: vec![
+                let_stmt(&items, self.desugar_expr(iter), span),
+                let_stmt(&index, Expr::new(ExprKind::Number(0), span), span),
+                Stmt::new(StmtKind::Expression(while_loop), span),
+            ],
+            tail: None,
+            span,
+        }), span)
+    }
+
+    /// match value { p1 => a, p2 => b, _ => c }
+    ///
+    /// becomes
+    ///
+    /// { let m = value; if m == p1 { a } else if m == p2 { b } else { c } }
+    fn desugar_match(&mut self, scrutinee: &Expr, arms: &[MatchArm], span: Span) -> Expr {
+        let temp = self.fresh("match");
+        let scrutinee = self.desugar_expr(scrutinee);
+        let exhaustive = self.covers_all_values(arms);
+        let chain = self.match_chain(&temp, arms, exhaustive);
+
+        Expr::new(ExprKind::Block(Block {
+            stmts: vec![let_stmt(&temp, scrutinee, span)],
+            tail: chain.map(Box::new),
+            span,
+        }), span)
+    }
+
+    fn match_chain(&mut self, temp: &str, arms: &[MatchArm], exhaustive: bool) -> Option<Expr> {
+        let (arm, rest) = arms.split_first()?;
+        let body = self.desugar_expr(&arm.body);
+        let span = arm.span;
+        let temp_ident = Expr::new(ExprKind::Ident(temp.to_string()), arm.pattern.span);
+
+        let literal = match &arm.pattern.kind {
+            PatternKind::Wildcard => return Some(block_expr(Vec::new(), body, span)),
+            PatternKind::Binding(name) => {
+                let binding = let_stmt(name, temp_ident, arm.pattern.span);
+                return Some(block_expr(vec![binding], body, span));
+            }
+            // The last arm of a match covering every value needs no test;
+            // the type checker rejects matches that are not exhaustive
+            PatternKind::Variant { .. } | PatternKind::Bool(_) if exhaustive && rest.is_empty() => {
+                return Some(block_expr(Vec::new(), body, span));
+            }
+            PatternKind::Number(n) => ExprKind::Number(*n),
+            PatternKind::String(s) => ExprKind::String(s.clone()),
+            PatternKind::Bool(b) => ExprKind::Bool(*b),
+            PatternKind::Variant { enum_name, variant } => {
+                match self.discriminant(enum_name, variant) {
+                    Some(value) => ExprKind::Number(value),
+                    None => ExprKind::Variant {
+                        enum_name: enum_name.clone(),
+                        variant: variant.clone(),
+                    },
+                }
+            }
+        };
+
+        let cond = Expr::new(ExprKind::Binary {
+            op: BinOp::Eq,
+            lhs: Box::new(temp_ident),
+            rhs: Box::new(Expr::new(literal, arm.pattern.span)),
+        }, arm.pattern.span);
+        let then_branch = match block_expr(Vec::new(), body, span).kind {
+            ExprKind::Block(block) => block,
+            _ => unreachable!("block_expr always builds a block"),
+        };
+        let else_branch = self.match_chain(temp, rest, exhaustive).map(Box::new);
+
+        Some(Expr::new(ExprKind::If { cond: Box::new(cond), then_branch, else_branch }, span))
+    }
+
+    /// Whether the arms name every variant of a single declared enum, or
+    /// both `true` and `false`
+    fn covers_all_values(&self, arms: &[MatchArm]) -> bool {
+        let patterns = || arms.iter().map(|arm| &arm.pattern.kind);
+        if patterns().all(|p| matches!(p, PatternKind::Bool(_))) {
+            return [true, false].iter().all(|b| patterns().any(|p| matches!(p, PatternKind::Bool(v) if v == b)));
+        }
+        let mut enum_name = None;
+        let mut seen = Vec::new();
+        for pattern in patterns() {
+            match pattern {
+                PatternKind::Variant { enum_name: name, variant } => {
+                    if enum_name.get_or_insert(name) != &name {
+                        return false;
+                    }
+                    seen.push(variant);
+                }
+                _ => return false,
+            }
+        }
+        match enum_name.and_then(|name| self.enums.get(name)) {
+            Some(variants) => variants.iter().all(|v| seen.contains(&v)),
+            None => false,
+        }
+    }
+}
+
+fn let_stmt(name: &str, value: Expr, span: Span) -> Stmt {
+    Stmt::new(StmtKind::Let { name: name.to_string(), ty: None, value }, span)
+}
+
+/// Wrap `stmts` and a value expression into a block expression, reusing
+/// the value's own block when it already is one
+fn block_expr(stmts: Vec<Stmt>, value: Expr, span: Span) -> Expr {
+    let block = match value.kind {
+        ExprKind::Block(mut inner) if stmts.is_empty() => {
+            inner.span = value.span;
+            inner
+        }
+        _ => Block { stmts, tail: Some(Box::new(value)), span },
+    };
+    Expr::new(ExprKind::Block(block), span)
+}
+
+/// Desugar every function body of a module
+pub fn desugar_module(module: &Module) -> Module {
+    let mut desugarer = Desugarer::new(module);
+    Module {
+        name: module.name.clone(),
+        capability: module.capability.clone(),
+        imports: module.imports.clone(),
+        effects: module.effects.clone(),
+        statements: module.statements.iter().map(|s| desugarer.desugar_stmt(s)).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn desugar_fn(src: &str) -> Block {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let module = desugar_module(&module);
+        module.statements.into_iter()
+            .find_map(|s| match s.kind {
+                StmtKind::Function { body, .. } => body,
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    fn contains_kind(expr: &Expr, pred: &dyn Fn(&ExprKind) -> bool) -> bool {
+        if pred(&expr.kind) {
+            return true;
+        }
+        match &expr.kind {
+            ExprKind::Block(b) => block_contains(b, pred),
+            ExprKind::If { cond, then_branch, else_branch } => {
+                contains_kind(cond, pred)
+                    || block_contains(then_branch, pred)
+                    || else_branch.as_ref().is_some_and(|e| contains_kind(e, pred))
+            }
+            ExprKind::While { cond, body } => contains_kind(cond, pred) || block_contains(body, pred),
+            ExprKind::Binary { lhs, rhs, .. } => contains_kind(lhs, pred) || contains_kind(rhs, pred),
+            _ => false,
+        }
+    }
+
+    fn block_contains(block: &Block, pred: &dyn Fn(&ExprKind) -> bool) -> bool {
+        block.stmts.iter().any(|s| match &s.kind {
+            StmtKind::Let { value, .. } | StmtKind::Assign { value, .. } => contains_kind(value, pred),
+            StmtKind::Expression(e) => contains_kind(e, pred),
+            _ => false,
+        }) || block.tail.as_ref().is_some_and(|e| contains_kind(e, pred))
+    }
+
+    #[test]
+    fn test_match_becomes_if_chain() {
+        let body = desugar_fn("module m
+enum Color { Red, Green, Blue }
+fn f(c: Color) -> Int {
+    match c { Color::Red => 1, Color::Green => 2, Color::Blue => 3 }
+}");
+        let is_match = |k: &ExprKind| matches!(k, ExprKind::Match { .. });
+        let is_variant = |k: &ExprKind| matches!(k, ExprKind::Variant { .. });
+        assert!(!block_contains(&body, &is_match));
+        assert!(!block_contains(&body, &is_variant));
+
+        // Two tests; the last variant arm is the unconditional else
+        let ExprKind::Block(outer) = &body.tail.as_ref().unwrap().kind else { panic!() };
+        let ExprKind::If { else_branch, .. } = &outer.tail.as_ref().unwrap().kind else { panic!() };
+        let ExprKind::If { else_branch, .. } = &else_branch.as_ref().unwrap().kind else { panic!() };
+        assert!(matches!(else_branch.as_ref().unwrap().kind, ExprKind::Block(_)));
+    }
+
+    #[test]
+    fn test_variants_become_discriminants() {
+        let body = desugar_fn("module m
+enum Color { Red, Green, Blue }
+fn f() -> Color { Color::Blue }");
+        assert!(matches!(body.tail.unwrap().kind, ExprKind::Number(2)));
+    }
+
+    #[test]
+    fn test_for_becomes_while() {
+        let body = desugar_fn("module m
+fn total(xs: Array<Int>) -> Int {
+    let sum = 0;
+    for x in xs { sum = sum + x; }
+    sum
+}");
+        let is_for = |k: &ExprKind| matches!(k, ExprKind::For { .. });
+        let is_while = |k: &ExprKind| matches!(k, ExprKind::While { .. });
+        let is_len = |k: &ExprKind| matches!(k, ExprKind::Call { func, .. }
+            if matches!(&func.kind, ExprKind::Ident(f) if f == LEN_BUILTIN));
+        assert!(!block_contains(&body, &is_for));
+        assert!(block_contains(&body, &is_while));
+        assert!(block_contains(&body, &is_len));
+    }
+
+    #[test]
+    fn test_binding_and_wildcard_arms() {
+        let body = desugar_fn("module m
+fn f(n: Int) -> Int {
+    match n { 0 => 1, other => other * 2 }
+}");
+        let ExprKind::Block(outer) = &body.tail.as_ref().unwrap().kind else { panic!() };
+        let ExprKind::If { else_branch, .. } = &outer.tail.as_ref().unwrap().kind else { panic!() };
+        let ExprKind::Block(binding) = &else_branch.as_ref().unwrap().kind else { panic!() };
+        assert!(matches!(&binding.stmts[0].kind, StmtKind::Let { name, .. } if name == "other"));
+    }
+}
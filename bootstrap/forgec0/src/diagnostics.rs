@@ -0,0 +1,307 @@
+//! Diagnostics module for Forge Lang - Phase α
+//!
+//! Structured compiler diagnostics shared by the parser, lowering and checkers.
+//! A diagnostic renders either as a rustc-style source snippet with caret
+//! underlines, or as plain `file:line:col:` lines for CI logs.
+
+use std::fmt;
+
+use crate::span::Span;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A span with an attached message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+/// A single compiler message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<String>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+/// Output format for rendered diagnostics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStyle {
+    /// Source snippet with caret underlines
+    Rich,
+    /// One `file:line:col:` line per message
+    Plain,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.into(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Diagnostic::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Diagnostic::new(Severity::Warning, message)
+    }
+
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_string());
+        self
+    }
+
+    /// Add the label the diagnostic points at
+    pub fn with_primary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: true });
+        self
+    }
+
+    /// Add a label giving extra context
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into(), primary: false });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Span of the first primary label, if any
+    pub fn primary_span(&self) -> Option<Span> {
+        self.labels.iter().find(|l| l.primary).map(|l| l.span)
+    }
+
+    fn header(&self) -> String {
+        match &self.code {
+            Some(code) => format!("{}[{}]: {}", self.severity, code, self.message),
+            None => format!("{}: {}", self.severity, self.message),
+        }
+    }
+
+    /// Render against the file the spans refer to
+    pub fn render(&self, source: &SourceFile, style: RenderStyle) -> String {
+        match style {
+            RenderStyle::Rich => self.render_rich(source),
+            RenderStyle::Plain => self.render_plain(source),
+        }
+    }
+
+    fn render_plain(&self, source: &SourceFile) -> String {
+        let mut output = String::new();
+        match self.primary_span() {
+            Some(span) => output.push_str(&format!(
+                "{}:{}:{}: {}\n", source.name, span.line, span.col, self.header()
+            )),
+            None => output.push_str(&format!("{}: {}\n", source.name, self.header())),
+        }
+        for label in &self.labels {
+            if !label.message.is_empty() {
+                output.push_str(&format!(
+                    "{}:{}:{}: {}\n", source.name, label.span.line, label.span.col, label.message
+                ));
+            }
+        }
+        for note in &self.notes {
+            output.push_str(&format!("  note: {}\n", note));
+        }
+        if let Some(help) = &self.help {
+            output.push_str(&format!("  help: {}\n", help));
+        }
+        output
+    }
+
+    fn render_rich(&self, source: &SourceFile) -> String {
+        let mut output = format!("{}\n", self.header());
+
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|l| (l.span.line, !l.primary, l.span.col));
+        let max_line = labels.iter().map(|l| l.span.line).max().unwrap_or(1);
+        let width = max_line.to_string().len();
+        let gutter = " ".repeat(width);
+
+        match self.primary_span().or_else(|| labels.first().map(|l| l.span)) {
+            Some(span) => output.push_str(&format!(
+                "{}--> {}:{}:{}\n", gutter, source.name, span.line, span.col
+            )),
+            None => output.push_str(&format!("{}--> {}\n", gutter, source.name)),
+        }
+
+        if !labels.is_empty() {
+            output.push_str(&format!("{} |\n", gutter));
+        }
+        let mut last_line = None;
+        for label in &labels {
+            let line = label.span.line;
+            let text = source.line_text(line);
+            if last_line != Some(line) {
+                let row = format!("{:>width$} | {}", line, text, width = width);
+                output.push_str(row.trim_end());
+                output.push('\n');
+                last_line = Some(line);
+            }
+            let col = label.span.col.max(1) as usize;
+            let marker = if label.primary { "^" } else { "-" };
+            let underline = marker.repeat(source.underline_width(label.span, text, col));
+            let mut row = format!("{} | {}{}", gutter, " ".repeat(col - 1), underline);
+            if !label.message.is_empty() {
+                row.push(' ');
+                row.push_str(&label.message);
+            }
+            output.push_str(row.trim_end());
+            output.push('\n');
+        }
+
+        for note in &self.notes {
+            output.push_str(&format!("{} = note: {}\n", gutter, note));
+        }
+        if let Some(help) = &self.help {
+            output.push_str(&format!("{} = help: {}\n", gutter, help));
+        }
+        output
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.header())
+    }
+}
+
+/// A named source file that diagnostics are rendered against
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub text: String,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        SourceFile { name: name.into(), text: text.into() }
+    }
+
+    /// Text of a 1-based line, without its line terminator
+    pub fn line_text(&self, line: u32) -> &str {
+        self.text
+            .lines()
+            .nth(line.saturating_sub(1) as usize)
+            .unwrap_or("")
+    }
+
+    /// Number of columns to underline for `span`, clipped to its first line
+    fn underline_width(&self, span: Span, line_text: &str, col: usize) -> usize {
+        let remaining = line_text.chars().count().saturating_sub(col - 1);
+        let spanned = self
+            .text
+            .get(span.start..span.end.min(self.text.len()))
+            .map(|s| s.chars().take_while(|&c| c != '\n').count())
+            .unwrap_or(0);
+        spanned.min(remaining).max(1)
+    }
+}
+
+/// Render a list of diagnostics, one after another
+pub fn render_all(diagnostics: &[Diagnostic], source: &SourceFile, style: RenderStyle) -> String {
+    let mut output = String::new();
+    for (i, diag) in diagnostics.iter().enumerate() {
+        if i > 0 && style == RenderStyle::Rich {
+            output.push('\n');
+        }
+        output.push_str(&diag.render(source, style));
+    }
+    output
+}
+
+/// Whether any diagnostic in the list is an error
+pub fn has_errors(diagnostics: &[Diagnostic]) -> bool {
+    diagnostics.iter().any(Diagnostic::is_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> SourceFile {
+        SourceFile::new("demo.fg", "module demo\nfn f(x Int) -> Int\n")
+    }
+
+    #[test]
+    fn test_render_rich() {
+        let diag = Diagnostic::error("expected `:`, found identifier `Int`")
+            .with_code("E0001")
+            .with_primary(Span::new(19, 22, 2, 8), "expected `:`")
+            .with_secondary(Span::new(17, 18, 2, 6), "parameter declared here")
+            .with_help("write the parameter as `x: Int`");
+
+        let expected = "\
+error[E0001]: expected `:`, found identifier `Int`
+ --> demo.fg:2:8
+  |
+2 | fn f(x Int) -> Int
+  |        ^^^ expected `:`
+  |      - parameter declared here
+  = help: write the parameter as `x: Int`
+";
+        assert_eq!(diag.render(&source(), RenderStyle::Rich), expected);
+    }
+
+    #[test]
+    fn test_render_plain() {
+        let diag = Diagnostic::warning("unused parameter")
+            .with_primary(Span::new(17, 18, 2, 6), "")
+            .with_note("prefix it with `_` to silence this warning");
+
+        let expected = "\
+demo.fg:2:6: warning: unused parameter
+  note: prefix it with `_` to silence this warning
+";
+        assert_eq!(diag.render(&source(), RenderStyle::Plain), expected);
+    }
+
+    #[test]
+    fn test_underline_at_end_of_file() {
+        let diag = Diagnostic::error("unexpected end of file")
+            .with_primary(Span::new(31, 31, 3, 1), "");
+        let rendered = diag.render(&source(), RenderStyle::Rich);
+        assert!(rendered.contains("3 |\n  | ^\n"));
+        assert!(has_errors(&[diag]));
+    }
+}
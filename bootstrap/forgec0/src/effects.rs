@@ -0,0 +1,618 @@
+//! Effect checker - enforces declared `!{...}` capabilities
+//!
+//! Each call contributes its callee's effect to the caller. The join over a
+//! function body must be subsumed by the function's declared capability, and
+//! every declared capability by the module's. Functions without a capability
+//! get the least effect their body needs, inferred over the call graph (see
+//! docs/rfcs/RFC-effect-lattice.md); an empty body infers `pure`.
+//!
+//! Effects are sets ordered by the module's [`EffectLattice`], so `fs`
+//! declared with `effect fs <: io;` fits an `io` capability while `fs` and
+//! `clock` declared side by side are unrelated. Invalid declarations are
+//! reported here (E0104).
+//!
+//! A function may take effect variables, `fn map<e>(f: fn(Int) -> Int !{e})
+//! -> ... !{alloc, e}`. Inside its body a variable is an effect unrelated
+//! to every other; at each call it is bound to the effects of the function
+//! arguments whose parameter types mention it, so `map` applied to a pure
+//! function only needs `alloc`. A variable no parameter mentions could
+//! never be bound (E0105).
+
+use std::collections::HashMap;
+
+use crate::ast::*;
+use crate::diagnostics::Diagnostic;
+use crate::span::Span;
+
+/// Effect signature of a module-level function
+#[derive(Debug)]
+struct Signature<'a> {
+    /// Declared effect, or the current inference estimate
+    effect: Effect,
+    effect_params: &'a [String],
+    params: &'a [(String, Type)],
+    body: Option<&'a Block>,
+}
+
+/// A call found in a function body, with the effect it contributes
+#[derive(Debug, Clone)]
+pub struct CallSite {
+    pub callee: String,
+    pub effect: Effect,
+    pub span: Span,
+}
+
+/// Effect of a callable parameter type; `None` for non-function types
+fn function_type_effect(ty: &Type) -> Option<Effect> {
+    match &ty.kind {
+        TypeKind::Function { capability, .. } => {
+            Some(capability.as_ref().map_or(Effect::pure(), Capability::effect))
+        }
+        _ => None,
+    }
+}
+
+/// Walks function bodies, resolving callees to their effects
+struct EffectChecker<'a> {
+    lattice: EffectLattice,
+    signatures: HashMap<&'a str, Signature<'a>>,
+    /// Local names in scope: `Some` for callables, `None` for values that
+    /// shadow a function of the same name
+    locals: HashMap<String, Option<Effect>>,
+    /// Functions with a body but no capability
+    unannotated: Vec<&'a str>,
+    calls: Vec<CallSite>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> EffectChecker<'a> {
+    fn new(module: &'a Module) -> Self {
+        let lattice = module.effect_lattice();
+        let mut signatures = HashMap::new();
+        let mut unannotated = Vec::new();
+        for stmt in &module.statements {
+            if let StmtKind::Function { name, effect_params, params, capability, body, .. } = &stmt.kind {
+                signatures.insert(name.as_str(), Signature {
+                    effect: capability.as_ref().map_or(Effect::pure(), |cap| lattice.normalize(&cap.effect())),
+                    effect_params,
+                    params,
+                    body: body.as_ref(),
+                });
+                if capability.is_none() && body.is_some() {
+                    unannotated.push(name.as_str());
+                }
+            }
+        }
+        let mut checker = EffectChecker {
+            lattice,
+            signatures,
+            locals: HashMap::new(),
+            unannotated,
+            calls: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        checker.infer();
+        checker
+    }
+
+    /// Raise the estimates of unannotated functions, starting from `pure`,
+    /// until no body needs more. Estimates only grow and the lattice is
+    /// finite, so recursive call graphs reach a fixpoint.
+    fn infer(&mut self) {
+        loop {
+            let mut changed = false;
+            for name in self.unannotated.clone() {
+                let sig = &self.signatures[name];
+                let (params, body) = (sig.params, sig.body.expect("unannotated function has a body"));
+                let calls = self.body_calls(params, body);
+                let effect = self.join_calls(&calls);
+                let sig = self.signatures.get_mut(name).expect("unannotated function has a signature");
+                if sig.effect != effect {
+                    sig.effect = effect;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        // Argument checks are reported once, by the checking pass
+        self.diagnostics.clear();
+    }
+
+    /// Declared or inferred effect of a module-level function
+    fn effect_of(&self, name: &str) -> Effect {
+        self.signatures.get(name).map_or(Effect::pure(), |sig| sig.effect.clone())
+    }
+
+    /// Effect of calling `name`, if it names something callable
+    fn callable(&self, name: &str) -> Option<Effect> {
+        match self.locals.get(name) {
+            Some(local) => local.clone(),
+            None => self.signatures.get(name).map(|sig| sig.effect.clone()),
+        }
+    }
+
+    /// Effect of calling `name` with `args`, with the callee's effect
+    /// variables instantiated from the arguments
+    fn call_effect(&self, name: &str, args: &[Expr]) -> Option<Effect> {
+        let effect = self.callable(name)?;
+        match self.signatures.get(name) {
+            Some(sig) if !self.locals.contains_key(name) && !sig.effect_params.is_empty() => {
+                Some(self.lattice.normalize(&effect.substitute(&self.bind(sig, args))))
+            }
+            _ => Some(effect),
+        }
+    }
+
+    /// Bind the effect variables of `sig` for a call with `args`. Arguments
+    /// whose effect is not known statically could be anything.
+    fn bind(&self, sig: &Signature, args: &[Expr]) -> HashMap<String, Effect> {
+        let pairs = sig.params.iter().zip(args).filter_map(|((_, ty), arg)| {
+            let param = function_type_effect(ty)?;
+            let arg = match &arg.kind {
+                ExprKind::Ident(name) => self.callable(name),
+                _ => None,
+            };
+            Some((param, arg.unwrap_or_else(|| self.lattice.top())))
+        });
+        let mut bindings = bind_effect_vars(sig.effect_params, pairs);
+        for var in sig.effect_params {
+            bindings.entry(var.clone()).or_insert_with(|| self.lattice.top());
+        }
+        bindings
+    }
+
+    /// Collect the calls made by a function body
+    fn body_calls(&mut self, params: &[(String, Type)], body: &Block) -> Vec<CallSite> {
+        self.locals = params.iter()
+            .map(|(name, ty)| (name.clone(), function_type_effect(ty)))
+            .collect();
+        self.calls.clear();
+        self.visit_block(body);
+        std::mem::take(&mut self.calls)
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        let saved = self.locals.clone();
+        for stmt in &block.stmts {
+            self.visit_stmt(stmt);
+        }
+        if let Some(tail) = &block.tail {
+            self.visit_expr(tail);
+        }
+        self.locals = saved;
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } => {
+                self.visit_expr(value);
+                let effect = match &value.kind {
+                    ExprKind::Ident(alias) => self.callable(alias),
+                    _ => None,
+                };
+                self.locals.insert(name.clone(), effect);
+            }
+            StmtKind::Assign { value, .. } | StmtKind::Expression(value) => self.visit_expr(value),
+            StmtKind::Function { .. } | StmtKind::Enum { .. } | StmtKind::Error => {}
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Call { func, args } => {
+                for arg in args {
+                    self.visit_expr(arg);
+                }
+                match &func.kind {
+                    ExprKind::Ident(name) => {
+                        if let Some(effect) = self.call_effect(name, args) {
+                            self.calls.push(CallSite {
+                                callee: name.clone(),
+                                effect,
+                                span: expr.span,
+                            });
+                        }
+                        self.check_function_arguments(name, args);
+                    }
+                    _ => self.visit_expr(func),
+                }
+            }
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.visit_expr(lhs);
+                self.visit_expr(rhs);
+            }
+            ExprKind::Unary { operand, .. } => self.visit_expr(operand),
+            ExprKind::Array(items) => {
+                for item in items {
+                    self.visit_expr(item);
+                }
+            }
+            ExprKind::Index { base, index } => {
+                self.visit_expr(base);
+                self.visit_expr(index);
+            }
+            ExprKind::Block(block) => self.visit_block(block),
+            ExprKind::If { cond, then_branch, else_branch } => {
+                self.visit_expr(cond);
+                self.visit_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.visit_expr(else_branch);
+                }
+            }
+            ExprKind::While { cond, body } => {
+                self.visit_expr(cond);
+                self.visit_block(body);
+            }
+            ExprKind::For { var, iter, body } => {
+                self.visit_expr(iter);
+                let saved = self.locals.clone();
+                self.locals.insert(var.clone(), None);
+                self.visit_block(body);
+                self.locals = saved;
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                self.visit_expr(scrutinee);
+                for arm in arms {
+                    let saved = self.locals.clone();
+                    if let PatternKind::Binding(name) = &arm.pattern.kind {
+                        self.locals.insert(name.clone(), None);
+                    }
+                    self.visit_expr(&arm.body);
+                    self.locals = saved;
+                }
+            }
+            ExprKind::Ident(_)
+            | ExprKind::Number(_)
+            | ExprKind::String(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Variant { .. }
+            | ExprKind::IntentBlock { .. }
+            | ExprKind::Error => {}
+        }
+    }
+
+    /// Functions passed as arguments must fit the parameter's capability
+    fn check_function_arguments(&mut self, callee: &str, args: &[Expr]) {
+        if self.locals.contains_key(callee) {
+            return;
+        }
+        let Some(sig) = self.signatures.get(callee) else {
+            return;
+        };
+        let bindings = self.bind(sig, args);
+        let mut errors = Vec::new();
+        for ((_, param_ty), arg) in sig.params.iter().zip(args) {
+            let ExprKind::Ident(arg_name) = &arg.kind else {
+                continue;
+            };
+            let (Some(allowed), Some(actual)) = (function_type_effect(param_ty), self.callable(arg_name)) else {
+                continue;
+            };
+            let allowed = self.lattice.normalize(&allowed.substitute(&bindings));
+            if !self.lattice.subsumes(&actual, &allowed) {
+                errors.push(
+                    Diagnostic::error(format!(
+                        "`{}` requires `{}`, but `{}` only accepts `!{{{}}}` functions here",
+                        arg_name, actual, callee, allowed
+                    ))
+                    .with_code("E0103")
+                    .with_primary(arg.span, format!("requires `{}`", actual))
+                    .with_secondary(param_ty.span, format!("parameter allows `{}`", allowed)),
+                );
+            }
+        }
+        self.diagnostics.extend(errors);
+    }
+
+    /// Join of the effects of a list of calls; `pure` when there are none
+    fn join_calls(&self, calls: &[CallSite]) -> Effect {
+        calls.iter().fold(Effect::pure(), |acc, call| self.lattice.join(&acc, &call.effect))
+    }
+}
+
+/// Report effect variables that no parameter type mentions
+fn check_effect_params(name: &str, effect_params: &[String], params: &[(String, Type)], span: Span) -> Vec<Diagnostic> {
+    effect_params.iter()
+        .filter(|var| !params.iter().any(|(_, ty)| function_type_effect(ty).is_some_and(|e| e.names().any(|n| n == var.as_str()))))
+        .map(|var| {
+            Diagnostic::error(format!(
+                "effect variable `{}` of `{}` is not used by any parameter, so no call can instantiate it",
+                var, name
+            ))
+            .with_code("E0105")
+            .with_primary(span, format!("`{}` declared here", var))
+            .with_help(format!("mention `{}` in the capability of a function-typed parameter", var))
+        })
+        .collect()
+}
+
+/// Report `effect` declarations that do not extend the lattice: repeated
+/// names, and effects declared under one not declared before them
+fn check_declarations(module: &Module) -> Vec<Diagnostic> {
+    let mut lattice = EffectLattice::default();
+    module.effects.iter()
+        .filter_map(|decl| lattice.declare(&decl.name, decl.parent.as_deref()).err().map(|message| {
+            Diagnostic::error(message)
+                .with_code("E0104")
+                .with_primary(decl.span, "invalid effect declaration")
+        }))
+        .collect()
+}
+
+/// Effect of every function in the module: the declared one, or the
+/// inferred one for functions without a capability
+pub fn infer_effects(module: &Module) -> HashMap<String, Effect> {
+    let checker = EffectChecker::new(module);
+    checker.signatures.iter()
+        .map(|(name, sig)| (name.to_string(), sig.effect.clone()))
+        .collect()
+}
+
+/// Check every function body against its declared capability and every
+/// function's effect against the module's capability
+pub fn check_effects(module: &Module) -> Vec<Diagnostic> {
+    let mut checker = EffectChecker::new(module);
+    let mut diagnostics = check_declarations(module);
+
+    // A module capability that lists no effects only constrains budgets
+    let module_cap = module.capability.as_ref().filter(|cap| !cap.effects.is_empty());
+
+    for stmt in &module.statements {
+        let StmtKind::Function { name, effect_params, params, capability, body, .. } = &stmt.kind else {
+            continue;
+        };
+        let declared = checker.effect_of(name);
+        diagnostics.extend(check_effect_params(name, effect_params, params, stmt.span));
+
+        if let (Some(module_cap), Some(cap)) = (module_cap, capability) {
+            let allowed = checker.lattice.normalize(&module_cap.effect());
+            if !checker.lattice.subsumes(&declared, &allowed) {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "`{}` declares `{}`, which module `{}` does not allow",
+                        name, declared, module.name
+                    ))
+                    .with_code("E0102")
+                    .with_primary(cap.span, format!("declares `{}`", declared))
+                    .with_secondary(module_cap.span, format!("module allows at most `{}`", allowed)),
+                );
+            }
+        }
+
+        let Some(body) = body else {
+            continue;
+        };
+        let calls = checker.body_calls(params, body);
+
+        let Some(cap) = capability else {
+            // Inferred effects fit by construction; only the module bounds them
+            let Some(module_cap) = module_cap else {
+                continue;
+            };
+            let allowed = checker.lattice.normalize(&module_cap.effect());
+            for call in calls.iter().filter(|call| !checker.lattice.subsumes(&call.effect, &allowed)) {
+                diagnostics.push(
+                    Diagnostic::error(format!(
+                        "`{}` is inferred to require `{}`, which module `{}` does not allow",
+                        name, call.effect, module.name
+                    ))
+                    .with_code("E0102")
+                    .with_primary(call.span, format!("requires `{}`", call.effect))
+                    .with_secondary(module_cap.span, format!("module allows at most `{}`", allowed)),
+                );
+            }
+            continue;
+        };
+        if checker.lattice.subsumes(&checker.join_calls(&calls), &declared) {
+            continue;
+        }
+        for call in calls.iter().filter(|call| !checker.lattice.subsumes(&call.effect, &declared)) {
+            diagnostics.push(
+                Diagnostic::error(format!(
+                    "call to `{}` requires `{}`, which `{}` does not declare",
+                    call.callee, call.effect, name
+                ))
+                .with_code("E0101")
+                .with_primary(call.span, format!("requires `{}`", call.effect))
+                .with_secondary(cap.span, format!("`{}` declared here", declared))
+                .with_help(format!("add `{}` to the capability of `{}`", call.effect, name)),
+            );
+        }
+    }
+
+    diagnostics.extend(checker.diagnostics);
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn check(src: &str) -> Vec<Diagnostic> {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        check_effects(&module)
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().filter_map(|d| d.code.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_callee_effects_within_capability() {
+        let diagnostics = check("module m
+fn alloc_buffer() -> Int !{alloc}
+fn write(x: Int) !{io}
+fn pipeline() !{io} {
+    let buf = alloc_buffer();
+    write(buf)
+}");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_net_call_from_io_function() {
+        let diagnostics = check("module m
+fn fetch() -> Text !{net}
+fn local_only() -> Text !{io} {
+    fetch()
+}");
+        assert_eq!(codes(&diagnostics), vec!["E0101"]);
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 4);
+        assert_eq!(diagnostics[0].message, "call to `fetch` requires `net`, which `local_only` does not declare");
+    }
+
+    #[test]
+    fn test_unannotated_callee_uses_inferred_effect() {
+        let diagnostics = check("module m
+fn log(x: Int) !{io}
+fn helper(x: Int) -> Int {
+    if x > 0 { log(x); }
+    x
+}
+fn compute(x: Int) -> Int !{alloc} { helper(x) }");
+        assert_eq!(codes(&diagnostics), vec!["E0101"]);
+        assert!(diagnostics[0].message.starts_with("call to `helper` requires `io`"));
+    }
+
+    #[test]
+    fn test_infer_effects() {
+        let (module, _) = Parser::new("module m
+fn alloc_buffer() -> Int !{alloc}
+fn write(x: Int) !{io}
+fn pure_add(x: Int, y: Int) -> Int { x + y }
+fn pipeline() {
+    let buf = alloc_buffer();
+    write(buf);
+}
+fn fetch() -> Text !{net}
+fn apply(f: fn() -> Text !{net}) -> Text { f() }").parse_program();
+        let effects = infer_effects(&module);
+        assert_eq!(effects["pure_add"], Effect::pure());
+        assert_eq!(effects["pipeline"], Effect::named("io"));
+        assert_eq!(effects["apply"], Effect::named("net"));
+        assert_eq!(effects["write"], Effect::named("io"));
+    }
+
+    #[test]
+    fn test_infer_through_recursion() {
+        let (module, _) = Parser::new("module m
+fn fetch() -> Text !{net}
+fn even(n: Int) -> Bool { if n == 0 { true } else { odd(n - 1) } }
+fn odd(n: Int) -> Bool { if n == 0 { fetch(); false } else { even(n - 1) } }
+fn spin(n: Int) -> Int { spin(n) }").parse_program();
+        let effects = infer_effects(&module);
+        assert_eq!(effects["even"], Effect::named("net"));
+        assert_eq!(effects["odd"], Effect::named("net"));
+        assert_eq!(effects["spin"], Effect::pure());
+    }
+
+    #[test]
+    fn test_inferred_effect_bounded_by_module() {
+        let diagnostics = check("module m !{io}
+fn fetch() -> Text !{net}
+fn wrapper() -> Text { fetch() }");
+        // Once for the declaration, once for the inferred caller
+        assert_eq!(codes(&diagnostics), vec!["E0102", "E0102"]);
+        assert_eq!(diagnostics[1].primary_span().unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_function_parameter_effects() {
+        let diagnostics = check("module m
+fn network_fetch() -> Text !{net}
+fn local_only(f: fn() -> Text !{io}) -> Text !{io} {
+    f()
+}
+fn main() -> Text !{net} {
+    local_only(network_fetch)
+}");
+        assert_eq!(codes(&diagnostics), vec!["E0103"]);
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 7);
+    }
+
+    #[test]
+    fn test_module_capability_bounds_functions() {
+        let diagnostics = check("module m !{io}
+fn read() -> Text !{io}
+fn fetch() -> Text !{net}
+fn compute() -> Int");
+        assert_eq!(codes(&diagnostics), vec!["E0102"]);
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 3);
+    }
+
+    #[test]
+    fn test_budget_only_module_capability() {
+        let diagnostics = check("module m !{energy ≤ 10mJ}\nfn fetch() -> Text !{net}");
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn test_declared_effect_hierarchy() {
+        let src = "module m
+effect fs <: io;
+effect clock;
+effect tmp <: fs;
+fn read() -> Text !{fs}
+fn now() -> Int !{clock}
+fn scratch() !{tmp}
+fn load() -> Text !{io} { scratch(); read() }
+fn stamp() -> Int !{io} { now() }
+fn both() -> Int !{fs, clock} { read(); now() }
+fn sample() -> Int { now(); read(); 1 }";
+        let diagnostics = check(src);
+        assert_eq!(codes(&diagnostics), vec!["E0101"]);
+        assert_eq!(diagnostics[0].message, "call to `now` requires `clock`, which `stamp` does not declare");
+
+        let (module, _) = Parser::new(src).parse_program();
+        assert_eq!(infer_effects(&module)["sample"].to_string(), "clock, fs");
+    }
+
+    #[test]
+    fn test_invalid_effect_declarations() {
+        let diagnostics = check("module m
+effect gpu;
+effect gpu;
+effect cache <: disk;
+effect disk <: io;
+effect pure;
+fn f() !{cache, disk}");
+        assert_eq!(codes(&diagnostics), vec!["E0104", "E0104", "E0104"]);
+        assert_eq!(diagnostics[1].message, "`disk` must be declared before `cache` can extend it");
+    }
+
+    #[test]
+    fn test_effect_polymorphism() {
+        let src = "module m
+fn log(x: Int) -> Int !{io}
+fn fetch(x: Int) -> Int !{net}
+fn double(x: Int) -> Int { x * 2 }
+fn map<e>(xs: Array<Int>, f: fn(Int) -> Int !{e}) -> Array<Int> !{alloc, e} { [f(xs[0])] }
+fn compose<e>(f: fn(Int) -> Int !{e}, g: fn(Int) -> Int !{e}, x: Int) -> Int !{e} { g(f(x)) }
+fn pure_map(xs: Array<Int>) -> Array<Int> !{alloc} { map(xs, double) }
+fn logged(xs: Array<Int>) -> Array<Int> !{alloc} { map(xs, log) }
+fn both(x: Int) -> Int !{io} { compose(double, log, x) }
+fn inferred(xs: Array<Int>) -> Array<Int> { map(xs, fetch) }
+fn again<e>(xs: Array<Int>, f: fn(Int) -> Int !{e}) -> Array<Int> !{alloc, e} { map(xs, f) }";
+        let diagnostics = check(src);
+        assert_eq!(codes(&diagnostics), vec!["E0101"]);
+        assert_eq!(diagnostics[0].message, "call to `map` requires `io`, which `logged` does not declare");
+
+        let (module, _) = Parser::new(src).parse_program();
+        let effects = infer_effects(&module);
+        assert_eq!(effects["inferred"], Effect::named("net"));
+        assert_eq!(effects["map"].to_string(), "alloc, e");
+    }
+
+    #[test]
+    fn test_effect_variable_checks() {
+        let diagnostics = check("module m
+fn apply<e>(f: fn(Int) -> Int !{e}, x: Int) -> Int !{alloc} { f(x) }
+fn stray<e>(x: Int) -> Int !{e} { x }");
+        assert_eq!(codes(&diagnostics), vec!["E0101", "E0105"]);
+        assert_eq!(diagnostics[0].message, "call to `f` requires `e`, which `apply` does not declare");
+    }
+}
@@ -0,0 +1,936 @@
+//! Tree-walking interpreter over the desugared AST
+//!
+//! Functions declared without a body are host functions, provided by a
+//! `Host`. Declared effects are enforced at runtime: every call runs with
+//! the meet of its caller's allowed effect and the callee's own (declared
+//! or inferred) effect, and a host call whose effect is not allowed there
+//! fails with `RuntimeError::EffectViolation`, so an `io` or `net` host
+//! function can never run from a `pure` context.
+//!
+//! Resource budgets are enforced the same way. Each function with a
+//! budget, and the module when it has one, runs under a meter, and running
+//! over any limit fails with `RuntimeError::BudgetExceeded`. Latency is the
+//! time a `Clock` reports since the function was entered. Tokens and
+//! energy are only spent by host calls, which charge every running meter:
+//!
+//! * with what `Host::last_cost` reports the call actually used, for hosts
+//!   that measure it (`Host::measures_costs`);
+//! * otherwise with the `tokens` and `energy` bounds the host function
+//!   declares. This is the worst case the static budget checker assumes
+//!   too, and it means such a call always uses exactly its own budget, so
+//!   only its callers' meters can run out. Being known in advance, this
+//!   cost is charged before the call, and a call it would take over a
+//!   budget fails without running.
+//!
+//! None of this carries over to compiled code: the WASM backend emits no
+//! meters and calls imports directly, so budgets there are only as good
+//! as the static checks and whatever metering the host does itself.
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::ast::*;
+use crate::budgets::Cost;
+use crate::desugar::{desugar_module, LEN_BUILTIN};
+use crate::diagnostics::Diagnostic;
+use crate::effects::infer_effects;
+use crate::span::Span;
+
+/// Runtime value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unit,
+    Int(i64),
+    Text(String),
+    Bool(bool),
+    Array(Vec<Value>),
+    /// Module-level function, by name
+    Function(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Function(name) => write!(f, "fn {}", name),
+        }
+    }
+}
+
+/// Error that stops evaluation
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeError {
+    /// Host call needing more than the calling context allows
+    EffectViolation { callee: String, effect: Effect, allowed: Effect, span: Span },
+    /// Call to a host function the host does not provide, or to no function
+    UnknownFunction { name: String, span: Span },
+    /// Host function reported a failure
+    Host { callee: String, message: String, span: Span },
+    IndexOutOfBounds { index: i64, len: usize, span: Span },
+    DivisionByZero { span: Span },
+    Overflow { span: Span },
+    /// Operation on values of the wrong kind; only reachable in programs the
+    /// type checker rejects
+    TypeMismatch { message: String, span: Span },
+    /// Construct the interpreter cannot evaluate, such as an intent block
+    Unsupported { what: String, span: Span },
+    /// A function, or the module, used more of a resource than its
+    /// capability allows
+    BudgetExceeded { function: String, resource: Resource, limit: u64, used: u64, span: Span },
+    /// Calls nested deeper than `MAX_CALL_DEPTH`, e.g. unbounded recursion
+    CallDepthExceeded { callee: String, limit: usize, span: Span },
+}
+
+impl RuntimeError {
+    pub fn span(&self) -> Span {
+        match self {
+            RuntimeError::EffectViolation { span, .. }
+            | RuntimeError::UnknownFunction { span, .. }
+            | RuntimeError::Host { span, .. }
+            | RuntimeError::IndexOutOfBounds { span, .. }
+            | RuntimeError::DivisionByZero { span }
+            | RuntimeError::Overflow { span }
+            | RuntimeError::TypeMismatch { span, .. }
+            | RuntimeError::Unsupported { span, .. }
+            | RuntimeError::BudgetExceeded { span, .. }
+            | RuntimeError::CallDepthExceeded { span, .. } => *span,
+        }
+    }
+
+    /// Stable error code used in rendered diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            RuntimeError::EffectViolation { .. } => "E0601",
+            RuntimeError::UnknownFunction { .. } => "E0602",
+            RuntimeError::Host { .. } => "E0603",
+            RuntimeError::IndexOutOfBounds { .. } => "E0604",
+            RuntimeError::DivisionByZero { .. } | RuntimeError::Overflow { .. } => "E0605",
+            RuntimeError::TypeMismatch { .. } => "E0606",
+            RuntimeError::Unsupported { .. } => "E0607",
+            RuntimeError::BudgetExceeded { .. } => "E0608",
+            RuntimeError::CallDepthExceeded { .. } => "E0609",
+        }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diag = Diagnostic::error(self.to_string()).with_code(self.code());
+        match self {
+            RuntimeError::EffectViolation { allowed, .. } => diag
+                .with_primary(self.span(), format!("only `{}` is allowed here", allowed)),
+            RuntimeError::BudgetExceeded { resource, used, .. } => diag
+                .with_primary(self.span(), format!("{} used by here", resource.amount(*used))),
+            RuntimeError::CallDepthExceeded { limit, .. } => diag
+                .with_primary(self.span(), format!("{} calls deep", limit))
+                .with_help("check that the recursion reaches its base case"),
+            _ => diag.with_primary(self.span(), ""),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::EffectViolation { callee, effect, allowed, .. } => write!(
+                f,
+                "host call to `{}` requires `{}`, but the calling context allows only `{}`",
+                callee, effect, allowed
+            ),
+            RuntimeError::UnknownFunction { name, .. } => write!(f, "no function `{}` to call", name),
+            RuntimeError::Host { callee, message, .. } => write!(f, "host call to `{}` failed: {}", callee, message),
+            RuntimeError::IndexOutOfBounds { index, len, .. } => {
+                write!(f, "index {} is out of bounds for an array of length {}", index, len)
+            }
+            RuntimeError::DivisionByZero { .. } => write!(f, "division by zero"),
+            RuntimeError::Overflow { .. } => write!(f, "integer overflow"),
+            RuntimeError::TypeMismatch { message, .. } => write!(f, "{}", message),
+            RuntimeError::Unsupported { what, .. } => write!(f, "{} cannot be evaluated", what),
+            RuntimeError::BudgetExceeded { function, resource, limit, used, .. } => write!(
+                f,
+                "`{}` used {}, over its budget of {}",
+                function, resource.amount(*used), resource.amount(*limit)
+            ),
+            RuntimeError::CallDepthExceeded { callee, .. } => {
+                write!(f, "call to `{}` nests too deeply", callee)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+pub type RuntimeResult<T> = Result<T, RuntimeError>;
+
+/// Environment providing the functions a module declares without a body
+pub trait Host {
+    /// Run host function `name`; effects were checked by the caller
+    fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, String>;
+
+    /// Whether this host measures what its calls cost. Hosts that do not,
+    /// the default, are charged each host function's declared bounds
+    /// before it runs.
+    fn measures_costs(&self) -> bool {
+        false
+    }
+
+    /// Tokens and energy the most recent `call` used, for hosts that
+    /// measure them. `None` charges the host function's declared bounds
+    /// instead; latency is always measured by the `Clock`.
+    fn last_cost(&mut self) -> Option<Cost> {
+        None
+    }
+}
+
+/// Host for command-line runs: `print` writes its arguments to stdout
+#[derive(Debug, Default)]
+pub struct StdHost;
+
+impl Host for StdHost {
+    fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+        match name {
+            "print" => {
+                let text: Vec<String> = args.iter().map(Value::to_string).collect();
+                println!("{}", text.join(" "));
+                Ok(Value::Unit)
+            }
+            _ => Err(format!("`{}` is not provided by this host", name)),
+        }
+    }
+}
+
+/// Source of time for latency budgets
+pub trait Clock {
+    /// Time since some fixed starting point
+    fn now(&self) -> Duration;
+}
+
+/// Wall-clock time
+#[derive(Debug)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only moves when told to, for deterministic runs. Clones
+/// share the same time, so a test host can advance the interpreter's clock.
+#[derive(Debug, Clone, Default)]
+pub struct FakeClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl FakeClock {
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+/// Deepest nesting of calls before evaluation stops with
+/// `RuntimeError::CallDepthExceeded` instead of overflowing the Rust stack
+pub const MAX_CALL_DEPTH: usize = 1000;
+
+/// Stack for a thread running the interpreter to reach `MAX_CALL_DEPTH`.
+/// Each call takes tens of KiB of Rust stack in debug builds, so the
+/// 2 MiB of a default spawned thread only covers a few dozen.
+pub const INTERPRETER_STACK_SIZE: usize = 64 << 20;
+
+/// Module-level function as the interpreter sees it
+struct Function {
+    params: Vec<String>,
+    body: Option<Block>,
+    effect: Effect,
+    effect_params: Vec<String>,
+    /// Declared effect of each function-typed parameter
+    param_effects: Vec<Option<Effect>>,
+    budget: ResourceBudget,
+}
+
+/// Budget being enforced while a function, or the module, runs
+struct Meter {
+    function: String,
+    budget: ResourceBudget,
+    started: Duration,
+    /// Tokens and energy charged so far; latency is measured instead
+    used: Cost,
+}
+
+fn has_limits(budget: &ResourceBudget) -> bool {
+    Resource::ALL.iter().any(|resource| resource.limit(budget).is_some())
+}
+
+/// Interpreter for one module
+pub struct Interpreter<H: Host> {
+    lattice: EffectLattice,
+    functions: HashMap<String, Rc<Function>>,
+    /// Top-level `let`s in source order, evaluated before the first call
+    global_inits: Vec<(String, Expr)>,
+    globals: Option<HashMap<String, Value>>,
+    /// Allowed effect at the top level, from the module's capability
+    module_effect: Effect,
+    /// Local scopes of the running function, innermost last
+    scopes: Vec<HashMap<String, Value>>,
+    module_name: String,
+    module_budget: ResourceBudget,
+    /// Meters of the running functions with budgets, innermost last
+    meters: Vec<Meter>,
+    /// Calls currently running
+    depth: usize,
+    clock: Box<dyn Clock>,
+    host: H,
+}
+
+impl<H: Host> Interpreter<H> {
+    pub fn new(module: &Module, host: H) -> Self {
+        let effects = infer_effects(module);
+        let lattice = module.effect_lattice();
+        let module = desugar_module(module);
+        let mut functions = HashMap::new();
+        let mut global_inits = Vec::new();
+        for stmt in module.statements {
+            match stmt.kind {
+                StmtKind::Function { name, effect_params, params, body, capability, .. } => {
+                    let effect = effects.get(&name).cloned().unwrap_or_default();
+                    let param_effects = params.iter().map(|(_, ty)| match &ty.kind {
+                        TypeKind::Function { capability, .. } => {
+                            Some(capability.as_ref().map_or(Effect::pure(), Capability::effect))
+                        }
+                        _ => None,
+                    }).collect();
+                    let params = params.into_iter().map(|(param, _)| param).collect();
+                    let budget = capability.map(|cap| cap.budgets).unwrap_or_default();
+                    functions.insert(name, Rc::new(Function { params, body, effect, effect_params, param_effects, budget }));
+                }
+                StmtKind::Let { name, value, .. } => global_inits.push((name, value)),
+                _ => {}
+            }
+        }
+        let module_budget = module.capability.as_ref().map(|cap| cap.budgets.clone()).unwrap_or_default();
+        // A module capability listing no effects only constrains budgets
+        let module_effect = module.capability
+            .filter(|cap| !cap.effects.is_empty())
+            .map_or_else(|| lattice.top(), |cap| cap.effect());
+        Interpreter {
+            lattice,
+            functions,
+            global_inits,
+            globals: None,
+            module_effect,
+            scopes: Vec::new(),
+            module_name: module.name,
+            module_budget,
+            meters: Vec::new(),
+            depth: 0,
+            clock: Box::new(SystemClock::default()),
+            host,
+        }
+    }
+
+    /// Measure latency budgets with `clock` instead of wall-clock time
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn host(&self) -> &H {
+        &self.host
+    }
+
+    /// Call module function `name` from the top level; each call gets the
+    /// whole module budget
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> RuntimeResult<Value> {
+        let metered = has_limits(&self.module_budget);
+        if metered {
+            self.enter(self.module_name.clone(), self.module_budget.clone());
+        }
+        let result = self.init_globals()
+            .and_then(|()| self.call_function(name, args, self.module_effect.clone(), Span::default()));
+        self.exit(metered, result, Span::default())
+    }
+
+    // -- Budgets --------------------------------------------------------
+
+    fn enter(&mut self, function: String, budget: ResourceBudget) {
+        let started = self.clock.now();
+        self.meters.push(Meter { function, budget, started, used: Cost::default() });
+    }
+
+    /// Leave a metered call, checking its latency one last time
+    fn exit(&mut self, metered: bool, result: RuntimeResult<Value>, span: Span) -> RuntimeResult<Value> {
+        let result = result.and_then(|value| self.check_time(span).map(|()| value));
+        if metered {
+            self.meters.pop();
+        }
+        result
+    }
+
+    /// Charge every running meter, failing at the innermost one over budget
+    fn charge(&mut self, cost: Cost, span: Span) -> RuntimeResult<()> {
+        for meter in &mut self.meters {
+            meter.used.tokens = meter.used.tokens.saturating_add(cost.tokens);
+            meter.used.energy_uj = meter.used.energy_uj.saturating_add(cost.energy_uj);
+        }
+        for meter in self.meters.iter().rev() {
+            for resource in [Resource::Tokens, Resource::Energy] {
+                over_budget(meter, resource, meter.used.get(resource), span)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if any running function has been running for too long
+    fn check_time(&self, span: Span) -> RuntimeResult<()> {
+        let now = self.clock.now();
+        for meter in self.meters.iter().rev() {
+            let elapsed = now.saturating_sub(meter.started).as_micros();
+            over_budget(meter, Resource::Latency, u64::try_from(elapsed).unwrap_or(u64::MAX), span)?;
+        }
+        Ok(())
+    }
+
+    fn init_globals(&mut self) -> RuntimeResult<()> {
+        if self.globals.is_some() {
+            return Ok(());
+        }
+        self.globals = Some(HashMap::new());
+        for (name, init) in std::mem::take(&mut self.global_inits) {
+            let value = self.eval(&init, &self.module_effect.clone())?;
+            self.globals.as_mut().unwrap().insert(name, value);
+        }
+        Ok(())
+    }
+
+    /// Effect of calling `func` with `args`, binding its effect variables
+    /// to the effects of the functions passed for them
+    fn instantiate(&self, func: &Function, args: &[Value]) -> Effect {
+        if func.effect_params.is_empty() {
+            return func.effect.clone();
+        }
+        let pairs = func.param_effects.iter().zip(args).filter_map(|(param, arg)| match (param, arg) {
+            (Some(param), Value::Function(name)) => {
+                let effect = self.functions.get(name).map_or_else(Effect::pure, |f| f.effect.clone());
+                Some((param.clone(), effect))
+            }
+            _ => None,
+        });
+        let bindings = bind_effect_vars(&func.effect_params, pairs);
+        self.lattice.normalize(&func.effect.substitute(&bindings))
+    }
+
+    fn call_function(&mut self, name: &str, args: Vec<Value>, allowed: Effect, span: Span) -> RuntimeResult<Value> {
+        let Some(func) = self.functions.get(name).cloned() else {
+            return Err(RuntimeError::UnknownFunction { name: name.to_string(), span });
+        };
+        if func.params.len() != args.len() {
+            return Err(RuntimeError::TypeMismatch {
+                message: format!("`{}` takes {} arguments but got {}", name, func.params.len(), args.len()),
+                span,
+            });
+        }
+        // A callee can do no more than its caller allows
+        let effect = self.instantiate(&func, &args);
+        let allowed = self.lattice.meet(&allowed, &effect);
+
+        if func.body.is_none() && !self.lattice.subsumes(&effect, &allowed) {
+            return Err(RuntimeError::EffectViolation {
+                callee: name.to_string(),
+                effect,
+                allowed,
+                span,
+            });
+        }
+
+        let metered = has_limits(&func.budget);
+        if metered {
+            self.enter(name.to_string(), func.budget.clone());
+        }
+
+        let Some(body) = &func.body else {
+            // Without a measurement, the declared bounds are the cost, and
+            // are charged up front so an unaffordable call never runs
+            let declared = Cost {
+                tokens: Resource::Tokens.limit(&func.budget).unwrap_or(0),
+                latency_us: 0,
+                energy_uj: Resource::Energy.limit(&func.budget).unwrap_or(0),
+            };
+            let measured = self.host.measures_costs();
+            if !measured {
+                if let Err(error) = self.charge(declared, span) {
+                    return self.exit(metered, Err(error), span);
+                }
+            }
+            let mut result = self.host.call(name, &args)
+                .map_err(|message| RuntimeError::Host { callee: name.to_string(), message, span });
+            if measured {
+                let cost = self.host.last_cost().unwrap_or(declared);
+                result = result.and_then(|value| self.charge(cost, span).map(|()| value));
+            }
+            return self.exit(metered, result, span);
+        };
+
+        if self.depth == MAX_CALL_DEPTH {
+            let error = RuntimeError::CallDepthExceeded { callee: name.to_string(), limit: MAX_CALL_DEPTH, span };
+            return self.exit(metered, Err(error), span);
+        }
+        let frame: HashMap<String, Value> = func.params.iter().cloned().zip(args).collect();
+        let caller_scopes = std::mem::replace(&mut self.scopes, vec![frame]);
+        self.depth += 1;
+        let result = self.eval_block(body, &allowed);
+        self.depth -= 1;
+        self.scopes = caller_scopes;
+        self.exit(metered, result, span)
+    }
+
+    // -- Evaluation -----------------------------------------------------
+
+    fn eval_block(&mut self, block: &Block, allowed: &Effect) -> RuntimeResult<Value> {
+        self.scopes.push(HashMap::new());
+        let result = self.eval_block_inner(block, allowed);
+        self.scopes.pop();
+        result
+    }
+
+    fn eval_block_inner(&mut self, block: &Block, allowed: &Effect) -> RuntimeResult<Value> {
+        for stmt in &block.stmts {
+            self.exec(stmt, allowed)?;
+        }
+        match &block.tail {
+            Some(tail) => self.eval(tail, allowed),
+            None => Ok(Value::Unit),
+        }
+    }
+
+    fn exec(&mut self, stmt: &Stmt, allowed: &Effect) -> RuntimeResult<()> {
+        match &stmt.kind {
+            StmtKind::Let { name, value, .. } => {
+                let value = self.eval(value, allowed)?;
+                self.scopes.last_mut().expect("a scope is open").insert(name.clone(), value);
+            }
+            StmtKind::Assign { name, value } => {
+                let value = self.eval(value, allowed)?;
+                let slot = self.scopes.iter_mut().rev()
+                    .find_map(|scope| scope.get_mut(name))
+                    .or_else(|| self.globals.as_mut().and_then(|globals| globals.get_mut(name)));
+                match slot {
+                    Some(slot) => *slot = value,
+                    None => return Err(mismatch(format!("assignment to unknown variable `{}`", name), stmt.span)),
+                }
+            }
+            StmtKind::Expression(expr) => {
+                self.eval(expr, allowed)?;
+            }
+            StmtKind::Function { .. } | StmtKind::Enum { .. } | StmtKind::Error => {}
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Option<Value> {
+        if let Some(value) = self.scopes.iter().rev().find_map(|scope| scope.get(name)) {
+            return Some(value.clone());
+        }
+        if let Some(value) = self.globals.as_ref().and_then(|globals| globals.get(name)) {
+            return Some(value.clone());
+        }
+        self.functions.contains_key(name).then(|| Value::Function(name.to_string()))
+    }
+
+    fn eval(&mut self, expr: &Expr, allowed: &Effect) -> RuntimeResult<Value> {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Number(n) => Ok(Value::Int(*n)),
+            ExprKind::String(s) => Ok(Value::Text(s.clone())),
+            ExprKind::Bool(b) => Ok(Value::Bool(*b)),
+            ExprKind::Ident(name) => self.lookup(name)
+                .ok_or_else(|| mismatch(format!("unknown name `{}`", name), span)),
+            ExprKind::Binary { op: BinOp::And, lhs, rhs } => {
+                if self.eval_bool(lhs, allowed)? { Ok(Value::Bool(self.eval_bool(rhs, allowed)?)) } else { Ok(Value::Bool(false)) }
+            }
+            ExprKind::Binary { op: BinOp::Or, lhs, rhs } => {
+                if self.eval_bool(lhs, allowed)? { Ok(Value::Bool(true)) } else { Ok(Value::Bool(self.eval_bool(rhs, allowed)?)) }
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let lhs = self.eval(lhs, allowed)?;
+                let rhs = self.eval(rhs, allowed)?;
+                binary(*op, lhs, rhs, span)
+            }
+            ExprKind::Unary { op, operand } => match (op, self.eval(operand, allowed)?) {
+                (UnaryOp::Neg, Value::Int(n)) => n.checked_neg().map(Value::Int).ok_or(RuntimeError::Overflow { span }),
+                (UnaryOp::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                (op, value) => Err(mismatch(format!("cannot apply `{:?}` to `{}`", op, value), span)),
+            },
+            ExprKind::Call { func, args } => self.eval_call(func, args, allowed, span),
+            ExprKind::Array(items) => {
+                let items = items.iter().map(|item| self.eval(item, allowed)).collect::<RuntimeResult<_>>()?;
+                Ok(Value::Array(items))
+            }
+            ExprKind::Index { base, index } => {
+                let base = self.eval(base, allowed)?;
+                let index = self.eval(index, allowed)?;
+                match (base, index) {
+                    (Value::Array(items), Value::Int(i)) => usize::try_from(i).ok()
+                        .and_then(|i| items.get(i).cloned())
+                        .ok_or(RuntimeError::IndexOutOfBounds { index: i, len: items.len(), span }),
+                    (base, _) => Err(mismatch(format!("cannot index `{}`", base), span)),
+                }
+            }
+            ExprKind::Block(block) => self.eval_block(block, allowed),
+            ExprKind::If { cond, then_branch, else_branch } => {
+                if self.eval_bool(cond, allowed)? {
+                    self.eval_block(then_branch, allowed)
+                } else if let Some(else_branch) = else_branch {
+                    self.eval(else_branch, allowed)
+                } else {
+                    Ok(Value::Unit)
+                }
+            }
+            ExprKind::While { cond, body } => {
+                while self.eval_bool(cond, allowed)? {
+                    self.eval_block(body, allowed)?;
+                    // Loops may run long without calling anything
+                    self.check_time(span)?;
+                }
+                Ok(Value::Unit)
+            }
+            ExprKind::IntentBlock { .. } => Err(RuntimeError::Unsupported { what: "intent blocks".to_string(), span }),
+            // Removed by desugaring, or rejected by the parser
+            ExprKind::For { .. } | ExprKind::Match { .. } | ExprKind::Variant { .. } | ExprKind::Error => {
+                Err(RuntimeError::Unsupported { what: "this expression".to_string(), span })
+            }
+        }
+    }
+
+    fn eval_bool(&mut self, expr: &Expr, allowed: &Effect) -> RuntimeResult<bool> {
+        match self.eval(expr, allowed)? {
+            Value::Bool(b) => Ok(b),
+            value => Err(mismatch(format!("expected a Bool, found `{}`", value), expr.span)),
+        }
+    }
+
+    fn eval_call(&mut self, func: &Expr, args: &[Expr], allowed: &Effect, span: Span) -> RuntimeResult<Value> {
+        let args: Vec<Value> = args.iter().map(|arg| self.eval(arg, allowed)).collect::<RuntimeResult<_>>()?;
+
+        // `len` is a builtin unless something shadows it
+        let is_len = matches!(&func.kind, ExprKind::Ident(name) if name == LEN_BUILTIN && self.lookup(name).is_none());
+        if is_len {
+            return match args.as_slice() {
+                [Value::Array(items)] => Ok(Value::Int(items.len() as i64)),
+                _ => Err(mismatch("`len` takes one array".to_string(), span)),
+            };
+        }
+        match self.eval(func, allowed)? {
+            Value::Function(name) => self.call_function(&name, args, allowed.clone(), span),
+            value => Err(mismatch(format!("`{}` is not callable", value), span)),
+        }
+    }
+}
+
+fn over_budget(meter: &Meter, resource: Resource, used: u64, span: Span) -> RuntimeResult<()> {
+    match resource.limit(&meter.budget) {
+        Some(limit) if used > limit => Err(RuntimeError::BudgetExceeded {
+            function: meter.function.clone(),
+            resource,
+            limit,
+            used,
+            span,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn mismatch(message: String, span: Span) -> RuntimeError {
+    RuntimeError::TypeMismatch { message, span }
+}
+
+fn binary(op: BinOp, lhs: Value, rhs: Value, span: Span) -> RuntimeResult<Value> {
+    let overflow = RuntimeError::Overflow { span };
+    match (op, lhs, rhs) {
+        (BinOp::Eq, lhs, rhs) => Ok(Value::Bool(lhs == rhs)),
+        (BinOp::Ne, lhs, rhs) => Ok(Value::Bool(lhs != rhs)),
+        (BinOp::Add, Value::Text(a), Value::Text(b)) => Ok(Value::Text(a + &b)),
+        (BinOp::Div | BinOp::Rem, Value::Int(_), Value::Int(0)) => Err(RuntimeError::DivisionByZero { span }),
+        (op, Value::Int(a), Value::Int(b)) => match op {
+            BinOp::Add => a.checked_add(b).map(Value::Int).ok_or(overflow),
+            BinOp::Sub => a.checked_sub(b).map(Value::Int).ok_or(overflow),
+            BinOp::Mul => a.checked_mul(b).map(Value::Int).ok_or(overflow),
+            BinOp::Div => a.checked_div(b).map(Value::Int).ok_or(overflow),
+            BinOp::Rem => a.checked_rem(b).map(Value::Int).ok_or(overflow),
+            BinOp::Lt => Ok(Value::Bool(a < b)),
+            BinOp::Le => Ok(Value::Bool(a <= b)),
+            BinOp::Gt => Ok(Value::Bool(a > b)),
+            BinOp::Ge => Ok(Value::Bool(a >= b)),
+            BinOp::Eq | BinOp::Ne | BinOp::And | BinOp::Or => unreachable!("handled above"),
+        },
+        (op, lhs, rhs) => Err(mismatch(format!("cannot apply `{:?}` to `{}` and `{}`", op, lhs, rhs), span)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    /// Host recording every call, with `print` and a `fetch` returning its
+    /// argument after 30ms on `clock`, and reporting `usage` as each
+    /// call's cost when set
+    #[derive(Default)]
+    struct TestHost {
+        calls: Vec<String>,
+        clock: FakeClock,
+        usage: Option<Cost>,
+    }
+
+    impl Host for TestHost {
+        fn call(&mut self, name: &str, args: &[Value]) -> Result<Value, String> {
+            let text: Vec<String> = args.iter().map(Value::to_string).collect();
+            self.calls.push(format!("{}({})", name, text.join(", ")));
+            match name {
+                "print" => Ok(Value::Unit),
+                "fetch" => {
+                    self.clock.advance(Duration::from_millis(30));
+                    Ok(args[0].clone())
+                }
+                _ => Err("unavailable".to_string()),
+            }
+        }
+
+        fn measures_costs(&self) -> bool {
+            self.usage.is_some()
+        }
+
+        fn last_cost(&mut self) -> Option<Cost> {
+            self.usage
+        }
+    }
+
+    fn interpreter(src: &str) -> Interpreter<TestHost> {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        Interpreter::new(&module, TestHost::default())
+    }
+
+    /// Interpreter whose latency is measured on the host's fake clock
+    fn metered(src: &str) -> Interpreter<TestHost> {
+        let interp = interpreter(src);
+        let clock = interp.host().clock.clone();
+        interp.with_clock(clock)
+    }
+
+    #[test]
+    fn test_arithmetic_and_control_flow() {
+        let mut interp = interpreter("module m
+enum Sign { Neg, Zero, Pos }
+fn sign(x: Int) -> Sign { if x < 0 { Sign::Neg } else if x == 0 { Sign::Zero } else { Sign::Pos } }
+fn describe(x: Int) -> Text {
+    match sign(x) { Sign::Neg => \"negative\", Sign::Zero => \"zero\", _ => \"positive\" }
+}
+fn sum_odd(xs: Array<Int>) -> Int {
+    let total = 0;
+    for x in xs { if x % 2 == 1 || x < 0 { total = total + x; } }
+    total
+}");
+        assert_eq!(interp.call("describe", vec![Value::Int(-4)]), Ok(Value::Text("negative".to_string())));
+        assert_eq!(interp.call("describe", vec![Value::Int(0)]), Ok(Value::Text("zero".to_string())));
+        let xs = Value::Array(vec![Value::Int(1), Value::Int(2), Value::Int(3), Value::Int(-6)]);
+        assert_eq!(interp.call("sum_odd", vec![xs]), Ok(Value::Int(-2)));
+    }
+
+    #[test]
+    fn test_globals_arrays_and_function_values() {
+        let mut interp = interpreter("module m
+let greeting = \"hi \";
+fn twice(f: fn(Int) -> Int, x: Int) -> Int { f(f(x)) }
+fn inc(x: Int) -> Int { x + 1 }
+fn build(n: Int) -> Array<Text> {
+    let i = 0;
+    while i < n { i = i + 1; }
+    [greeting + \"there\", greeting]
+}");
+        assert_eq!(interp.call("twice", vec![Value::Function("inc".to_string()), Value::Int(5)]), Ok(Value::Int(7)));
+        let built = interp.call("build", vec![Value::Int(3)]).unwrap();
+        assert_eq!(built.to_string(), "[hi there, hi ]");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let mut interp = interpreter("module m
+fn at(xs: Array<Int>, i: Int) -> Int { xs[i] }
+fn div(a: Int, b: Int) -> Int { a / b }");
+        let xs = Value::Array(vec![Value::Int(1)]);
+        let err = interp.call("at", vec![xs, Value::Int(3)]).unwrap_err();
+        assert_eq!(err.to_string(), "index 3 is out of bounds for an array of length 1");
+        assert_eq!(err.span().line, 2);
+        assert!(matches!(interp.call("div", vec![Value::Int(1), Value::Int(0)]), Err(RuntimeError::DivisionByZero { .. })));
+        assert!(matches!(interp.call("div", vec![Value::Int(i64::MIN), Value::Int(-1)]), Err(RuntimeError::Overflow { .. })));
+    }
+
+    #[test]
+    fn test_call_depth_limit() {
+        let deep = std::thread::Builder::new().stack_size(INTERPRETER_STACK_SIZE).spawn(|| {
+            let mut interp = interpreter("module m
+fn main() -> Int { main() }
+fn count(n: Int) -> Int { if n == 0 { 0 } else { 1 + count(n - 1) } }");
+            let err = interp.call("main", Vec::new()).unwrap_err();
+            // Recursion within the limit still runs, after a failed call too
+            let deepest = MAX_CALL_DEPTH as i64 - 1;
+            (err, interp.call("count", vec![Value::Int(deepest)]))
+        });
+        let (err, count) = deep.unwrap().join().unwrap();
+        assert_eq!(err, RuntimeError::CallDepthExceeded {
+            callee: "main".to_string(),
+            limit: MAX_CALL_DEPTH,
+            span: err.span(),
+        });
+        assert_eq!(err.code(), "E0609");
+        assert_eq!(err.span().line, 2);
+        assert_eq!(count, Ok(Value::Int(MAX_CALL_DEPTH as i64 - 1)));
+    }
+
+    #[test]
+    fn test_host_calls_within_declared_effects() {
+        let mut interp = interpreter("module m
+fn print(x: Text) !{io}
+fn fetch(url: Text) -> Text !{net}
+fn show(x: Text) { print(x) }
+fn main() !{net} { show(fetch(\"page\")) }");
+        assert_eq!(interp.call("main", vec![]), Ok(Value::Unit));
+        assert_eq!(interp.host().calls, vec!["fetch(page)", "print(page)"]);
+    }
+
+    #[test]
+    fn test_io_refused_from_pure_context() {
+        let mut interp = interpreter("module m
+fn print(x: Text) !{io}
+fn sneaky(f: fn(Text) -> Unit) -> Int !{pure} { f(\"leak\"); 1 }
+fn logged(x: Text) -> Int !{io} { print(x); 2 }
+fn compute() -> Int !{pure} { logged(\"x\") }");
+        let err = interp.call("sneaky", vec![Value::Function("print".to_string())]).unwrap_err();
+        assert_eq!(err.to_string(), "host call to `print` requires `io`, but the calling context allows only `pure`");
+        assert_eq!(err.to_diagnostic().code.as_deref(), Some("E0601"));
+
+        // The callee's own capability does not widen its caller's
+        assert!(matches!(interp.call("compute", vec![]), Err(RuntimeError::EffectViolation { .. })));
+        assert_eq!(interp.call("logged", vec![Value::Text("ok".to_string())]), Ok(Value::Int(2)));
+        assert_eq!(interp.host().calls, vec!["print(ok)"]);
+    }
+
+    #[test]
+    fn test_module_capability_bounds_top_level() {
+        let mut interp = interpreter("module m !{io}
+fn fetch(url: Text) -> Text !{net}
+fn get() -> Text !{net} { fetch(\"x\") }");
+        let err = interp.call("get", vec![]).unwrap_err();
+        assert!(matches!(err, RuntimeError::EffectViolation { ref allowed, .. } if *allowed == Effect::named("io")));
+    }
+
+    #[test]
+    fn test_token_and_energy_budgets() {
+        let mut interp = metered("module m
+fn fetch(q: Text) -> Text !{net, tokens <= 40, energy <= 3mJ}
+fn twice() -> Text !{net, tokens <= 100} { fetch(\"a\"); fetch(\"b\") }
+fn thrice() -> Text !{net, tokens <= 100} { fetch(\"a\"); fetch(\"b\"); fetch(\"c\") }
+fn frugal() -> Text !{net, energy <= 5mJ} { twice() }");
+        assert_eq!(interp.call("twice", vec![]), Ok(Value::Text("b".to_string())));
+
+        let err = interp.call("thrice", vec![]).unwrap_err();
+        assert_eq!(err.to_string(), "`thrice` used 120 tokens, over its budget of 100 tokens");
+        assert_eq!(err.to_diagnostic().code.as_deref(), Some("E0608"));
+        // The declared cost of the third call is charged before it runs
+        assert_eq!(interp.host().calls.len(), 4, "the third call did not run");
+
+        // Charges count against every running caller
+        let err = interp.call("frugal", vec![]).unwrap_err();
+        assert!(matches!(err, RuntimeError::BudgetExceeded { ref function, resource: Resource::Energy, limit: 5_000, used: 6_000, .. }
+            if function == "frugal"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_host_reported_costs() {
+        let mut interp = metered("module m
+fn fetch(q: Text) -> Text !{net, tokens <= 40}
+fn thrice() -> Text !{net, tokens <= 100} { fetch(\"a\"); fetch(\"b\"); fetch(\"c\") }");
+        // Measured costs replace the declared ones
+        interp.host.usage = Some(Cost { tokens: 25, ..Cost::default() });
+        assert_eq!(interp.call("thrice", vec![]), Ok(Value::Text("c".to_string())));
+
+        // So a host call can now run over its own budget
+        interp.host.usage = Some(Cost { tokens: 41, ..Cost::default() });
+        let err = interp.call("fetch", vec![Value::Text("a".to_string())]).unwrap_err();
+        assert_eq!(err.to_string(), "`fetch` used 41 tokens, over its budget of 40 tokens");
+    }
+
+    #[test]
+    fn test_latency_budget_uses_clock() {
+        let mut interp = metered("module m !{net, latency <= 100ms}
+fn fetch(url: Text) -> Text !{net, latency <= 40ms}
+fn quick() -> Text !{net, latency <= 50ms} { fetch(\"a\") }
+fn slow() -> Text !{net, latency <= 50ms} { fetch(\"a\"); fetch(\"b\") }
+fn crawl(n: Int) -> Int !{net} { let i = 0; while i < n { fetch(\"x\"); i = i + 1; } i }");
+        assert_eq!(interp.call("quick", vec![]), Ok(Value::Text("a".to_string())));
+
+        let err = interp.call("slow", vec![]).unwrap_err();
+        assert_eq!(err.to_string(), "`slow` used 60ms of latency, over its budget of 50ms of latency");
+
+        assert_eq!(interp.call("crawl", vec![Value::Int(3)]), Ok(Value::Int(3)));
+        let err = interp.call("crawl", vec![Value::Int(4)]).unwrap_err();
+        assert!(matches!(err, RuntimeError::BudgetExceeded { ref function, used: 120_000, .. } if function == "m"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_declared_effects_at_runtime() {
+        let mut interp = interpreter("module m
+effect log <: io;
+effect clock;
+fn print(x: Text) !{log}
+fn fetch(x: Text) -> Text !{clock}
+fn note() !{io} { print(\"ok\") }
+fn sneaky(f: fn(Text) -> Text) -> Text !{log} { f(\"t\") }");
+        assert_eq!(interp.call("note", vec![]), Ok(Value::Unit));
+        let err = interp.call("sneaky", vec![Value::Function("fetch".to_string())]).unwrap_err();
+        assert_eq!(err.to_string(), "host call to `fetch` requires `clock`, but the calling context allows only `pure`");
+    }
+
+    #[test]
+    fn test_effect_variables_at_runtime() {
+        let mut interp = interpreter("module m
+fn print(x: Text) !{io}
+fn fetch(x: Text) -> Text !{net}
+fn shout(x: Text) -> Text !{io} { print(x); x }
+fn apply<e>(f: fn(Text) -> Text !{e}, x: Text) -> Text !{e} { f(x) }
+fn local() -> Text !{io} { apply(shout, \"a\") }
+fn remote() -> Text !{io} { apply(fetch, \"b\") }");
+        assert_eq!(interp.call("local", vec![]), Ok(Value::Text("a".to_string())));
+        let err = interp.call("remote", vec![]).unwrap_err();
+        assert_eq!(err.to_string(), "host call to `fetch` requires `net`, but the calling context allows only `io`");
+        assert_eq!(interp.host().calls, vec!["print(a)"]);
+    }
+}
@@ -1,14 +1,188 @@
-/// Forge IR module - Phase α
-/// 
-/// Intermediate representation for Forge programs
+//! Forge IR module - Phase α
+//!
+//! Intermediate representation for Forge programs
 
-use crate::ast::{Effect, ResourceBudget};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::{BinOp, Effect, EffectDecl, EffectLattice, Resource, ResourceBudget, UnaryOp};
+use crate::span::Span;
 
 /// IR capability (mirrors AST capability)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct IrCapability {
     pub effects: Vec<Effect>,
     pub budgets: ResourceBudget,
+    /// Inferred by the compiler rather than declared in source
+    pub inferred: bool,
+}
+
+/// Integer width in bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IntWidth {
+    I8,
+    I16,
+    I32,
+    I64,
+}
+
+impl IntWidth {
+    pub fn bits(self) -> u32 {
+        match self {
+            IntWidth::I8 => 8,
+            IntWidth::I16 => 16,
+            IntWidth::I32 => 32,
+            IntWidth::I64 => 64,
+        }
+    }
+}
+
+/// Type of an IR value
+#[derive(Debug, Clone, PartialEq)]
+pub enum IrType {
+    /// No value, for functions returning nothing
+    Unit,
+    /// Signed integer; Forge `Int` and enum discriminants are `i64`
+    Int(IntWidth),
+    Bool,
+    Text,
+    /// Length cell followed by the elements (see `lower`)
+    Array(Box<IrType>),
+    /// Function values always carry the capability calls through them need
+    Function {
+        params: Vec<IrType>,
+        returns: Box<IrType>,
+        capability: IrCapability,
+    },
+    /// Named struct; no Forge construct lowers to one yet
+    Struct(String),
+}
+
+impl IrType {
+    /// Type of Forge `Int`
+    pub const INT: IrType = IrType::Int(IntWidth::I64);
+    
+    /// Whether values of this type own memory, and so are borrowed or moved
+    /// rather than copied
+    pub fn is_owned(&self) -> bool {
+        matches!(self, IrType::Array(_) | IrType::Struct(_))
+    }
+}
+
+impl fmt::Display for IrType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IrType::Unit => write!(f, "unit"),
+            IrType::Int(width) => write!(f, "i{}", width.bits()),
+            IrType::Bool => write!(f, "bool"),
+            IrType::Text => write!(f, "text"),
+            IrType::Array(element) => write!(f, "[{}]", element),
+            IrType::Function { params, returns, capability } => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") -> {} {}", returns, capability)
+            }
+            IrType::Struct(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl IrCapability {
+    /// Declared capability allowing nothing but pure computation
+    pub fn pure() -> Self {
+        IrCapability {
+            effects: vec![Effect::pure()],
+            budgets: ResourceBudget::default(),
+            inferred: false,
+        }
+    }
+}
+
+/// Written as in source, `!{io, tokens <= 5}`, then `inferred` if it was
+impl fmt::Display for IrCapability {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut items: Vec<String> = self.effects.iter().map(Effect::to_string).collect();
+        for resource in Resource::ALL {
+            if let Some(limit) = resource.limit(&self.budgets) {
+                items.push(format!("{} <= {}", resource, resource.quantity(limit)));
+            }
+        }
+        write!(f, "!{{{}}}", items.join(", "))?;
+        if self.inferred {
+            write!(f, " inferred")?;
+        }
+        Ok(())
+    }
+}
+
+/// Value of unknown contents, never defined by an instruction. Lowering
+/// uses it for variables read before assignment, which the checkers
+/// reject, and for constructs it cannot lower yet (W0002).
+pub const UNDEF: &str = "%undef";
+
+/// SSA values are `%`-prefixed (`%0`, `%x`); functions are referred to by
+/// bare name
+pub fn is_value(name: &str) -> bool {
+    name.starts_with('%')
+}
+
+/// How an instruction takes hold of an operand (see `docs/ir_draft.md`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ownership {
+    /// Plain read of a value without ownership, such as an Int
+    Copy,
+    /// Ownership passes to the instruction; the value is dead afterwards
+    Move,
+    /// Read-only borrow
+    Shared,
+    /// Exclusive borrow that may write
+    Unique,
+}
+
+impl fmt::Display for Ownership {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ownership::Copy => Ok(()),
+            Ownership::Move => write!(f, "move "),
+            Ownership::Shared => write!(f, "&shared "),
+            Ownership::Unique => write!(f, "&unique "),
+        }
+    }
+}
+
+/// SSA value used by an instruction, tagged with its ownership mode
+#[derive(Clone, PartialEq, Eq)]
+pub struct IrOperand {
+    pub value: String,
+    pub mode: Ownership,
+}
+
+impl IrOperand {
+    pub fn new(value: impl Into<String>, mode: Ownership) -> Self {
+        IrOperand { value: value.into(), mode }
+    }
+    
+    pub fn copy(value: impl Into<String>) -> Self {
+        IrOperand::new(value, Ownership::Copy)
+    }
+}
+
+impl fmt::Display for IrOperand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.mode, self.value)
+    }
+}
+
+// Operands read far better as `&shared %0` than as structs in IR dumps
+impl fmt::Debug for IrOperand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\"", self)
+    }
 }
 
 /// IR instruction types
@@ -17,19 +191,166 @@ pub enum IrInst {
     /// Constant value
     Const { dest: String, value: IrValue },
     
-    /// Function call with capability
+    /// Function call with capability; `func` is a function name, or an SSA
+    /// value for indirect calls
     Call {
         dest: String,
         func: String,
-        args: Vec<String>,
+        args: Vec<IrOperand>,
         capability: Option<IrCapability>,
     },
     
-    /// Allocate memory
+    /// Allocate `size` memory cells
     Alloc { dest: String, size: u32 },
     
+    /// Read the cell at `base + offset`
+    Load {
+        dest: String,
+        base: IrOperand,
+        offset: IrOperand,
+    },
+    
+    /// Write `value` to the cell at `base + offset`
+    Store {
+        base: IrOperand,
+        offset: IrOperand,
+        value: IrOperand,
+    },
+    
+    /// Reference to `source` that aliases it for as long as `dest` is live
+    Borrow { dest: String, source: IrOperand },
+    
+    /// Arithmetic or comparison on two values
+    Binary {
+        dest: String,
+        op: BinOp,
+        lhs: IrOperand,
+        rhs: IrOperand,
+    },
+    
+    /// Negation or logical not
+    Unary {
+        dest: String,
+        op: UnaryOp,
+        operand: IrOperand,
+    },
+    
+    /// SSA join: the value flowing in from each predecessor label
+    Phi {
+        dest: String,
+        incoming: Vec<(String, IrOperand)>,
+    },
+}
+
+impl IrInst {
+    /// SSA value defined by this instruction
+    pub fn dest(&self) -> Option<&str> {
+        match self {
+            IrInst::Const { dest, .. }
+            | IrInst::Call { dest, .. }
+            | IrInst::Alloc { dest, .. }
+            | IrInst::Load { dest, .. }
+            | IrInst::Borrow { dest, .. }
+            | IrInst::Binary { dest, .. }
+            | IrInst::Unary { dest, .. }
+            | IrInst::Phi { dest, .. } => Some(dest),
+            IrInst::Store { .. } => None,
+        }
+    }
+    
+    /// Operands of this instruction; the callee of an indirect call is a
+    /// plain read
+    pub fn operands(&self) -> Vec<IrOperand> {
+        match self {
+            IrInst::Call { func, args, .. } => {
+                let callee = is_value(func).then(|| IrOperand::copy(func.clone()));
+                callee.into_iter().chain(args.iter().cloned()).collect()
+            }
+            IrInst::Load { base, offset, .. } => vec![base.clone(), offset.clone()],
+            IrInst::Store { base, offset, value } => vec![base.clone(), offset.clone(), value.clone()],
+            IrInst::Borrow { source, .. } => vec![source.clone()],
+            IrInst::Binary { lhs, rhs, .. } => vec![lhs.clone(), rhs.clone()],
+            IrInst::Unary { operand, .. } => vec![operand.clone()],
+            IrInst::Phi { incoming, .. } => incoming.iter().map(|(_, value)| value.clone()).collect(),
+            IrInst::Const { .. } | IrInst::Alloc { .. } => Vec::new(),
+        }
+    }
+    
+    /// SSA values read by this instruction, for renaming
+    pub fn operands_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            IrInst::Call { func, args, .. } => {
+                let callee = is_value(func).then_some(func);
+                callee.into_iter().chain(args.iter_mut().map(|arg| &mut arg.value)).collect()
+            }
+            IrInst::Load { base, offset, .. } => vec![&mut base.value, &mut offset.value],
+            IrInst::Store { base, offset, value } => {
+                vec![&mut base.value, &mut offset.value, &mut value.value]
+            }
+            IrInst::Borrow { source, .. } => vec![&mut source.value],
+            IrInst::Binary { lhs, rhs, .. } => vec![&mut lhs.value, &mut rhs.value],
+            IrInst::Unary { operand, .. } => vec![&mut operand.value],
+            IrInst::Phi { incoming, .. } => incoming.iter_mut().map(|(_, value)| &mut value.value).collect(),
+            IrInst::Const { .. } | IrInst::Alloc { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Instruction ending a basic block
+#[derive(Debug, Clone)]
+pub enum IrTerminator {
+    /// Unconditional jump
+    Jump { target: String },
+    
+    /// Conditional branch on a Bool value
+    Branch {
+        cond: IrOperand,
+        then_target: String,
+        else_target: String,
+    },
+    
     /// Return value
-    Return { value: Option<String> },
+    Return { value: Option<IrOperand> },
+}
+
+impl IrTerminator {
+    /// Labels control may continue at
+    pub fn successors(&self) -> Vec<&str> {
+        match self {
+            IrTerminator::Jump { target } => vec![target],
+            IrTerminator::Branch { then_target, else_target, .. } => vec![then_target, else_target],
+            IrTerminator::Return { .. } => Vec::new(),
+        }
+    }
+    
+    pub fn operands(&self) -> Vec<IrOperand> {
+        match self {
+            IrTerminator::Jump { .. } => Vec::new(),
+            IrTerminator::Branch { cond, .. } => vec![cond.clone()],
+            IrTerminator::Return { value } => value.iter().cloned().collect(),
+        }
+    }
+    
+    /// SSA values read by this terminator, for renaming
+    pub fn operands_mut(&mut self) -> Vec<&mut String> {
+        match self {
+            IrTerminator::Jump { .. } => Vec::new(),
+            IrTerminator::Branch { cond, .. } => vec![&mut cond.value],
+            IrTerminator::Return { value } => value.iter_mut().map(|v| &mut v.value).collect(),
+        }
+    }
+}
+
+/// Labelled basic block: phis first, then straight-line instructions, then
+/// exactly one terminator
+#[derive(Debug, Clone)]
+pub struct IrBlock {
+    pub label: String,
+    pub insts: Vec<IrInst>,
+    /// Source span of each instruction in `insts`
+    pub spans: Vec<Span>,
+    pub terminator: IrTerminator,
+    pub terminator_span: Span,
 }
 
 /// IR value types
@@ -44,10 +365,57 @@ pub enum IrValue {
 #[derive(Debug)]
 pub struct IrFunction {
     pub name: String,
-    pub params: Vec<(String, String)>, // (name, type)
-    pub returns: String,
+    /// Where the function is declared
+    pub span: Span,
+    /// Parameter names; parameter `x` is the SSA value `%x`
+    pub params: Vec<(String, IrType)>,
+    pub returns: IrType,
     pub capability: Option<IrCapability>,
-    pub body: Vec<IrInst>,
+    /// Control flow graph, entry block first; empty for external
+    /// declarations
+    pub blocks: Vec<IrBlock>,
+    /// Type of every SSA value, parameters included
+    pub value_types: HashMap<String, IrType>,
+    /// Source variable a value was bound to, where lowering knows one
+    pub value_names: HashMap<String, String>,
+}
+
+impl IrFunction {
+    /// Type of this function when used as a value
+    pub fn ty(&self) -> IrType {
+        IrType::Function {
+            params: self.params.iter().map(|(_, ty)| ty.clone()).collect(),
+            returns: Box::new(self.returns.clone()),
+            capability: self.capability.clone().unwrap_or_else(IrCapability::pure),
+        }
+    }
+    
+    pub fn value_type(&self, value: &str) -> Option<&IrType> {
+        self.value_types.get(value)
+    }
+
+    /// Name to show for a value in diagnostics: its source variable, or
+    /// the value itself
+    pub fn value_name<'v>(&'v self, value: &'v str) -> &'v str {
+        self.value_names.get(value).map_or(value, String::as_str)
+    }
+    
+    pub fn block(&self, label: &str) -> Option<&IrBlock> {
+        self.blocks.iter().find(|block| block.label == label)
+    }
+    
+    /// Predecessor labels of every block, in block order
+    pub fn predecessors(&self) -> HashMap<&str, Vec<&str>> {
+        let mut preds: HashMap<&str, Vec<&str>> = self.blocks.iter()
+            .map(|block| (block.label.as_str(), Vec::new()))
+            .collect();
+        for block in &self.blocks {
+            for succ in block.terminator.successors() {
+                preds.entry(succ).or_default().push(&block.label);
+            }
+        }
+        preds
+    }
 }
 
 /// IR module (compilation unit)
@@ -55,51 +423,19 @@ pub struct IrFunction {
 pub struct IrModule {
     pub name: String,
     pub capability: Option<IrCapability>,
+    /// Effects the source module declares, in declaration order
+    pub effects: Vec<EffectDecl>,
     pub functions: Vec<IrFunction>,
 }
 
 impl IrModule {
-    /// Pretty-print IR for debugging
+    /// Built-in effects plus the ones this module declares
+    pub fn effect_lattice(&self) -> EffectLattice {
+        EffectLattice::new(&self.effects)
+    }
+    
+    /// Pretty-print IR in the textual format (see `ir_text`)
     pub fn debug_print(&self) -> String {
-        let mut output = String::new();
-        
-        output.push_str(&format!("module {} ", self.name));
-        if let Some(cap) = &self.capability {
-            output.push_str(&format!("!{:?} ", cap));
-        }
-        output.push_str("{\n");
-        
-        for func in &self.functions {
-            output.push_str(&format!("\n  fn {}(", func.name));
-            for (i, (name, ty)) in func.params.iter().enumerate() {
-                if i > 0 { output.push_str(", "); }
-                output.push_str(&format!("{}: {}", name, ty));
-            }
-            output.push_str(&format!(") -> {} ", func.returns));
-            
-            if let Some(cap) = &func.capability {
-                output.push_str(&format!("!{{"));
-                for (i, effect) in cap.effects.iter().enumerate() {
-                    if i > 0 { output.push_str(", "); }
-                    output.push_str(&format!("{:?}", effect).to_lowercase());
-                }
-                if let Some(tokens) = cap.budgets.tokens {
-                    output.push_str(&format!(", tokens ≤ {}", tokens));
-                }
-                if let Some(latency) = cap.budgets.latency_ms {
-                    output.push_str(&format!(", latency ≤ {}ms", latency));
-                }
-                output.push_str("} ");
-            }
-            
-            output.push_str("{\n");
-            for inst in &func.body {
-                output.push_str(&format!("    {:?}\n", inst));
-            }
-            output.push_str("  }\n");
-        }
-        
-        output.push_str("}\n");
-        output
+        crate::ir_text::print_module(self)
     }
-}
\ No newline at end of file
+}
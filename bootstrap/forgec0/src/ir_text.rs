@@ -0,0 +1,835 @@
+//! Textual Forge-IR - printer and parser
+//!
+//! `print_module` writes the format described in `docs/ir_draft.md` and
+//! `parse_module` reads it back, so `print_module(&parse_module(text)?)`
+//! reproduces any printed module exactly. Spans are not part of the text;
+//! parsed instructions get the span of their first token in the IR text.
+//!
+//! ```text
+//! module demo !{io}
+//!
+//! fn first(%xs: [i64]) -> i64 !{pure} inferred {
+//! entry:
+//!   %0: i64 = const 1
+//!   %1: i64 = load &shared %xs, %0
+//!   return %1
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::ast::{BinOp, Effect, EffectDecl, Resource, ResourceBudget, UnaryOp};
+use crate::diagnostics::Diagnostic;
+use crate::ir::*;
+use crate::span::Span;
+
+// -- Printer ----------------------------------------------------------------
+
+/// Print a module in the textual format
+pub fn print_module(module: &IrModule) -> String {
+    let mut out = format!("module {}", module.name);
+    if let Some(cap) = &module.capability {
+        write!(out, " {}", cap).unwrap();
+    }
+    out.push('\n');
+    for decl in &module.effects {
+        write!(out, "effect {}", decl.name).unwrap();
+        if let Some(parent) = &decl.parent {
+            write!(out, " <: {}", parent).unwrap();
+        }
+        out.push('\n');
+    }
+    for func in &module.functions {
+        out.push('\n');
+        print_function(&mut out, func);
+    }
+    out
+}
+
+fn print_function(out: &mut String, func: &IrFunction) {
+    let params: Vec<String> = func.params.iter()
+        .map(|(name, ty)| format!("%{}: {}", name, ty))
+        .collect();
+    write!(out, "fn {}({}) -> {}", func.name, params.join(", "), func.returns).unwrap();
+    if let Some(cap) = &func.capability {
+        write!(out, " {}", cap).unwrap();
+    }
+    // External declarations have no body
+    if func.blocks.is_empty() {
+        out.push('\n');
+        return;
+    }
+    out.push_str(" {\n");
+    for block in &func.blocks {
+        writeln!(out, "{}:", block.label).unwrap();
+        for inst in &block.insts {
+            writeln!(out, "  {}", InstText { func, inst }).unwrap();
+        }
+        writeln!(out, "  {}", block.terminator).unwrap();
+    }
+    out.push_str("}\n");
+}
+
+pub(crate) fn binary_mnemonic(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::Div => "div",
+        BinOp::Rem => "rem",
+        BinOp::Eq => "eq",
+        BinOp::Ne => "ne",
+        BinOp::Lt => "lt",
+        BinOp::Le => "le",
+        BinOp::Gt => "gt",
+        BinOp::Ge => "ge",
+        BinOp::And => "and",
+        BinOp::Or => "or",
+    }
+}
+
+const BINARY_OPS: [BinOp; 13] = [
+    BinOp::Add, BinOp::Sub, BinOp::Mul, BinOp::Div, BinOp::Rem,
+    BinOp::Eq, BinOp::Ne, BinOp::Lt, BinOp::Le, BinOp::Gt, BinOp::Ge,
+    BinOp::And, BinOp::Or,
+];
+
+pub(crate) fn unary_mnemonic(op: UnaryOp) -> &'static str {
+    match op {
+        UnaryOp::Neg => "neg",
+        UnaryOp::Not => "not",
+    }
+}
+
+fn write_operands(f: &mut fmt::Formatter, operands: &[IrOperand]) -> fmt::Result {
+    for (i, operand) in operands.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", operand)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for IrValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IrValue::Int(n) => write!(f, "{}", n),
+            IrValue::Bool(b) => write!(f, "{}", b),
+            IrValue::Text(text) => {
+                f.write_char('"')?;
+                for c in text.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\t' => f.write_str("\\t")?,
+                        c => f.write_char(c)?,
+                    }
+                }
+                f.write_char('"')
+            }
+        }
+    }
+}
+
+/// Instruction with the function holding its result type
+struct InstText<'a> {
+    func: &'a IrFunction,
+    inst: &'a IrInst,
+}
+
+impl fmt::Display for InstText<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(dest) = self.inst.dest() {
+            write!(f, "{}", dest)?;
+            if let Some(ty) = self.func.value_type(dest) {
+                write!(f, ": {}", ty)?;
+            }
+            write!(f, " = ")?;
+        }
+        match self.inst {
+            IrInst::Const { value, .. } => write!(f, "const {}", value),
+            IrInst::Call { func, args, capability, .. } => {
+                write!(f, "call {}(", func)?;
+                write_operands(f, args)?;
+                write!(f, ")")?;
+                if let Some(cap) = capability {
+                    write!(f, " {}", cap)?;
+                }
+                Ok(())
+            }
+            IrInst::Alloc { size, .. } => write!(f, "alloc {}", size),
+            IrInst::Load { base, offset, .. } => write!(f, "load {}, {}", base, offset),
+            IrInst::Store { base, offset, value } => write!(f, "store {}, {}, {}", base, offset, value),
+            IrInst::Borrow { source, .. } => write!(f, "borrow {}", source),
+            IrInst::Binary { op, lhs, rhs, .. } => {
+                write!(f, "{} {}, {}", binary_mnemonic(*op), lhs, rhs)
+            }
+            IrInst::Unary { op, operand, .. } => write!(f, "{} {}", unary_mnemonic(*op), operand),
+            IrInst::Phi { incoming, .. } => {
+                write!(f, "phi")?;
+                for (i, (label, value)) in incoming.iter().enumerate() {
+                    write!(f, "{} [{}: {}]", if i > 0 { "," } else { "" }, label, value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for IrTerminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IrTerminator::Jump { target } => write!(f, "jump {}", target),
+            IrTerminator::Branch { cond, then_target, else_target } => {
+                write!(f, "branch {}, {}, {}", cond, then_target, else_target)
+            }
+            IrTerminator::Return { value: Some(value) } => write!(f, "return {}", value),
+            IrTerminator::Return { value: None } => write!(f, "return"),
+        }
+    }
+}
+
+// -- Lexer ------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    /// Keyword, label, function, struct or effect name
+    Ident(String),
+    /// `%`-prefixed SSA value
+    Value(String),
+    Int(i64),
+    Str(String),
+    /// Punctuation: ( ) { } [ ] , : = -> ! & <=
+    Sym(&'static str),
+    Eof,
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tok::Ident(name) => write!(f, "`{}`", name),
+            Tok::Value(value) => write!(f, "value `{}`", value),
+            Tok::Int(n) => write!(f, "number `{}`", n),
+            Tok::Str(_) => write!(f, "string"),
+            Tok::Sym(sym) => write!(f, "`{}`", sym),
+            Tok::Eof => write!(f, "end of input"),
+        }
+    }
+}
+
+/// Error in textual IR
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrParseError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl IrParseError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.message.clone())
+            .with_code("E0005")
+            .with_primary(self.span, "")
+    }
+}
+
+impl fmt::Display for IrParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.line, self.span.col, self.message)
+    }
+}
+
+impl std::error::Error for IrParseError {}
+
+type IrParseResult<T> = Result<T, IrParseError>;
+
+const SYMBOLS: [&str; 14] = ["->", "<=", "<:", "(", ")", "{", "}", "[", "]", ",", ":", "=", "!", "&"];
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | '$')
+}
+
+/// Split IR text into tokens; `;` starts a comment running to the end of
+/// the line
+fn tokenize(text: &str) -> IrParseResult<Vec<(Tok, Span)>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let (mut line, mut line_start) = (1, 0);
+
+    while let Some(&(start, c)) = chars.peek() {
+        let (tok_line, col) = (line, (start - line_start) as u32 + 1);
+        let span_to = move |end: usize| Span::new(start, end, tok_line, col);
+        if c == '\n' {
+            chars.next();
+            line += 1;
+            line_start = start + 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == ';' {
+            while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            continue;
+        }
+
+        let rest = &text[start..];
+        let is_number = c.is_ascii_digit()
+            || (c == '-' && rest[1..].starts_with(|d: char| d.is_ascii_digit()));
+        let tok = if is_number {
+            chars.next();
+            let mut end = start + c.len_utf8();
+            while let Some((i, _)) = chars.next_if(|(_, d)| d.is_ascii_digit()) {
+                end = i + 1;
+            }
+            let n = text[start..end].parse()
+                .map_err(|_| IrParseError { message: "integer out of range".to_string(), span: span_to(end) })?;
+            (Tok::Int(n), span_to(end))
+        } else if c == '%' || is_name_char(c) {
+            chars.next();
+            let mut end = start + c.len_utf8();
+            while let Some((i, d)) = chars.next_if(|&(_, d)| is_name_char(d)) {
+                end = i + d.len_utf8();
+            }
+            let name = text[start..end].to_string();
+            if c == '%' {
+                if name.len() == 1 {
+                    return Err(IrParseError { message: "expected a value name after `%`".to_string(), span: span_to(end) });
+                }
+                (Tok::Value(name), span_to(end))
+            } else {
+                (Tok::Ident(name), span_to(end))
+            }
+        } else if c == '"' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                let Some((i, c)) = chars.next() else {
+                    return Err(IrParseError { message: "unterminated string".to_string(), span: span_to(text.len()) });
+                };
+                match c {
+                    '"' => break tokens.push((Tok::Str(value.clone()), span_to(i + 1))),
+                    '\\' => {
+                        let escaped = match chars.next().map(|(_, c)| c) {
+                            Some('n') => '\n',
+                            Some('r') => '\r',
+                            Some('t') => '\t',
+                            Some(c @ ('"' | '\\')) => c,
+                            _ => return Err(IrParseError { message: "invalid escape in string".to_string(), span: span_to(i + 1) }),
+                        };
+                        value.push(escaped);
+                    }
+                    '\n' => {
+                        value.push(c);
+                        line += 1;
+                        line_start = i + 1;
+                    }
+                    c => value.push(c),
+                }
+            }
+            continue;
+        } else if let Some(sym) = SYMBOLS.iter().find(|sym| rest.starts_with(**sym)) {
+            for _ in 0..sym.len() {
+                chars.next();
+            }
+            (Tok::Sym(sym), span_to(start + sym.len()))
+        } else {
+            return Err(IrParseError { message: format!("unexpected character `{}`", c), span: span_to(start + c.len_utf8()) });
+        };
+        tokens.push(tok);
+    }
+    let end = Span::new(text.len(), text.len(), line, (text.len() - line_start) as u32 + 1);
+    tokens.push((Tok::Eof, end));
+    Ok(tokens)
+}
+
+// -- Parser -----------------------------------------------------------------
+
+/// Parse a module in the textual format
+pub fn parse_module(text: &str) -> IrParseResult<IrModule> {
+    let mut parser = IrParser { tokens: tokenize(text)?, pos: 0 };
+    parser.module()
+}
+
+struct IrParser {
+    tokens: Vec<(Tok, Span)>,
+    pos: usize,
+}
+
+impl IrParser {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].0
+    }
+
+    fn peek_at(&self, offset: usize) -> &Tok {
+        let index = (self.pos + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].0
+    }
+
+    fn span(&self) -> Span {
+        self.tokens[self.pos].1
+    }
+
+    fn advance(&mut self) -> Tok {
+        let tok = self.tokens[self.pos].0.clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn error<T>(&self, expected: &str) -> IrParseResult<T> {
+        Err(IrParseError {
+            message: format!("expected {}, found {}", expected, self.peek()),
+            span: self.span(),
+        })
+    }
+
+    fn at_sym(&self, sym: &str) -> bool {
+        matches!(self.peek(), Tok::Sym(s) if *s == sym)
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Tok::Ident(name) if name == keyword)
+    }
+
+    fn eat_sym(&mut self, sym: &str) -> bool {
+        let found = self.at_sym(sym);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.at_keyword(keyword);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> IrParseResult<()> {
+        if self.eat_sym(sym) { Ok(()) } else { self.error(&format!("`{}`", sym)) }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> IrParseResult<()> {
+        if self.eat_keyword(keyword) { Ok(()) } else { self.error(&format!("`{}`", keyword)) }
+    }
+
+    fn ident(&mut self) -> IrParseResult<String> {
+        match self.peek().clone() {
+            Tok::Ident(name) => {
+                self.advance();
+                Ok(name)
+            }
+            _ => self.error("a name"),
+        }
+    }
+
+    fn value(&mut self) -> IrParseResult<String> {
+        match self.peek().clone() {
+            Tok::Value(value) => {
+                self.advance();
+                Ok(value)
+            }
+            _ => self.error("a `%` value"),
+        }
+    }
+
+    fn number<T: TryFrom<i64>>(&mut self) -> IrParseResult<T> {
+        let span = self.span();
+        match self.peek().clone() {
+            Tok::Int(n) => {
+                self.advance();
+                T::try_from(n).map_err(|_| IrParseError {
+                    message: format!("number `{}` is out of range", n),
+                    span,
+                })
+            }
+            _ => self.error("a number"),
+        }
+    }
+
+    /// Comma-separated items up to the closing `close`, which is consumed
+    fn list<T>(
+        &mut self,
+        close: &str,
+        mut item: impl FnMut(&mut Self) -> IrParseResult<T>,
+    ) -> IrParseResult<Vec<T>> {
+        let mut items = Vec::new();
+        if self.eat_sym(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.eat_sym(close) {
+                return Ok(items);
+            }
+            self.expect_sym(",")?;
+        }
+    }
+
+    fn module(&mut self) -> IrParseResult<IrModule> {
+        self.expect_keyword("module")?;
+        let name = self.ident()?;
+        let capability = if self.at_sym("!") { Some(self.capability()?) } else { None };
+        let mut effects = Vec::new();
+        while self.at_keyword("effect") {
+            let span = self.span();
+            self.advance();
+            let name = self.ident()?;
+            let parent = if self.eat_sym("<:") { Some(self.ident()?) } else { None };
+            effects.push(EffectDecl { name, parent, span });
+        }
+        let mut functions = Vec::new();
+        while self.at_keyword("fn") {
+            functions.push(self.function()?);
+        }
+        if *self.peek() != Tok::Eof {
+            return self.error("`fn`");
+        }
+        Ok(IrModule { name, capability, effects, functions })
+    }
+
+    fn capability(&mut self) -> IrParseResult<IrCapability> {
+        self.expect_sym("!")?;
+        self.expect_sym("{")?;
+        let mut effects = Vec::new();
+        let mut budgets = ResourceBudget::default();
+        self.list("}", |p| {
+            let name = p.ident()?;
+            match name.as_str() {
+                "tokens" | "latency" | "energy" => {
+                    let resource = Resource::from_name(&name).expect("matched a resource name");
+                    p.expect_sym("<=")?;
+                    let n: u64 = p.number()?;
+                    // Bounds print with a unit, except for plain token counts
+                    let scale = if resource == Resource::Tokens && !matches!(p.peek(), Tok::Ident(_)) {
+                        1
+                    } else {
+                        let span = p.span();
+                        let unit = p.ident()?;
+                        resource.units().iter().find(|(name, _)| *name == unit).map(|&(_, scale)| scale)
+                            .ok_or_else(|| IrParseError { message: format!("unknown {} unit `{}`", resource, unit), span })?
+                    };
+                    let bound = n.checked_mul(scale).ok_or_else(|| IrParseError {
+                        message: format!("{} bound is out of range", resource),
+                        span: p.span(),
+                    })?;
+                    match resource {
+                        Resource::Tokens => budgets.tokens = Some(bound),
+                        Resource::Latency => budgets.latency_us = Some(bound),
+                        Resource::Energy => budgets.energy_uj = Some(bound),
+                    }
+                }
+                // Any name is an effect, as effect variables are never declared
+                _ => effects.push(Effect::named(&name)),
+            }
+            Ok(())
+        })?;
+        let inferred = self.eat_keyword("inferred");
+        Ok(IrCapability { effects, budgets, inferred })
+    }
+
+    fn ty(&mut self) -> IrParseResult<IrType> {
+        if self.eat_sym("[") {
+            let element = self.ty()?;
+            self.expect_sym("]")?;
+            return Ok(IrType::Array(Box::new(element)));
+        }
+        let name = self.ident()?;
+        Ok(match name.as_str() {
+            "unit" => IrType::Unit,
+            "bool" => IrType::Bool,
+            "text" => IrType::Text,
+            "i8" => IrType::Int(IntWidth::I8),
+            "i16" => IrType::Int(IntWidth::I16),
+            "i32" => IrType::Int(IntWidth::I32),
+            "i64" => IrType::Int(IntWidth::I64),
+            "fn" => {
+                self.expect_sym("(")?;
+                let params = self.list(")", Self::ty)?;
+                self.expect_sym("->")?;
+                let returns = Box::new(self.ty()?);
+                let capability = self.capability()?;
+                IrType::Function { params, returns, capability }
+            }
+            _ => IrType::Struct(name),
+        })
+    }
+
+    fn function(&mut self) -> IrParseResult<IrFunction> {
+        let span = self.span();
+        self.expect_keyword("fn")?;
+        let name = self.ident()?;
+        let mut value_types = HashMap::new();
+        self.expect_sym("(")?;
+        let params = self.list(")", |p| {
+            let value = p.value()?;
+            p.expect_sym(":")?;
+            let ty = p.ty()?;
+            value_types.insert(value.clone(), ty.clone());
+            Ok((value[1..].to_string(), ty))
+        })?;
+        self.expect_sym("->")?;
+        let returns = self.ty()?;
+        let capability = if self.at_sym("!") { Some(self.capability()?) } else { None };
+
+        let mut blocks = Vec::new();
+        if self.eat_sym("{") {
+            while !self.eat_sym("}") {
+                blocks.push(self.block(&mut value_types)?);
+            }
+            if blocks.is_empty() {
+                return self.error("a block");
+            }
+        }
+        Ok(IrFunction { name, span, params, returns, capability, blocks, value_types, value_names: HashMap::new() })
+    }
+
+    fn block(&mut self, value_types: &mut HashMap<String, IrType>) -> IrParseResult<IrBlock> {
+        let label = self.ident()?;
+        self.expect_sym(":")?;
+        let mut insts = Vec::new();
+        let mut spans = Vec::new();
+        loop {
+            let span = self.span();
+            if let Some(terminator) = self.terminator()? {
+                return Ok(IrBlock { label, insts, spans, terminator, terminator_span: span });
+            }
+            insts.push(self.inst(value_types)?);
+            spans.push(span);
+        }
+    }
+
+    /// Operand with its ownership tag; functions used as values are bare
+    /// names
+    fn operand(&mut self) -> IrParseResult<IrOperand> {
+        let mode = if self.eat_keyword("move") {
+            Ownership::Move
+        } else if self.eat_sym("&") {
+            if self.eat_keyword("shared") {
+                Ownership::Shared
+            } else if self.eat_keyword("unique") {
+                Ownership::Unique
+            } else {
+                return self.error("`shared` or `unique`");
+            }
+        } else {
+            Ownership::Copy
+        };
+        match self.peek().clone() {
+            Tok::Value(value) | Tok::Ident(value) => {
+                self.advance();
+                Ok(IrOperand::new(value, mode))
+            }
+            _ => self.error("an operand"),
+        }
+    }
+
+    fn terminator(&mut self) -> IrParseResult<Option<IrTerminator>> {
+        let terminator = if self.eat_keyword("jump") {
+            IrTerminator::Jump { target: self.ident()? }
+        } else if self.eat_keyword("branch") {
+            let cond = self.operand()?;
+            self.expect_sym(",")?;
+            let then_target = self.ident()?;
+            self.expect_sym(",")?;
+            let else_target = self.ident()?;
+            IrTerminator::Branch { cond, then_target, else_target }
+        } else if self.eat_keyword("return") {
+            // The next block's label or the closing brace follows a bare return
+            let bare = self.at_sym("}")
+                || (matches!(self.peek(), Tok::Ident(_)) && *self.peek_at(1) == Tok::Sym(":"));
+            let value = if bare { None } else { Some(self.operand()?) };
+            IrTerminator::Return { value }
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(terminator))
+    }
+
+    fn inst(&mut self, value_types: &mut HashMap<String, IrType>) -> IrParseResult<IrInst> {
+        if self.eat_keyword("store") {
+            let base = self.operand()?;
+            self.expect_sym(",")?;
+            let offset = self.operand()?;
+            self.expect_sym(",")?;
+            let value = self.operand()?;
+            return Ok(IrInst::Store { base, offset, value });
+        }
+
+        let dest = self.value()?;
+        if self.eat_sym(":") {
+            let ty = self.ty()?;
+            value_types.insert(dest.clone(), ty);
+        }
+        self.expect_sym("=")?;
+        let span = self.span();
+        let opcode = self.ident()?;
+        let inst = match opcode.as_str() {
+            "const" => {
+                let value = match self.peek().clone() {
+                    Tok::Int(n) => IrValue::Int(n),
+                    Tok::Str(text) => IrValue::Text(text),
+                    Tok::Ident(b) if b == "true" || b == "false" => IrValue::Bool(b == "true"),
+                    _ => return self.error("a constant"),
+                };
+                self.advance();
+                IrInst::Const { dest, value }
+            }
+            "call" => {
+                let func = match self.advance() {
+                    Tok::Value(func) | Tok::Ident(func) => func,
+                    _ => {
+                        self.pos -= 1;
+                        return self.error("a function");
+                    }
+                };
+                self.expect_sym("(")?;
+                let args = self.list(")", Self::operand)?;
+                let capability = if self.at_sym("!") { Some(self.capability()?) } else { None };
+                IrInst::Call { dest, func, args, capability }
+            }
+            "alloc" => IrInst::Alloc { dest, size: self.number()? },
+            "load" => {
+                let base = self.operand()?;
+                self.expect_sym(",")?;
+                IrInst::Load { dest, base, offset: self.operand()? }
+            }
+            "borrow" => IrInst::Borrow { dest, source: self.operand()? },
+            "neg" | "not" => {
+                let op = if opcode == "neg" { UnaryOp::Neg } else { UnaryOp::Not };
+                IrInst::Unary { dest, op, operand: self.operand()? }
+            }
+            "phi" => {
+                let mut incoming = Vec::new();
+                if self.at_sym("[") {
+                    loop {
+                        self.expect_sym("[")?;
+                        let label = self.ident()?;
+                        self.expect_sym(":")?;
+                        incoming.push((label, self.operand()?));
+                        self.expect_sym("]")?;
+                        if !self.eat_sym(",") {
+                            break;
+                        }
+                    }
+                }
+                IrInst::Phi { dest, incoming }
+            }
+            _ => match BINARY_OPS.iter().find(|op| binary_mnemonic(**op) == opcode) {
+                Some(&op) => {
+                    let lhs = self.operand()?;
+                    self.expect_sym(",")?;
+                    IrInst::Binary { dest, op, lhs, rhs: self.operand()? }
+                }
+                None => {
+                    return Err(IrParseError {
+                        message: format!("unknown instruction `{}`", opcode),
+                        span,
+                    })
+                }
+            },
+        };
+        Ok(inst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lower_module, Parser};
+
+    fn round_trip(text: &str) {
+        let module = parse_module(text).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(print_module(&module), text);
+    }
+
+    #[test]
+    fn test_round_trip_lowered_module() {
+        let (module, errors) = Parser::new("module demo !{io, net, tokens <= 500, latency <= 20ms}
+fn fetch(url: Text) -> Text !{net}
+fn log(msg: Text) !{io}
+fn pick(xs: Array<Int>, f: fn(Int) -> Int !{io}) -> Array<Int> !{io, net} {
+    let total = 0;
+    for x in xs { total = total + f(x); }
+    if total > 10 && !(total == 12) { log(\"big\"); }
+    let page = fetch(\"x\");
+    [total, -len(xs)]
+}
+fn first(xs: Array<Int>) -> Int { xs[0] }").parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let text = print_module(&lower_module(&module));
+
+        let parsed = parse_module(&text).unwrap_or_else(|e| panic!("{}\n{}", e, text));
+        assert_eq!(print_module(&parsed), text);
+        assert!(text.contains("fn first(%xs: [i64]) -> i64 !{pure} inferred {\n"), "{}", text);
+        assert!(text.contains("call %f(%"), "{}", text);
+        assert!(text.contains("!{io, net, tokens <= 500, latency <= 20ms}"), "{}", text);
+
+        let pick = &parsed.functions[2];
+        assert_eq!(pick.value_type("%f").unwrap().to_string(), "fn(i64) -> i64 !{io}");
+        assert_eq!(pick.block("entry").unwrap().terminator.successors(), vec!["while1"]);
+    }
+
+    #[test]
+    fn test_round_trip_hand_written() {
+        round_trip("module m
+effect fs <: io
+effect clock
+
+fn ext(%a: [[i8]], %s: Point) -> fn() -> unit !{} !{alloc, energy <= 3mJ}
+
+fn timed() -> unit !{tokens <= 1500, latency <= 2s, energy <= 250uJ}
+
+fn f(%a: [i64]) -> unit {
+entry:
+  %0: i64 = const -7
+  %t: text = const \"tab\\there\"
+  %r: [i64] = borrow &shared %a
+  %1 = load &shared %r, %0
+  store &unique %a, %0, move %1
+  %2: bool = not %t
+  %3 = call ext(%a, ext)
+  branch %2, done, done
+done:
+  %4: i64 = phi [entry: %0], [entry: %1]
+  %5: i64 = phi
+  return
+}
+");
+    }
+
+    #[test]
+    fn test_comments_and_spans() {
+        let module = parse_module("module m ; the module
+fn f() -> i64 {
+entry: ; first block
+  %0: i64 = const 1
+  return %0
+}").unwrap();
+        let block = &module.functions[0].blocks[0];
+        assert_eq!(block.spans[0].line, 4);
+        assert_eq!(block.terminator_span.line, 5);
+        assert_eq!(module.functions[0].value_type("%0"), Some(&IrType::INT));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let err = parse_module("module m\nfn f() -> i64 {\nentry:\n  %0: i64 = frob 1\n}").unwrap_err();
+        assert_eq!(err.to_string(), "4:13: unknown instruction `frob`");
+
+        let err = parse_module("module m\nfn f() -> i64 {\nentry:\n  %0: i64 = const 1\n}").unwrap_err();
+        assert_eq!(err.message, "expected a `%` value, found `}`");
+
+        let err = parse_module("module m !{latency <= 5min}").unwrap_err();
+        assert_eq!(err.message, "unknown latency unit `min`");
+        assert_eq!(err.to_diagnostic().code.as_deref(), Some("E0005"));
+    }
+}
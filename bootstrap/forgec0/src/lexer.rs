@@ -1,18 +1,23 @@
-/// Lexer module for Forge Lang - Phase α
-/// 
-/// This module will handle tokenization including:
-/// - Basic tokens (identifiers, keywords, literals)
-/// - Capability syntax: !{...}
-/// - Intent blocks: ⟦...⟧
-/// - Effect annotations
+//! Lexer module for Forge Lang - Phase α
+//!
+//! This module will handle tokenization including:
+//! - Basic tokens (identifiers, keywords, literals)
+//! - Capability syntax: !{...}
+//! - Intent blocks: ⟦...⟧
+//! - Effect annotations
 
 use std::fmt;
 
+use crate::span::Span;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     // Identifiers and literals
     Ident(String),
     Number(i64),
+    /// Numeric literal that is not an `i64`: it has a fractional part, as
+    /// in `1.5`, or is too large
+    Numeral(String),
     String(String),
     
     // Keywords
@@ -20,8 +25,17 @@ pub enum Token {
     Let,
     Module,
     Use,
+    True,
+    False,
+    If,
+    Else,
+    While,
+    For,
+    In,
+    Match,
+    Enum,
     
-    // Capability tokens
+    // Capability tokens; `!` is also boolean negation in expressions
     Bang,           // !
     LBrace,         // {
     RBrace,         // }
@@ -32,13 +46,32 @@ pub enum Token {
     
     // Operators
     Arrow,          // ->
-    LessThanEqual,  // ≤
+    LessThanEqual,  // ≤ or <=
     Colon,          // :
     Comma,          // ,
+    Assign,         // =
+    Semicolon,      // ;
+    Plus,           // +
+    Minus,          // -
+    Star,           // *
+    Slash,          // /
+    Percent,        // %
+    EqualEqual,     // ==
+    NotEqual,       // !=
+    Less,           // <
+    Greater,        // >
+    GreaterThanEqual, // ≥ or >=
+    AndAnd,         // &&
+    OrOr,           // ||
+    
+    FatArrow,       // =>
+    ColonColon,     // ::
     
     // Delimiters
     LParen,         // (
     RParen,         // )
+    LBracket,       // [
+    RBracket,       // ]
     
     // Special
     Eof,
@@ -50,11 +83,21 @@ impl fmt::Display for Token {
         match self {
             Token::Ident(s) => write!(f, "Ident({})", s),
             Token::Number(n) => write!(f, "Number({})", n),
+            Token::Numeral(s) => write!(f, "Numeral({})", s),
             Token::String(s) => write!(f, "String(\"{}\")", s),
             Token::Fn => write!(f, "fn"),
             Token::Let => write!(f, "let"),
             Token::Module => write!(f, "module"),
             Token::Use => write!(f, "use"),
+            Token::True => write!(f, "true"),
+            Token::False => write!(f, "false"),
+            Token::If => write!(f, "if"),
+            Token::Else => write!(f, "else"),
+            Token::While => write!(f, "while"),
+            Token::For => write!(f, "for"),
+            Token::In => write!(f, "in"),
+            Token::Match => write!(f, "match"),
+            Token::Enum => write!(f, "enum"),
             Token::Bang => write!(f, "!"),
             Token::LBrace => write!(f, "{{"),
             Token::RBrace => write!(f, "}}"),
@@ -64,6 +107,24 @@ impl fmt::Display for Token {
             Token::LessThanEqual => write!(f, "≤"),
             Token::Colon => write!(f, ":"),
             Token::Comma => write!(f, ","),
+            Token::Assign => write!(f, "="),
+            Token::Semicolon => write!(f, ";"),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Star => write!(f, "*"),
+            Token::Slash => write!(f, "/"),
+            Token::Percent => write!(f, "%"),
+            Token::EqualEqual => write!(f, "=="),
+            Token::NotEqual => write!(f, "!="),
+            Token::Less => write!(f, "<"),
+            Token::Greater => write!(f, ">"),
+            Token::GreaterThanEqual => write!(f, "≥"),
+            Token::AndAnd => write!(f, "&&"),
+            Token::OrOr => write!(f, "||"),
+            Token::FatArrow => write!(f, "=>"),
+            Token::ColonColon => write!(f, "::"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
             Token::LParen => write!(f, "("),
             Token::RParen => write!(f, ")"),
             Token::Eof => write!(f, "EOF"),
@@ -72,12 +133,23 @@ impl fmt::Display for Token {
     }
 }
 
+/// Token together with the source span it was read from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
 /// Lexer implementation
 #[derive(Debug)]
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
     current_char: Option<char>,
+    // Byte offset, line and column of `current_char`
+    offset: usize,
+    line: u32,
+    col: u32,
 }
 
 impl Lexer {
@@ -86,25 +158,41 @@ impl Lexer {
             input: input.chars().collect(),
             position: 0,
             current_char: None,
+            offset: 0,
+            line: 1,
+            col: 1,
         };
         lexer.read_char();
         lexer
     }
     
     fn read_char(&mut self) {
+        if let Some(ch) = self.current_char {
+            self.offset += ch.len_utf8();
+            if ch == '\n' {
+                self.line += 1;
+                self.col = 1;
+            } else {
+                self.col += 1;
+            }
+        }
         self.current_char = self.input.get(self.position).copied();
         self.position += 1;
     }
     
-    #[allow(dead_code)]
     fn peek_char(&self) -> Option<char> {
         self.input.get(self.position).copied()
     }
     
+    /// Skip whitespace and `//` line comments
     fn skip_whitespace(&mut self) {
         while let Some(ch) = self.current_char {
             if ch.is_whitespace() {
                 self.read_char();
+            } else if ch == '/' && self.peek_char() == Some('/') {
+                while !matches!(self.current_char, None | Some('\n')) {
+                    self.read_char();
+                }
             } else {
                 break;
             }
@@ -124,29 +212,82 @@ impl Lexer {
         ident
     }
     
-    fn read_number(&mut self) -> i64 {
+    fn read_digits(&mut self, digits: &mut String) {
+        while let Some(ch) = self.current_char.filter(char::is_ascii_digit) {
+            digits.push(ch);
+            self.read_char();
+        }
+    }
+
+    /// Read an integer, or a decimal such as `1.5` when a digit follows the
+    /// dot
+    fn read_number(&mut self) -> Token {
         let mut num_str = String::new();
-        while let Some(ch) = self.current_char {
-            if ch.is_numeric() {
-                num_str.push(ch);
-                self.read_char();
-            } else {
-                break;
+        self.read_digits(&mut num_str);
+        if self.current_char == Some('.') && self.peek_char().is_some_and(|ch| ch.is_ascii_digit()) {
+            num_str.push('.');
+            self.read_char();
+            self.read_digits(&mut num_str);
+        }
+        match num_str.parse() {
+            Ok(num) => Token::Number(num),
+            Err(_) => Token::Numeral(num_str),
+        }
+    }
+    
+    /// Read a string literal body; `None` if the literal is unterminated
+    fn read_string(&mut self) -> Option<String> {
+        self.read_char(); // opening quote
+        let mut value = String::new();
+        loop {
+            match self.current_char? {
+                '"' => {
+                    self.read_char();
+                    return Some(value);
+                }
+                '\\' => {
+                    self.read_char();
+                    let escaped = match self.current_char? {
+                        'n' => '\n',
+                        't' => '\t',
+                        other => other,
+                    };
+                    value.push(escaped);
+                    self.read_char();
+                }
+                ch => {
+                    value.push(ch);
+                    self.read_char();
+                }
             }
         }
-        num_str.parse().unwrap_or(0)
     }
     
-    pub fn next_token(&mut self) -> Token {
+    /// Read the next token along with its span
+    pub fn next_token(&mut self) -> SpannedToken {
         self.skip_whitespace();
         
+        let (start, line, col) = (self.offset, self.line, self.col);
+        let token = self.read_token();
+        SpannedToken {
+            token,
+            span: Span::new(start, self.offset, line, col),
+        }
+    }
+    
+    fn read_token(&mut self) -> Token {
         match self.current_char {
             None => Token::Eof,
             Some(ch) => {
                 let token = match ch {
                     '!' => {
                         self.read_char();
-                        Token::Bang
+                        if self.current_char == Some('=') {
+                            self.read_char();
+                            Token::NotEqual
+                        } else {
+                            Token::Bang
+                        }
                     }
                     '{' => {
                         self.read_char();
@@ -166,19 +307,108 @@ impl Lexer {
                     }
                     ':' => {
                         self.read_char();
-                        Token::Colon
+                        if self.current_char == Some(':') {
+                            self.read_char();
+                            Token::ColonColon
+                        } else {
+                            Token::Colon
+                        }
+                    }
+                    '[' => {
+                        self.read_char();
+                        Token::LBracket
+                    }
+                    ']' => {
+                        self.read_char();
+                        Token::RBracket
                     }
                     ',' => {
                         self.read_char();
                         Token::Comma
                     }
+                    '=' => {
+                        self.read_char();
+                        if self.current_char == Some('=') {
+                            self.read_char();
+                            Token::EqualEqual
+                        } else if self.current_char == Some('>') {
+                            self.read_char();
+                            Token::FatArrow
+                        } else {
+                            Token::Assign
+                        }
+                    }
+                    '<' => {
+                        self.read_char();
+                        if self.current_char == Some('=') {
+                            self.read_char();
+                            Token::LessThanEqual
+                        } else {
+                            Token::Less
+                        }
+                    }
+                    '>' => {
+                        self.read_char();
+                        if self.current_char == Some('=') {
+                            self.read_char();
+                            Token::GreaterThanEqual
+                        } else {
+                            Token::Greater
+                        }
+                    }
+                    '≥' => {
+                        self.read_char();
+                        Token::GreaterThanEqual
+                    }
+                    '+' => {
+                        self.read_char();
+                        Token::Plus
+                    }
+                    '*' => {
+                        self.read_char();
+                        Token::Star
+                    }
+                    '/' => {
+                        self.read_char();
+                        Token::Slash
+                    }
+                    '%' => {
+                        self.read_char();
+                        Token::Percent
+                    }
+                    '&' => {
+                        self.read_char();
+                        if self.current_char == Some('&') {
+                            self.read_char();
+                            Token::AndAnd
+                        } else {
+                            Token::Unknown('&')
+                        }
+                    }
+                    '|' => {
+                        self.read_char();
+                        if self.current_char == Some('|') {
+                            self.read_char();
+                            Token::OrOr
+                        } else {
+                            Token::Unknown('|')
+                        }
+                    }
+                    ';' => {
+                        self.read_char();
+                        Token::Semicolon
+                    }
+                    '"' => match self.read_string() {
+                        Some(value) => Token::String(value),
+                        None => Token::Unknown('"'),
+                    },
                     '-' => {
                         self.read_char();
                         if self.current_char == Some('>') {
                             self.read_char();
                             Token::Arrow
                         } else {
-                            Token::Unknown('-')
+                            Token::Minus
                         }
                     }
                     '⟦' => {
@@ -193,20 +423,26 @@ impl Lexer {
                         self.read_char();
                         Token::LessThanEqual
                     }
-                    _ if ch.is_alphabetic() => {
+                    _ if ch.is_alphabetic() || ch == '_' => {
                         let ident = self.read_identifier();
                         match ident.as_str() {
                             "fn" => Token::Fn,
                             "let" => Token::Let,
                             "module" => Token::Module,
                             "use" => Token::Use,
+                            "true" => Token::True,
+                            "false" => Token::False,
+                            "if" => Token::If,
+                            "else" => Token::Else,
+                            "while" => Token::While,
+                            "for" => Token::For,
+                            "in" => Token::In,
+                            "match" => Token::Match,
+                            "enum" => Token::Enum,
                             _ => Token::Ident(ident),
                         }
                     }
-                    _ if ch.is_numeric() => {
-                        let num = self.read_number();
-                        Token::Number(num)
-                    }
+                    _ if ch.is_ascii_digit() => self.read_number(),
                     _ => {
                         self.read_char();
                         Token::Unknown(ch)
@@ -220,12 +456,17 @@ impl Lexer {
 
 /// Tokenize entire input
 pub fn tokenize(input: &str) -> Vec<Token> {
+    tokenize_spanned(input).into_iter().map(|t| t.token).collect()
+}
+
+/// Tokenize entire input, keeping the span of every token
+pub fn tokenize_spanned(input: &str) -> Vec<SpannedToken> {
     let mut lexer = Lexer::new(input);
     let mut tokens = Vec::new();
     
     loop {
         let token = lexer.next_token();
-        if token == Token::Eof {
+        if token.token == Token::Eof {
             tokens.push(token);
             break;
         }
@@ -266,4 +507,84 @@ mod tests {
         assert_eq!(tokens[1], Token::LessThanEqual);
         assert_eq!(tokens[2], Token::Number(100));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_token_spans() {
+        let tokens = tokenize_spanned("fn f()\n  !{io} ≤ 7");
+        assert_eq!(tokens[0].span, Span::new(0, 2, 1, 1));
+        assert_eq!(tokens[1].span, Span::new(3, 4, 1, 4));
+        // `!` on the second line, after two spaces
+        assert_eq!(tokens[4].token, Token::Bang);
+        assert_eq!(tokens[4].span, Span::new(9, 10, 2, 3));
+        // `≤` is three bytes wide but a single column
+        assert_eq!(tokens[8].token, Token::LessThanEqual);
+        assert_eq!(tokens[8].span, Span::new(15, 18, 2, 9));
+        assert_eq!(tokens[9].span, Span::new(19, 20, 2, 11));
+        assert_eq!(tokens[10].token, Token::Eof);
+    }
+    
+    #[test]
+    fn test_comments_and_strings() {
+        let tokens = tokenize("// header\nlet s = \"a \\\"b\\\"\\n\"; // trailing");
+        assert_eq!(tokens, vec![
+            Token::Let,
+            Token::Ident("s".to_string()),
+            Token::Assign,
+            Token::String("a \"b\"\n".to_string()),
+            Token::Semicolon,
+            Token::Eof,
+        ]);
+    }
+    
+    #[test]
+    fn test_operator_tokens() {
+        let tokens = tokenize("a + b - c * d / e % f == g != h < i <= j > k >= l && !m || n -> o ≥ p");
+        let ops: Vec<Token> = tokens.into_iter()
+            .filter(|t| !matches!(t, Token::Ident(_) | Token::Eof))
+            .collect();
+        assert_eq!(ops, vec![
+            Token::Plus, Token::Minus, Token::Star, Token::Slash, Token::Percent,
+            Token::EqualEqual, Token::NotEqual, Token::Less, Token::LessThanEqual,
+            Token::Greater, Token::GreaterThanEqual, Token::AndAnd, Token::Bang,
+            Token::OrOr, Token::Arrow, Token::GreaterThanEqual,
+        ]);
+    }
+    
+    #[test]
+    fn test_bang_before_brace_and_equals() {
+        assert_eq!(tokenize("!{io}")[0], Token::Bang);
+        assert_eq!(tokenize("!flag")[0], Token::Bang);
+        assert_eq!(tokenize("a!=b")[1], Token::NotEqual);
+        assert_eq!(tokenize("true false")[..2], [Token::True, Token::False]);
+    }
+    
+    #[test]
+    fn test_control_flow_tokens() {
+        let tokens = tokenize("if else while for in match enum _ x => Color::Red [ ]");
+        assert_eq!(tokens, vec![
+            Token::If, Token::Else, Token::While, Token::For, Token::In,
+            Token::Match, Token::Enum,
+            Token::Ident("_".to_string()), Token::Ident("x".to_string()),
+            Token::FatArrow,
+            Token::Ident("Color".to_string()), Token::ColonColon, Token::Ident("Red".to_string()),
+            Token::LBracket, Token::RBracket, Token::Eof,
+        ]);
+    }
+    
+    #[test]
+    fn test_unterminated_string() {
+        let tokens = tokenize_spanned("x \"abc");
+        assert_eq!(tokens[1].token, Token::Unknown('"'));
+        assert_eq!(tokens[1].span.start, 2);
+    }
+
+    #[test]
+    fn test_decimal_and_oversized_numbers() {
+        let tokens = tokenize("1.5s 2.x 99999999999999999999");
+        assert_eq!(tokens, vec![
+            Token::Numeral("1.5".to_string()), Token::Ident("s".to_string()),
+            Token::Number(2), Token::Unknown('.'), Token::Ident("x".to_string()),
+            Token::Numeral("99999999999999999999".to_string()), Token::Eof,
+        ]);
+    }
+}
@@ -1,15 +1,41 @@
+pub mod span;
+pub mod diagnostics;
 pub mod ast;
 pub mod lexer;
 pub mod parser;
+pub mod desugar;
+pub mod effects;
+pub mod budgets;
+pub mod typeck;
 pub mod ir;
+pub mod ir_text;
 pub mod lower;
+pub mod ownership;
+pub mod verify;
+pub mod interp;
+pub mod wasm;
 
 // Re-export commonly used types
-pub use lexer::{Token, tokenize};
-pub use ast::{Effect, Capability, Type, Expr, Stmt, Module};
+pub use span::Span;
+pub use diagnostics::{Diagnostic, Severity, SourceFile, RenderStyle};
+pub use lexer::{Token, SpannedToken, tokenize, tokenize_spanned};
+pub use ast::{Effect, EffectLattice, EffectDecl, Resource, Capability, Type, TypeKind, Expr, ExprKind, Stmt, StmtKind, Block, Module, BinOp, UnaryOp,
+              MatchArm, Pattern, PatternKind};
 pub use parser::{Parser, ParseError};
-pub use ir::{IrModule, IrFunction, IrCapability};
-pub use lower::lower_module;
+pub use desugar::desugar_module;
+pub use effects::{check_effects, infer_effects};
+pub use budgets::{check_budgets, infer_costs, Cost};
+pub use typeck::{check_types, Ty, TypeTable};
+pub use ir::{IrModule, IrFunction, IrCapability, IrOperand, IrType, Ownership};
+pub use ir_text::{print_module, parse_module, IrParseError};
+pub use lower::{lower_module, lower_module_with_diagnostics, lower_module_with_types};
+pub use ownership::check_ownership;
+pub use verify::verify_module;
+pub use interp::{
+    Interpreter, Host, StdHost, Value, RuntimeError, Clock, SystemClock, FakeClock, MAX_CALL_DEPTH,
+    INTERPRETER_STACK_SIZE,
+};
+pub use wasm::{emit_wasm, emit_wat, WasmError};
 
 /// Legacy lexer function for backward compatibility
 /// Deprecated: Use lexer::tokenize() instead
@@ -29,42 +55,73 @@ mod tests {
     
     #[test]
     fn test_effect_hierarchy() {
-        use ast::Effect;
-        // Effect lattice: pure < alloc < io < net
-        let effects = vec![Effect::Pure, Effect::Alloc, Effect::Io, Effect::Net];
-        assert_eq!(effects.len(), 4);
+        use ast::{Effect, EffectLattice};
+        // Built-in lattice: pure < alloc < io < net
+        let lattice = EffectLattice::default();
+        assert_eq!(lattice.top(), Effect::named("net"));
+        assert!(["alloc", "io", "net"].iter().all(|name| lattice.contains(name)));
     }
     
     #[test]
     fn test_effect_subsumption() {
         use ast::Effect;
+        let lattice = ast::EffectLattice::default();
+        let [pure, alloc, io, net] = ["pure", "alloc", "io", "net"].map(Effect::named);
         
         // Pure subsumes everything
-        assert!(Effect::Pure.subsumes(&Effect::Pure));
-        assert!(Effect::Pure.subsumes(&Effect::Alloc));
-        assert!(Effect::Pure.subsumes(&Effect::Io));
-        assert!(Effect::Pure.subsumes(&Effect::Net));
+        assert!(lattice.subsumes(&pure, &pure));
+        assert!(lattice.subsumes(&pure, &alloc));
+        assert!(lattice.subsumes(&pure, &io));
+        assert!(lattice.subsumes(&pure, &net));
         
         // Alloc subsumes higher effects
-        assert!(!Effect::Alloc.subsumes(&Effect::Pure));
-        assert!(Effect::Alloc.subsumes(&Effect::Alloc));
-        assert!(Effect::Alloc.subsumes(&Effect::Io));
-        assert!(Effect::Alloc.subsumes(&Effect::Net));
+        assert!(!lattice.subsumes(&alloc, &pure));
+        assert!(lattice.subsumes(&alloc, &alloc));
+        assert!(lattice.subsumes(&alloc, &io));
+        assert!(lattice.subsumes(&alloc, &net));
         
         // Net only subsumes itself
-        assert!(!Effect::Net.subsumes(&Effect::Pure));
-        assert!(!Effect::Net.subsumes(&Effect::Alloc));
-        assert!(!Effect::Net.subsumes(&Effect::Io));
-        assert!(Effect::Net.subsumes(&Effect::Net));
+        assert!(!lattice.subsumes(&net, &pure));
+        assert!(!lattice.subsumes(&net, &alloc));
+        assert!(!lattice.subsumes(&net, &io));
+        assert!(lattice.subsumes(&net, &net));
     }
     
     #[test]
     fn test_effect_join() {
         use ast::Effect;
+        let lattice = ast::EffectLattice::default();
+        let [pure, alloc, io, net] = ["pure", "alloc", "io", "net"].map(Effect::named);
+        
+        assert_eq!(lattice.join(&pure, &pure), pure);
+        assert_eq!(lattice.join(&pure, &net), net);
+        assert_eq!(lattice.join(&alloc, &io), io);
+        assert_eq!(lattice.join(&io, &alloc), io);
+    }
+    
+    #[test]
+    fn test_declared_effects_form_sets() {
+        use ast::{Effect, EffectLattice};
+        let mut lattice = EffectLattice::default();
+        lattice.declare("fs", Some("io")).unwrap();
+        lattice.declare("clock", None).unwrap();
+        lattice.declare("llm", Some("net")).unwrap();
+        let [fs, clock, llm, io] = ["fs", "clock", "llm", "io"].map(Effect::named);
+        
+        // Unrelated effects join into a set and meet at `pure`
+        let both = lattice.join(&fs, &clock);
+        assert_eq!(both.to_string(), "clock, fs");
+        assert!(!lattice.subsumes(&clock, &fs) && !lattice.subsumes(&fs, &clock));
+        assert!(lattice.subsumes(&fs, &both) && lattice.subsumes(&clock, &both));
+        assert!(!lattice.subsumes(&both, &io));
+        assert_eq!(lattice.meet(&fs, &clock), Effect::pure());
         
-        assert_eq!(Effect::Pure.join(&Effect::Pure), Effect::Pure);
-        assert_eq!(Effect::Pure.join(&Effect::Net), Effect::Net);
-        assert_eq!(Effect::Alloc.join(&Effect::Io), Effect::Io);
-        assert_eq!(Effect::Io.join(&Effect::Alloc), Effect::Io);
+        // Declared effects sit below their parent
+        assert!(lattice.subsumes(&fs, &io));
+        assert_eq!(lattice.join(&fs, &io), io);
+        assert_eq!(lattice.meet(&both, &io), fs);
+        assert_eq!(lattice.meet(&lattice.join(&both, &llm), &io), fs);
+        assert_eq!(lattice.top().to_string(), "clock, net");
+        assert!(lattice.declare("fs", None).is_err());
     }
 }
@@ -1,61 +1,813 @@
-/// Lowering module - AST to IR conversion
+//! Lowering module - AST to IR conversion
+//!
+//! Function bodies are desugared (see `desugar`) and then lowered to SSA
+//! using the algorithm of Braun et al., "Simple and Efficient Construction
+//! of Static Single Assignment Form" (CC 2013): each local variable is
+//! tracked per block and phis are placed on demand while reading it.
+
+use std::collections::HashMap;
 
 use crate::ast;
-use crate::ir;
+use crate::desugar::{desugar_module, LEN_BUILTIN};
+use crate::diagnostics::Diagnostic;
+use crate::effects::infer_effects;
+use crate::ir::{self, UNDEF};
+use crate::span::Span;
+use crate::typeck::{check_types, FnSig, Ty, TypeTable};
+use crate::verify::debug_verify;
 
 /// Convert AST capability to IR capability
 pub fn lower_capability(cap: &ast::Capability) -> ir::IrCapability {
     ir::IrCapability {
         effects: cap.effects.clone(),
         budgets: cap.budgets.clone(),
+        inferred: false,
     }
 }
 
-/// Convert AST type to IR type string
-pub fn lower_type(ty: &ast::Type) -> String {
+/// Convert a checked type to its IR type. Enums are represented by their
+/// discriminant; erroneous types lower as `unit`, as programs with type
+/// errors never reach a backend.
+pub fn lower_type(ty: &Ty) -> ir::IrType {
     match ty {
-        ast::Type::Int => "Int".to_string(),
-        ast::Type::Text => "Text".to_string(),
-        ast::Type::Bool => "Bool".to_string(),
-        ast::Type::Array(inner) => format!("Array<{}>", lower_type(inner)),
-        ast::Type::Function { returns, .. } => format!("Func<{}>", lower_type(returns)),
-        ast::Type::Custom(name) => name.clone(),
+        Ty::Unit | Ty::Error => ir::IrType::Unit,
+        Ty::Int | Ty::Enum(_) => ir::IrType::INT,
+        Ty::Text => ir::IrType::Text,
+        Ty::Bool => ir::IrType::Bool,
+        Ty::Array(element) => ir::IrType::Array(Box::new(lower_type(element))),
+        Ty::Function { params, returns, effect } => ir::IrType::Function {
+            params: params.iter().map(lower_type).collect(),
+            returns: Box::new(lower_type(returns)),
+            capability: ir::IrCapability {
+                effects: vec![effect.clone()],
+                budgets: ast::ResourceBudget::default(),
+                inferred: false,
+            },
+        },
     }
 }
 
 /// Lower AST module to IR module
 pub fn lower_module(module: &ast::Module) -> ir::IrModule {
+    lower_module_with_diagnostics(module).0
+}
+
+/// Lower AST module to IR module, reporting anything lowering had to skip.
+/// Type errors are left to the type checker; erroneous types lower as
+/// `unit`.
+pub fn lower_module_with_diagnostics(module: &ast::Module) -> (ir::IrModule, Vec<Diagnostic>) {
+    let (types, _) = check_types(module);
+    lower_module_with_types(module, &types)
+}
+
+/// Lower a type-checked AST module to IR
+pub fn lower_module_with_types(module: &ast::Module, types: &TypeTable) -> (ir::IrModule, Vec<Diagnostic>) {
     let mut functions = Vec::new();
+    let mut diagnostics = Vec::new();
+    let effects = infer_effects(module);
+    let module = desugar_module(module);
     
-    // Convert each statement
+    // Capability of every function, declared or inferred
+    let mut callees = HashMap::new();
     for stmt in &module.statements {
-        match stmt {
-            ast::Stmt::Function { name, params, returns, capability, body } => {
-                let ir_params: Vec<(String, String)> = params.iter()
-                    .map(|(n, t)| (n.clone(), lower_type(t)))
-                    .collect();
-                
-                let mut ir_body = Vec::new();
-                
-                // Add a simple return for now
-                ir_body.push(ir::IrInst::Return { value: None });
-                
-                functions.push(ir::IrFunction {
-                    name: name.clone(),
-                    params: ir_params,
-                    returns: lower_type(returns),
-                    capability: capability.as_ref().map(lower_capability),
-                    body: ir_body,
-                });
+        if let ast::StmtKind::Function { name, capability, body, .. } = &stmt.kind {
+            let ir_capability = match capability {
+                Some(cap) => Some(lower_capability(cap)),
+                None if body.is_some() => Some(ir::IrCapability {
+                    effects: vec![effects[name].clone()],
+                    budgets: ast::ResourceBudget::default(),
+                    inferred: true,
+                }),
+                None => None,
+            };
+            callees.insert(name.clone(), ir_capability);
+        }
+    }
+    
+    // Top-level lets with literal values are inlined as constants
+    let mut constants = HashMap::new();
+    for stmt in &module.statements {
+        if let ast::StmtKind::Let { name, value, .. } = &stmt.kind {
+            if let Some(value) = literal_value(value) {
+                constants.insert(name.clone(), value);
             }
-            _ => {} // Skip other statements for now
         }
     }
     
-    ir::IrModule {
+    // Convert each statement
+    for stmt in &module.statements {
+        // Parse errors were already reported; enums are resolved by desugaring
+        // and literal globals are inlined where they are used
+        match &stmt.kind {
+            ast::StmtKind::Error | ast::StmtKind::Enum { .. } => continue,
+            ast::StmtKind::Let { name, .. } if constants.contains_key(name) => continue,
+            _ => {}
+        }
+        let ast::StmtKind::Function { name, params, body, .. } = &stmt.kind else {
+            diagnostics.push(
+                Diagnostic::warning("top-level statement is not lowered")
+                    .with_code("W0001")
+                    .with_primary(stmt.span, "ignored during lowering")
+                    .with_note("only function declarations produce IR for now"),
+            );
+            continue;
+        };
+        
+        let sig = &types.functions[name];
+        let ir_params: Vec<(String, ir::IrType)> = sig.params.iter()
+            .map(|(n, t)| (n.clone(), lower_type(t)))
+            .collect();
+        
+        // Declarations without a body are external and have no blocks
+        let (blocks, value_types, value_names) = match body {
+            Some(body) => {
+                let mut lowerer = FunctionLowerer::new(types, &callees, &constants);
+                let lowered = lowerer.lower_function(params, body, sig);
+                diagnostics.extend(lowerer.diagnostics);
+                lowered
+            }
+            None => (Vec::new(), HashMap::new(), HashMap::new()),
+        };
+        
+        functions.push(ir::IrFunction {
+            name: name.clone(),
+            span: stmt.span,
+            params: ir_params,
+            returns: lower_type(&sig.returns),
+            capability: callees[name].clone(),
+            blocks,
+            value_types,
+            value_names,
+        });
+    }
+    
+    let ir_module = ir::IrModule {
         name: module.name.clone(),
         capability: module.capability.as_ref().map(lower_capability),
+        effects: module.effects.clone(),
         functions,
+    };
+    // Lowering a well-typed module must produce valid IR
+    if diagnostics.is_empty() && !types.has_errors() {
+        debug_verify(&ir_module, "lowering");
+    }
+    (ir_module, diagnostics)
+}
+
+fn literal_value(expr: &ast::Expr) -> Option<ir::IrValue> {
+    match &expr.kind {
+        ast::ExprKind::Number(n) => Some(ir::IrValue::Int(*n)),
+        ast::ExprKind::String(s) => Some(ir::IrValue::Text(s.clone())),
+        ast::ExprKind::Bool(b) => Some(ir::IrValue::Bool(*b)),
+        _ => None,
+    }
+}
+
+/// Basic block under construction
+#[derive(Debug)]
+struct BlockBuilder {
+    label: String,
+    insts: Vec<ir::IrInst>,
+    spans: Vec<Span>,
+    terminator: Option<(ir::IrTerminator, Span)>,
+    preds: Vec<usize>,
+    /// All predecessors are known
+    sealed: bool,
+}
+
+/// SSA construction for a single function body
+struct FunctionLowerer<'a> {
+    types: &'a TypeTable,
+    callees: &'a HashMap<String, Option<ir::IrCapability>>,
+    constants: &'a HashMap<String, ir::IrValue>,
+    blocks: Vec<BlockBuilder>,
+    /// Blocks in the order lowering entered them, which is the output order
+    layout: Vec<usize>,
+    current: usize,
+    /// Span of the expression being lowered, attached to what it emits
+    span: Span,
+    next_value: usize,
+    /// Type of every SSA value, which decides how operands are owned
+    value_types: HashMap<String, Ty>,
+    /// Source names in scope, innermost last, mapped to unique variables
+    scopes: Vec<HashMap<String, String>>,
+    next_var: usize,
+    var_types: HashMap<String, Ty>,
+    /// Capability of variables holding functions, from their declared type
+    var_capabilities: HashMap<String, ir::IrCapability>,
+    /// Current SSA value of each variable, per block
+    defs: HashMap<(String, usize), String>,
+    /// Source name of the variable each value was first bound to
+    value_names: HashMap<String, String>,
+    /// Phis placed in unsealed blocks, completed when the block is sealed
+    incomplete_phis: HashMap<usize, Vec<(String, String)>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> FunctionLowerer<'a> {
+    fn new(
+        types: &'a TypeTable,
+        callees: &'a HashMap<String, Option<ir::IrCapability>>,
+        constants: &'a HashMap<String, ir::IrValue>,
+    ) -> Self {
+        FunctionLowerer {
+            types,
+            callees,
+            constants,
+            blocks: Vec::new(),
+            layout: Vec::new(),
+            current: 0,
+            span: Span::default(),
+            next_value: 0,
+            value_types: HashMap::new(),
+            scopes: Vec::new(),
+            next_var: 0,
+            var_types: HashMap::new(),
+            var_capabilities: HashMap::new(),
+            defs: HashMap::new(),
+            value_names: HashMap::new(),
+            incomplete_phis: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn lower_function(
+        &mut self,
+        params: &[(String, ast::Type)],
+        body: &ast::Block,
+        sig: &FnSig,
+    ) -> (Vec<ir::IrBlock>, HashMap<String, ir::IrType>, HashMap<String, String>) {
+        self.span = body.span;
+        let entry = self.new_block("entry");
+        self.seal(entry);
+        self.switch_to(entry);
+
+        self.scopes.push(HashMap::new());
+        for ((name, ty), (_, param_ty)) in params.iter().zip(&sig.params) {
+            let var = self.declare(name);
+            if let ast::TypeKind::Function { capability: Some(cap), .. } = &ty.kind {
+                self.var_capabilities.insert(var.clone(), lower_capability(cap));
+            }
+            let value = format!("%{}", name);
+            self.value_types.insert(value.clone(), param_ty.clone());
+            self.var_types.insert(var.clone(), param_ty.clone());
+            self.write_var(&var, entry, value);
+        }
+        let value = self.lower_block(body);
+        self.scopes.pop();
+
+        // Returning hands ownership of the result to the caller
+        self.span = body.tail.as_ref().map_or(body.span, |tail| tail.span);
+        let value = match value {
+            Some(value) if sig.returns != Ty::Unit => Some(self.operand(value, ir::Ownership::Move)),
+            _ => None,
+        };
+        self.terminate(ir::IrTerminator::Return { value });
+
+        self.remove_trivial_phis();
+        let mut blocks: Vec<Option<BlockBuilder>> = self.blocks.drain(..).map(Some).collect();
+        let blocks: Vec<ir::IrBlock> = self.layout.iter()
+            .filter_map(|&index| blocks[index].take())
+            .map(|block| {
+                let (terminator, terminator_span) = block.terminator
+                    .expect("every lowered block is terminated");
+                ir::IrBlock {
+                    label: block.label,
+                    insts: block.insts,
+                    spans: block.spans,
+                    terminator,
+                    terminator_span,
+                }
+            })
+            .collect();
+
+        // Only values that survived phi removal are kept
+        let params = params.iter().map(|(name, _)| format!("%{}", name));
+        let dests = blocks.iter()
+            .flat_map(|block| &block.insts)
+            .filter_map(|inst| inst.dest().map(str::to_string));
+        let value_types: HashMap<String, ir::IrType> = params.chain(dests)
+            .map(|value| {
+                let ty = lower_type(&self.type_of(&value));
+                (value, ty)
+            })
+            .collect();
+        let value_names = std::mem::take(&mut self.value_names).into_iter()
+            .filter(|(value, _)| value_types.contains_key(value))
+            .collect();
+        (blocks, value_types, value_names)
+    }
+
+    /// New SSA value of the given type
+    fn define(&mut self, ty: Ty) -> String {
+        let value = format!("%{}", self.next_value);
+        self.next_value += 1;
+        self.value_types.insert(value.clone(), ty);
+        value
+    }
+
+    fn type_of(&self, value: &str) -> Ty {
+        self.value_types.get(value).cloned().unwrap_or(Ty::Error)
+    }
+
+    /// Use `value` in `mode` if it owns memory; other values are copied
+    fn operand(&self, value: String, mode: ir::Ownership) -> ir::IrOperand {
+        if lower_type(&self.type_of(&value)).is_owned() {
+            ir::IrOperand::new(value, mode)
+        } else {
+            ir::IrOperand::copy(value)
+        }
+    }
+
+    fn new_block(&mut self, hint: &str) -> usize {
+        let index = self.blocks.len();
+        let label = if index == 0 { hint.to_string() } else { format!("{}{}", hint, index) };
+        self.blocks.push(BlockBuilder {
+            label,
+            insts: Vec::new(),
+            spans: Vec::new(),
+            terminator: None,
+            preds: Vec::new(),
+            sealed: false,
+        });
+        index
+    }
+
+    fn switch_to(&mut self, block: usize) {
+        self.layout.push(block);
+        self.current = block;
+    }
+
+    fn emit(&mut self, inst: ir::IrInst) {
+        let block = &mut self.blocks[self.current];
+        block.insts.push(inst);
+        block.spans.push(self.span);
+    }
+
+    fn terminate(&mut self, terminator: ir::IrTerminator) {
+        let block = &mut self.blocks[self.current];
+        debug_assert!(block.terminator.is_none(), "block {} terminated twice", block.label);
+        block.terminator = Some((terminator, self.span));
+    }
+
+    fn jump(&mut self, target: usize) {
+        let label = self.blocks[target].label.clone();
+        self.terminate(ir::IrTerminator::Jump { target: label });
+        self.blocks[target].preds.push(self.current);
+    }
+
+    fn branch(&mut self, cond: String, then_block: usize, else_block: usize) {
+        self.terminate(ir::IrTerminator::Branch {
+            cond: ir::IrOperand::copy(cond),
+            then_target: self.blocks[then_block].label.clone(),
+            else_target: self.blocks[else_block].label.clone(),
+        });
+        self.blocks[then_block].preds.push(self.current);
+        self.blocks[else_block].preds.push(self.current);
+    }
+
+    fn unsupported(&mut self, span: Span, what: &str) -> Option<String> {
+        self.diagnostics.push(
+            Diagnostic::warning(format!("{} are not lowered yet", what))
+                .with_code("W0002")
+                .with_primary(span, "replaced by an undefined value"),
+        );
+        Some(UNDEF.to_string())
+    }
+
+    // -- Variables ------------------------------------------------------
+
+    /// Bind a source name in the innermost scope to a fresh variable
+    fn declare(&mut self, name: &str) -> String {
+        let var = format!("{}.{}", name, self.next_var);
+        self.next_var += 1;
+        self.scopes.last_mut().expect("a scope is open").insert(name.to_string(), var.clone());
+        var
+    }
+
+    fn resolve(&self, name: &str) -> Option<String> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).cloned())
+    }
+
+    fn write_var(&mut self, var: &str, block: usize, value: String) {
+        if ir::is_value(&value) {
+            let name = var.rsplit_once('.').map_or(var, |(name, _)| name);
+            self.value_names.entry(value.clone()).or_insert_with(|| name.to_string());
+        }
+        self.defs.insert((var.to_string(), block), value);
+    }
+
+    fn read_var(&mut self, var: &str, block: usize) -> String {
+        if let Some(value) = self.defs.get(&(var.to_string(), block)) {
+            return value.clone();
+        }
+        self.read_var_recursive(var, block)
+    }
+
+    fn read_var_recursive(&mut self, var: &str, block: usize) -> String {
+        let value = if !self.blocks[block].sealed {
+            let phi = self.new_phi(var, block);
+            self.incomplete_phis.entry(block).or_default().push((var.to_string(), phi.clone()));
+            phi
+        } else if self.blocks[block].preds.len() == 1 {
+            let pred = self.blocks[block].preds[0];
+            self.read_var(var, pred)
+        } else if self.blocks[block].preds.is_empty() {
+            UNDEF.to_string()
+        } else {
+            // Break cycles through loops with an operandless phi first
+            let phi = self.new_phi(var, block);
+            self.write_var(var, block, phi.clone());
+            self.add_phi_operands(var, &phi, block);
+            phi
+        };
+        self.write_var(var, block, value.clone());
+        value
+    }
+
+    fn new_phi(&mut self, var: &str, block: usize) -> String {
+        let dest = self.define(self.var_types.get(var).cloned().unwrap_or(Ty::Error));
+        let block = &mut self.blocks[block];
+        block.insts.insert(0, ir::IrInst::Phi { dest: dest.clone(), incoming: Vec::new() });
+        block.spans.insert(0, self.span);
+        dest
+    }
+
+    /// Phis join names without taking ownership, so their operands are copies
+    fn add_phi_operands(&mut self, var: &str, phi: &str, block: usize) {
+        for pred in self.blocks[block].preds.clone() {
+            let value = self.read_var(var, pred);
+            let label = self.blocks[pred].label.clone();
+            let inst = self.blocks[block].insts.iter_mut()
+                .find(|inst| inst.dest() == Some(phi))
+                .expect("phi is in its block");
+            if let ir::IrInst::Phi { incoming, .. } = inst {
+                incoming.push((label, ir::IrOperand::copy(value)));
+            }
+        }
+    }
+
+    /// Mark a block's predecessors as complete and finish its pending phis
+    fn seal(&mut self, block: usize) {
+        for (var, phi) in self.incomplete_phis.remove(&block).unwrap_or_default() {
+            self.add_phi_operands(&var, &phi, block);
+        }
+        self.blocks[block].sealed = true;
+    }
+
+    /// Replace phis that merge a single value (besides themselves) with
+    /// that value until none are left
+    fn remove_trivial_phis(&mut self) {
+        loop {
+            let trivial = self.blocks.iter().enumerate().find_map(|(b, block)| {
+                block.insts.iter().enumerate().find_map(|(i, inst)| {
+                    let ir::IrInst::Phi { dest, incoming } = inst else {
+                        return None;
+                    };
+                    let mut values = incoming.iter().map(|(_, v)| &v.value).filter(|v| *v != dest);
+                    let first = values.next().cloned().unwrap_or_else(|| UNDEF.to_string());
+                    values.all(|v| *v == first).then(|| (b, i, dest.clone(), first))
+                })
+            });
+            let Some((block, index, phi, value)) = trivial else {
+                break;
+            };
+            self.blocks[block].insts.remove(index);
+            self.blocks[block].spans.remove(index);
+            for block in &mut self.blocks {
+                let insts = block.insts.iter_mut().flat_map(ir::IrInst::operands_mut);
+                let terminator = block.terminator.iter_mut().flat_map(|(t, _)| t.operands_mut());
+                for operand in insts.chain(terminator) {
+                    if *operand == phi {
+                        *operand = value.clone();
+                    }
+                }
+            }
+        }
+    }
+
+    // -- Statements and expressions -------------------------------------
+
+    fn lower_block(&mut self, block: &ast::Block) -> Option<String> {
+        self.scopes.push(HashMap::new());
+        for stmt in &block.stmts {
+            self.lower_stmt(stmt);
+        }
+        let value = block.tail.as_ref().and_then(|tail| self.lower_expr(tail));
+        self.scopes.pop();
+        value
+    }
+
+    fn lower_stmt(&mut self, stmt: &ast::Stmt) {
+        match &stmt.kind {
+            ast::StmtKind::Let { name, value, .. } => {
+                let value = self.lower_expr(value);
+                let var = self.declare(name);
+                if let Some(value) = value {
+                    self.var_types.insert(var.clone(), self.type_of(&value));
+                    self.write_var(&var, self.current, value);
+                }
+            }
+            ast::StmtKind::Assign { name, value } => {
+                let value = self.lower_expr(value);
+                match (self.resolve(name), value) {
+                    (Some(var), Some(value)) => self.write_var(&var, self.current, value),
+                    (None, _) => {
+                        self.unsupported(stmt.span, "assignments to globals");
+                    }
+                    (Some(_), None) => {}
+                }
+            }
+            ast::StmtKind::Expression(expr) => {
+                self.lower_expr(expr);
+            }
+            ast::StmtKind::Function { .. } | ast::StmtKind::Enum { .. } | ast::StmtKind::Error => {}
+        }
+    }
+
+    fn constant(&mut self, value: ir::IrValue) -> String {
+        let ty = match value {
+            ir::IrValue::Int(_) => Ty::Int,
+            ir::IrValue::Text(_) => Ty::Text,
+            ir::IrValue::Bool(_) => Ty::Bool,
+        };
+        let dest = self.define(ty);
+        self.emit(ir::IrInst::Const { dest: dest.clone(), value });
+        dest
+    }
+
+    /// Lower an expression, returning the SSA value holding its result, or
+    /// `None` for expressions without a value
+    fn lower_expr(&mut self, expr: &ast::Expr) -> Option<String> {
+        let outer = std::mem::replace(&mut self.span, expr.span);
+        let value = self.lower_expr_kind(expr);
+        self.span = outer;
+        value
+    }
+
+    fn lower_expr_kind(&mut self, expr: &ast::Expr) -> Option<String> {
+        match &expr.kind {
+            ast::ExprKind::Number(n) => Some(self.constant(ir::IrValue::Int(*n))),
+            ast::ExprKind::String(s) => Some(self.constant(ir::IrValue::Text(s.clone()))),
+            ast::ExprKind::Bool(b) => Some(self.constant(ir::IrValue::Bool(*b))),
+            ast::ExprKind::Ident(name) => {
+                if let Some(var) = self.resolve(name) {
+                    return Some(self.read_var(&var, self.current));
+                }
+                if let Some(value) = self.constants.get(name) {
+                    return Some(self.constant(value.clone()));
+                }
+                if self.callees.contains_key(name) {
+                    // Functions used as values are referred to by name
+                    return Some(name.clone());
+                }
+                // Type checking leaves only globals whose value is computed
+                self.unsupported(expr.span, "non-literal globals")
+            }
+            ast::ExprKind::Binary { op: op @ (ast::BinOp::And | ast::BinOp::Or), lhs, rhs } => {
+                self.lower_short_circuit(*op, lhs, rhs)
+            }
+            ast::ExprKind::Binary { op, lhs, rhs } => {
+                let lhs = self.lower_value(lhs);
+                let rhs = self.lower_value(rhs);
+                let ty = match op {
+                    ast::BinOp::Add if self.type_of(&lhs) == Ty::Text => Ty::Text,
+                    ast::BinOp::Add | ast::BinOp::Sub | ast::BinOp::Mul | ast::BinOp::Div
+                    | ast::BinOp::Rem => Ty::Int,
+                    _ => Ty::Bool,
+                };
+                let dest = self.define(ty);
+                self.emit(ir::IrInst::Binary {
+                    dest: dest.clone(),
+                    op: *op,
+                    lhs: ir::IrOperand::copy(lhs),
+                    rhs: ir::IrOperand::copy(rhs),
+                });
+                Some(dest)
+            }
+            ast::ExprKind::Unary { op, operand } => {
+                let operand = self.lower_value(operand);
+                let ty = match op {
+                    ast::UnaryOp::Neg => Ty::Int,
+                    ast::UnaryOp::Not => Ty::Bool,
+                };
+                let dest = self.define(ty);
+                self.emit(ir::IrInst::Unary {
+                    dest: dest.clone(),
+                    op: *op,
+                    operand: ir::IrOperand::copy(operand),
+                });
+                Some(dest)
+            }
+            ast::ExprKind::Call { func, args } => self.lower_call(func, args),
+            ast::ExprKind::Block(block) => self.lower_block(block),
+            ast::ExprKind::If { cond, then_branch, else_branch } => {
+                self.lower_if(expr, cond, then_branch, else_branch.as_deref())
+            }
+            ast::ExprKind::While { cond, body } => {
+                let header = self.new_block("while");
+                let body_block = self.new_block("body");
+                let exit = self.new_block("exit");
+
+                self.jump(header);
+                self.switch_to(header);
+                let cond = self.lower_value(cond);
+                self.branch(cond, body_block, exit);
+
+                self.seal(body_block);
+                self.switch_to(body_block);
+                self.lower_block(body);
+                self.jump(header);
+
+                // The back edge is known only now
+                self.seal(header);
+                self.seal(exit);
+                self.switch_to(exit);
+                None
+            }
+            ast::ExprKind::Array(items) => {
+                let items: Vec<String> = items.iter().map(|item| self.lower_value(item)).collect();
+                Some(self.lower_array(items))
+            }
+            ast::ExprKind::Index { base, index } => {
+                let base = self.lower_value(base);
+                let index = self.lower_value(index);
+                let one = self.constant(ir::IrValue::Int(1));
+                let offset = self.define(Ty::Int);
+                self.emit(ir::IrInst::Binary {
+                    dest: offset.clone(),
+                    op: ast::BinOp::Add,
+                    lhs: ir::IrOperand::copy(index),
+                    rhs: ir::IrOperand::copy(one),
+                });
+                let element = match self.type_of(&base) {
+                    Ty::Array(element) => *element,
+                    _ => Ty::Error,
+                };
+                let dest = self.define(element);
+                self.emit(ir::IrInst::Load {
+                    dest: dest.clone(),
+                    base: self.operand(base, ir::Ownership::Shared),
+                    offset: ir::IrOperand::copy(offset),
+                });
+                Some(dest)
+            }
+            ast::ExprKind::IntentBlock { .. } => self.unsupported(expr.span, "intent blocks"),
+            // Removed by desugaring, or already reported by the checkers
+            ast::ExprKind::For { .. }
+            | ast::ExprKind::Match { .. }
+            | ast::ExprKind::Variant { .. }
+            | ast::ExprKind::Error => Some(UNDEF.to_string()),
+        }
+    }
+
+    /// Lower an expression whose value is required
+    fn lower_value(&mut self, expr: &ast::Expr) -> String {
+        self.lower_expr(expr).unwrap_or_else(|| UNDEF.to_string())
+    }
+
+    /// Arrays are a length cell followed by the elements, which move into
+    /// the array
+    fn lower_array(&mut self, items: Vec<String>) -> String {
+        let element = items.first().map_or(Ty::Error, |item| self.type_of(item));
+        let array = self.define(Ty::Array(Box::new(element)));
+        self.emit(ir::IrInst::Alloc { dest: array.clone(), size: items.len() as u32 + 1 });
+
+        let len = self.constant(ir::IrValue::Int(items.len() as i64));
+        let cells = std::iter::once(len).chain(items);
+        for (i, value) in cells.enumerate() {
+            let offset = self.constant(ir::IrValue::Int(i as i64));
+            self.emit(ir::IrInst::Store {
+                base: ir::IrOperand::new(array.clone(), ir::Ownership::Unique),
+                offset: ir::IrOperand::copy(offset),
+                value: self.operand(value, ir::Ownership::Move),
+            });
+        }
+        array
+    }
+
+    /// Arguments are lent to the callee for the duration of the call
+    fn lower_call(&mut self, func: &ast::Expr, args: &[ast::Expr]) -> Option<String> {
+        let args: Vec<String> = args.iter().map(|arg| self.lower_value(arg)).collect();
+
+        // `len` reads the length cell unless something shadows it
+        let is_len = matches!(&func.kind, ast::ExprKind::Ident(name)
+            if name == LEN_BUILTIN && self.resolve(name).is_none() && !self.callees.contains_key(name));
+        if is_len && args.len() == 1 {
+            let zero = self.constant(ir::IrValue::Int(0));
+            let dest = self.define(Ty::Int);
+            self.emit(ir::IrInst::Load {
+                dest: dest.clone(),
+                base: self.operand(args[0].clone(), ir::Ownership::Shared),
+                offset: ir::IrOperand::copy(zero),
+            });
+            return Some(dest);
+        }
+
+        let (func, capability, callee_ty) = match &func.kind {
+            ast::ExprKind::Ident(name) => match self.resolve(name) {
+                // Indirect call through a local holding a function
+                Some(var) => {
+                    let capability = self.var_capabilities.get(&var).cloned();
+                    let value = self.read_var(&var, self.current);
+                    let ty = self.type_of(&value);
+                    (value, capability, ty)
+                }
+                None => {
+                    let capability = self.callees.get(name).cloned().flatten();
+                    let ty = self.types.functions.get(name).map_or(Ty::Error, FnSig::ty);
+                    (name.clone(), capability, ty)
+                }
+            },
+            _ => {
+                let value = self.lower_value(func);
+                let ty = self.type_of(&value);
+                (value, None, ty)
+            }
+        };
+        let returns = match callee_ty {
+            Ty::Function { returns, .. } => *returns,
+            _ => Ty::Error,
+        };
+        let args = args.into_iter().map(|arg| self.operand(arg, ir::Ownership::Shared)).collect();
+        let dest = self.define(returns);
+        self.emit(ir::IrInst::Call { dest: dest.clone(), func, args, capability });
+        Some(dest)
+    }
+
+    fn lower_if(
+        &mut self,
+        expr: &ast::Expr,
+        cond: &ast::Expr,
+        then_branch: &ast::Block,
+        else_branch: Option<&ast::Expr>,
+    ) -> Option<String> {
+        let cond = self.lower_value(cond);
+        let then_block = self.new_block("then");
+        let else_block = else_branch.map(|_| self.new_block("else"));
+        let merge = self.new_block("merge");
+        self.branch(cond, then_block, else_block.unwrap_or(merge));
+
+        self.seal(then_block);
+        self.switch_to(then_block);
+        let then_value = self.lower_block(then_branch);
+        let then_end = self.current;
+        self.jump(merge);
+
+        let mut else_value = None;
+        let mut else_end = None;
+        if let (Some(else_block), Some(else_branch)) = (else_block, else_branch) {
+            self.seal(else_block);
+            self.switch_to(else_block);
+            else_value = self.lower_expr(else_branch);
+            else_end = Some(self.current);
+            self.jump(merge);
+        }
+
+        self.seal(merge);
+        self.switch_to(merge);
+        let is_unit = self.types.exprs.get(&expr.span) == Some(&Ty::Unit);
+        match (then_value, else_value, else_end) {
+            (Some(then_value), Some(else_value), Some(else_end)) if !is_unit => {
+                let dest = self.define(self.type_of(&then_value));
+                let incoming = vec![
+                    (self.blocks[then_end].label.clone(), ir::IrOperand::copy(then_value)),
+                    (self.blocks[else_end].label.clone(), ir::IrOperand::copy(else_value)),
+                ];
+                self.emit(ir::IrInst::Phi { dest: dest.clone(), incoming });
+                Some(dest)
+            }
+            _ => None,
+        }
+    }
+
+    /// `a && b` and `a || b` only evaluate `b` when needed
+    fn lower_short_circuit(&mut self, op: ast::BinOp, lhs: &ast::Expr, rhs: &ast::Expr) -> Option<String> {
+        let lhs = self.lower_value(lhs);
+        let lhs_end = self.current;
+        let rhs_block = self.new_block("rhs");
+        let merge = self.new_block("merge");
+        if op == ast::BinOp::And {
+            self.branch(lhs.clone(), rhs_block, merge);
+        } else {
+            self.branch(lhs.clone(), merge, rhs_block);
+        }
+
+        self.seal(rhs_block);
+        self.switch_to(rhs_block);
+        let rhs = self.lower_value(rhs);
+        let rhs_end = self.current;
+        self.jump(merge);
+
+        self.seal(merge);
+        self.switch_to(merge);
+        let dest = self.define(Ty::Bool);
+        let incoming = vec![
+            (self.blocks[lhs_end].label.clone(), ir::IrOperand::copy(lhs)),
+            (self.blocks[rhs_end].label.clone(), ir::IrOperand::copy(rhs)),
+        ];
+        self.emit(ir::IrInst::Phi { dest: dest.clone(), incoming });
+        Some(dest)
     }
 }
 
@@ -74,6 +826,7 @@ mod tests {
             name: "test".to_string(),
             capability: None,
             imports: vec![],
+            effects: vec![],
             statements: vec![func],
         };
         
@@ -83,9 +836,229 @@ mod tests {
         let ir_func = &ir_module.functions[0];
         assert_eq!(ir_func.name, "add");
         assert_eq!(ir_func.params.len(), 2);
-        assert_eq!(ir_func.returns, "Int");
+        assert_eq!(ir_func.params[0], ("x".to_string(), ir::IrType::INT));
+        assert_eq!(ir_func.returns, ir::IrType::INT);
         
         let cap = ir_func.capability.as_ref().unwrap();
-        assert_eq!(cap.effects, vec![ast::Effect::Pure]);
+        assert_eq!(cap.effects, vec![ast::Effect::pure()]);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_lower_inferred_capability() {
+        let input = "module m
+fn write(x: Int) !{io}
+fn log_twice(x: Int) { write(x); write(x) }";
+        let (module, _) = Parser::new(input).parse_program();
+        let ir_module = lower_module(&module);
+        
+        let cap = ir_module.functions[1].capability.as_ref().unwrap();
+        assert_eq!(cap.effects, vec![ast::Effect::named("io")]);
+        assert!(cap.inferred);
+        assert!(ir_module.debug_print().contains("fn log_twice(%x: i64) -> unit !{io} inferred {"));
+        // Bodiless declarations have nothing to infer from
+        assert!(!ir_module.functions[0].capability.as_ref().unwrap().inferred);
+    }
+    
+    fn lower_function(src: &str, name: &str) -> ir::IrFunction {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let ir_module = lower_module(&module);
+        ir_module.functions.into_iter().find(|f| f.name == name).unwrap()
+    }
+    
+    fn insts(func: &ir::IrFunction) -> impl Iterator<Item = &ir::IrInst> {
+        func.blocks.iter().flat_map(|block| &block.insts)
+    }
+    
+    #[test]
+    fn test_lower_straight_line_code() {
+        let func = lower_function("module m
+fn f(x: Int, y: Int) -> Int {
+    let z = x + y;
+    z = z * 2;
+    -z
+}", "f");
+        assert_eq!(func.blocks.len(), 1);
+        let text: Vec<String> = insts(&func).map(|inst| format!("{:?}", inst)).collect();
+        assert_eq!(text, vec![
+            r#"Binary { dest: "%0", op: Add, lhs: "%x", rhs: "%y" }"#,
+            r#"Const { dest: "%1", value: Int(2) }"#,
+            r#"Binary { dest: "%2", op: Mul, lhs: "%0", rhs: "%1" }"#,
+            r#"Unary { dest: "%3", op: Neg, operand: "%2" }"#,
+        ]);
+        assert!(matches!(&func.blocks[0].terminator,
+            ir::IrTerminator::Return { value: Some(v) } if v.value == "%3"));
+    }
+    
+    #[test]
+    fn test_lower_call_capabilities() {
+        let func = lower_function("module m
+fn fetch() -> Text !{net}
+fn helper() -> Text { fetch() }
+fn g(f: fn() -> Int !{io}) -> Text !{net, io} { f(); helper() }", "g");
+        let calls: Vec<(&str, Vec<ast::Effect>)> = insts(&func)
+            .filter_map(|inst| match inst {
+                ir::IrInst::Call { func, capability, .. } => {
+                    Some((func.as_str(), capability.as_ref().unwrap().effects.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(calls, vec![("%f", vec![ast::Effect::named("io")]), ("helper", vec![ast::Effect::named("net")])]);
+    }
+    
+    #[test]
+    fn test_lower_if_merges_with_phi() {
+        let func = lower_function("module m
+fn abs(x: Int) -> Int { if x < 0 { -x } else { x } }", "abs");
+        let labels: Vec<&str> = func.blocks.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, vec!["entry", "then1", "else2", "merge3"]);
+        
+        let merge = func.block("merge3").unwrap();
+        let ir::IrInst::Phi { dest, incoming } = &merge.insts[0] else {
+            panic!("Expected phi");
+        };
+        assert_eq!(incoming[0].0, "then1");
+        assert_eq!(incoming[1], ("else2".to_string(), ir::IrOperand::copy("%x")));
+        assert!(matches!(&merge.terminator, ir::IrTerminator::Return { value: Some(v) } if v.value == *dest));
+        assert_eq!(func.predecessors()["merge3"], vec!["then1", "else2"]);
+    }
+    
+    #[test]
+    fn test_lower_loop_variables() {
+        let func = lower_function("module m
+fn count(n: Int) -> Int {
+    let i = 0;
+    let unused = 5;
+    while i < n { i = i + 1; }
+    i
+}", "count");
+        // Only `i` changes in the loop; `unused` and `n` need no phi
+        let phis: Vec<&Vec<(String, ir::IrOperand)>> = insts(&func)
+            .filter_map(|inst| match inst {
+                ir::IrInst::Phi { incoming, .. } => Some(incoming),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(phis.len(), 1);
+        let labels: Vec<&str> = phis[0].iter().map(|(label, _)| label.as_str()).collect();
+        assert_eq!(labels, vec!["entry", "body2"]);
+        assert_eq!(func.predecessors()["while1"], vec!["entry", "body2"]);
+    }
+    
+    #[test]
+    fn test_lower_arrays() {
+        let func = lower_function("module m
+fn total() -> Int {
+    let xs = [10, 20];
+    let sum = 0;
+    for x in xs { sum = sum + x; }
+    sum + xs[1]
+}", "total");
+        let allocs = insts(&func).filter(|i| matches!(i, ir::IrInst::Alloc { size: 3, .. })).count();
+        let stores = insts(&func).filter(|i| matches!(i, ir::IrInst::Store { .. })).count();
+        let loads = insts(&func).filter(|i| matches!(i, ir::IrInst::Load { .. })).count();
+        assert_eq!(allocs, 1);
+        // Length cell plus two elements
+        assert_eq!(stores, 3);
+        // `len(xs)` in the loop header, `xs[i]` in the body and `xs[1]`
+        assert_eq!(loads, 3);
+        assert!(!insts(&func).any(|i| matches!(i, ir::IrInst::Call { .. })));
+    }
+    
+    #[test]
+    fn test_lower_value_types() {
+        let func = lower_function("module m
+fn apply(f: fn(Int) -> Int !{io}, xs: Array<Int>) -> Bool !{io} {
+    let label = \"n\" + \"!\";
+    f(xs[0]) > len(xs)
+}", "apply");
+        let int_array = ir::IrType::Array(Box::new(ir::IrType::INT));
+        assert_eq!(func.params[1].1, int_array);
+        assert_eq!(func.value_type("%xs"), Some(&int_array));
+        assert_eq!(func.value_type("%f").unwrap().to_string(), "fn(i64) -> i64 !{io}");
+        assert_eq!(func.returns, ir::IrType::Bool);
+        
+        // Every instruction result is typed
+        for inst in insts(&func) {
+            let dest = inst.dest().unwrap();
+            let expected = match inst {
+                ir::IrInst::Const { value: ir::IrValue::Text(_), .. } => ir::IrType::Text,
+                ir::IrInst::Binary { op: ast::BinOp::Add, lhs, .. } if lhs.value == "%0" => ir::IrType::Text,
+                ir::IrInst::Binary { op: ast::BinOp::Gt, .. } => ir::IrType::Bool,
+                _ => ir::IrType::INT,
+            };
+            assert_eq!(func.value_type(dest), Some(&expected), "{:?}", inst);
+        }
+    }
+    
+    #[test]
+    fn test_lower_ownership_modes() {
+        let func = lower_function("module m
+fn sum(xs: Array<Int>) -> Int { xs[0] }
+fn wrap(n: Int) -> Array<Array<Int>> {
+    let inner = [n];
+    sum(inner);
+    [inner]
+}", "wrap");
+        let text: Vec<String> = insts(&func)
+            .filter(|i| matches!(i, ir::IrInst::Store { .. } | ir::IrInst::Call { .. }))
+            .map(|inst| format!("{:?}", inst))
+            .collect();
+        // Ints are copied, arrays are borrowed by calls and moved into arrays
+        assert!(text[1].contains(r#"base: "&unique %0", offset: "%3", value: "%n""#), "{:?}", text);
+        assert!(text[2].contains(r#"args: ["&shared %0"]"#), "{:?}", text);
+        assert!(text[4].contains(r#"value: "move %0""#), "{:?}", text);
+        assert!(matches!(&func.blocks[0].terminator,
+            ir::IrTerminator::Return { value: Some(v) } if v.mode == ir::Ownership::Move));
+    }
+    
+    #[test]
+    fn test_lower_reports_skipped_statements() {
+        use crate::span::Span;
+        
+        let literal = ast::Expr::new(ast::ExprKind::Number(1), Span::new(8, 9, 1, 9));
+        let computed = ast::Expr::new(ast::ExprKind::Binary {
+            op: ast::BinOp::Add,
+            lhs: Box::new(ast::Expr::new(ast::ExprKind::Ident("x".to_string()), Span::new(18, 19, 2, 9))),
+            rhs: Box::new(ast::Expr::new(ast::ExprKind::Number(1), Span::new(22, 23, 2, 13))),
+        }, Span::new(18, 23, 2, 9));
+        let module = ast::Module {
+            name: "test".to_string(),
+            capability: None,
+            imports: vec![],
+            effects: vec![],
+            statements: vec![
+                ast::Stmt::new(
+                    ast::StmtKind::Let { name: "x".to_string(), ty: None, value: literal },
+                    Span::new(0, 9, 1, 1),
+                ),
+                ast::Stmt::new(
+                    ast::StmtKind::Let { name: "y".to_string(), ty: None, value: computed },
+                    Span::new(10, 23, 2, 1),
+                ),
+            ],
+        };
+        
+        // The literal `x` is inlined; only the computed `y` is skipped
+        let (ir_module, diagnostics) = lower_module_with_diagnostics(&module);
+        assert!(ir_module.functions.is_empty());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code.as_deref(), Some("W0001"));
+        assert_eq!(diagnostics[0].primary_span(), Some(Span::new(10, 23, 2, 1)));
+    }
+    
+    #[test]
+    fn test_lower_reports_computed_global_uses() {
+        let (module, errors) = crate::Parser::new("module m
+let k = 3;
+let j = k + 1;
+fn f() -> Int { j + k }").parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (_, diagnostics) = lower_module_with_diagnostics(&module);
+        let codes: Vec<_> = diagnostics.iter().filter_map(|d| d.code.as_deref()).collect();
+        assert_eq!(codes, vec!["W0001", "W0002"]);
+        assert_eq!(diagnostics[1].message, "non-literal globals are not lowered yet");
+        assert_eq!(diagnostics[1].primary_span().unwrap().line, 4);
+    }
+}
@@ -0,0 +1,486 @@
+//! Ownership checker - enforces the borrow tags on IR operands
+//!
+//! Runs on lowered IR, where every operand says how it is held (see
+//! `ir::Ownership`). Three rules are checked per function:
+//!
+//! * a value may not be used on any path after it was moved (E0301);
+//! * a value may not be used `&unique` while a `&shared` borrow of it is
+//!   live, or twice in one instruction when one use is `&unique` (E0302);
+//! * memory may not be written through a `&shared` borrow (E0303).
+//!
+//! Phis only rename values, so their operands neither use nor move them;
+//! a phi's result is moved when the value it takes on the incoming edge
+//! was. Any other definition starts a value afresh, which matters in loops,
+//! where the body defines the same values again on every iteration.
+//!
+//! Lowering never emits `Borrow`: Forge source has no reference-taking
+//! syntax, and the borrows it makes are tags on call and store operands
+//! that end with the instruction. The live-borrow half of E0302 therefore
+//! only guards hand-written IR (see `ir_text`) and IR produced by passes
+//! that introduce `Borrow`, not programs compiled from `.fg` files.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostics::Diagnostic;
+use crate::ir::{is_value, IrBlock, IrFunction, IrInst, IrModule, IrOperand, Ownership};
+use crate::span::Span;
+
+/// Check every function with a body in `module`
+pub fn check_ownership(module: &IrModule) -> Vec<Diagnostic> {
+    module.functions.iter().flat_map(check_function).collect()
+}
+
+/// Check a single function
+pub fn check_function(func: &IrFunction) -> Vec<Diagnostic> {
+    let mut checker = OwnershipChecker::new(func);
+    checker.check_moves();
+    checker.check_borrows();
+    checker.diagnostics
+}
+
+/// Shared or unique reference made by a `Borrow` instruction
+#[derive(Debug)]
+struct BorrowSite<'a> {
+    source: &'a str,
+    mode: Ownership,
+    span: Span,
+}
+
+/// Values moved on some path, with where they were moved
+type MovedSet = HashMap<String, Span>;
+
+struct OwnershipChecker<'a> {
+    func: &'a IrFunction,
+    /// Predecessors and successors of each block, by index
+    preds: Vec<Vec<usize>>,
+    succs: Vec<Vec<usize>>,
+    borrows: HashMap<&'a str, BorrowSite<'a>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Operands of the instruction at `index`, or of the terminator when
+/// `index` is past the instructions; phi operands are not uses
+fn uses(block: &IrBlock, index: usize) -> Vec<IrOperand> {
+    match block.insts.get(index) {
+        Some(IrInst::Phi { .. }) => Vec::new(),
+        Some(inst) => inst.operands(),
+        None => block.terminator.operands(),
+    }
+    .into_iter()
+    .filter(|operand| is_value(&operand.value))
+    .collect()
+}
+
+/// A value defined at `index` holds something new, whatever happened to
+/// it on an earlier iteration; phis are handled on the way into the block
+fn kill_definition(moved: &mut MovedSet, block: &IrBlock, index: usize) {
+    match block.insts.get(index) {
+        Some(IrInst::Phi { .. }) | None => {}
+        Some(inst) => {
+            if let Some(dest) = inst.dest() {
+                moved.remove(dest);
+            }
+        }
+    }
+}
+
+fn span_at(block: &IrBlock, index: usize) -> Span {
+    block.spans.get(index).copied().unwrap_or(block.terminator_span)
+}
+
+/// Record the moves made by `operands` at `span`
+fn record_moves(moved: &mut MovedSet, operands: &[IrOperand], span: Span) {
+    for operand in operands.iter().filter(|o| o.mode == Ownership::Move) {
+        moved.entry(operand.value.clone()).or_insert(span);
+    }
+}
+
+impl<'a> OwnershipChecker<'a> {
+    fn new(func: &'a IrFunction) -> Self {
+        let index: HashMap<&str, usize> = func.blocks.iter()
+            .enumerate()
+            .map(|(i, block)| (block.label.as_str(), i))
+            .collect();
+        let mut preds = vec![Vec::new(); func.blocks.len()];
+        let mut succs = vec![Vec::new(); func.blocks.len()];
+        for (i, block) in func.blocks.iter().enumerate() {
+            for succ in block.terminator.successors() {
+                if let Some(&s) = index.get(succ) {
+                    succs[i].push(s);
+                    preds[s].push(i);
+                }
+            }
+        }
+
+        let mut borrows = HashMap::new();
+        for block in &func.blocks {
+            for (inst, span) in block.insts.iter().zip(&block.spans) {
+                if let IrInst::Borrow { dest, source } = inst {
+                    borrows.insert(dest.as_str(), BorrowSite {
+                        source: &source.value,
+                        mode: source.mode,
+                        span: *span,
+                    });
+                }
+            }
+        }
+
+        OwnershipChecker { func, preds, succs, borrows, diagnostics: Vec::new() }
+    }
+
+    // -- Moves ----------------------------------------------------------
+
+    /// Forward dataflow of the values that may have been moved, then one
+    /// pass reporting uses of them
+    fn check_moves(&mut self) {
+        let blocks = &self.func.blocks;
+        let mut moved_out: Vec<MovedSet> = vec![HashMap::new(); blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (b, block) in blocks.iter().enumerate() {
+                let mut moved = self.moved_in(b, &moved_out);
+                for index in 0..=block.insts.len() {
+                    record_moves(&mut moved, &uses(block, index), span_at(block, index));
+                    kill_definition(&mut moved, block, index);
+                }
+                // Sets only grow, so comparing sizes detects a change
+                if moved.len() != moved_out[b].len() {
+                    moved_out[b] = moved;
+                    changed = true;
+                }
+            }
+        }
+
+        for (b, block) in blocks.iter().enumerate() {
+            let mut moved = self.moved_in(b, &moved_out);
+            for index in 0..=block.insts.len() {
+                let operands = uses(block, index);
+                for operand in &operands {
+                    if let Some(&moved_at) = moved.get(&operand.value) {
+                        self.diagnostics.push(
+                            Diagnostic::error(format!("use of moved value `{}`", self.func.value_name(&operand.value)))
+                                .with_code("E0301")
+                                .with_primary(span_at(block, index), "value used here after move")
+                                .with_secondary(moved_at, "value moved here"),
+                        );
+                    }
+                }
+                record_moves(&mut moved, &operands, span_at(block, index));
+                kill_definition(&mut moved, block, index);
+            }
+        }
+    }
+
+    /// Values moved on some path into `block`, its phis included
+    fn moved_in(&self, block: usize, moved_out: &[MovedSet]) -> MovedSet {
+        let mut moved = HashMap::new();
+        for &pred in &self.preds[block] {
+            let mut from_pred = moved_out[pred].clone();
+            let label = &self.func.blocks[pred].label;
+            for inst in &self.func.blocks[block].insts {
+                let IrInst::Phi { dest, incoming } = inst else {
+                    continue;
+                };
+                from_pred.remove(dest);
+                let taken = incoming.iter().find(|(from, _)| from == label);
+                if let Some(&span) = taken.and_then(|(_, operand)| moved_out[pred].get(&operand.value)) {
+                    from_pred.insert(dest.clone(), span);
+                }
+            }
+            for (value, span) in from_pred {
+                moved.entry(value).or_insert(span);
+            }
+        }
+        moved
+    }
+
+    // -- Borrows --------------------------------------------------------
+
+    /// A `&unique` use of a value conflicts with any shared borrow of it
+    /// that is still live, i.e. used at or after that point
+    fn check_borrows(&mut self) {
+        if !self.borrows.is_empty() {
+            let live_out = self.live_out();
+            for (b, block) in self.func.blocks.iter().enumerate() {
+                // Walk backwards so `live` holds what is live after each index
+                let mut live = live_out[b].clone();
+                for index in (0..=block.insts.len()).rev() {
+                    let operands = uses(block, index);
+                    for operand in operands.iter().filter(|o| o.mode == Ownership::Unique) {
+                        let conflict = self.borrows.iter().find(|(reference, borrow)| {
+                            borrow.source == operand.value
+                                && borrow.mode == Ownership::Shared
+                                && (live.contains(**reference) || operands.iter().any(|o| o.value == **reference))
+                        });
+                        if let Some((_, borrow)) = conflict {
+                            self.diagnostics.push(
+                                Diagnostic::error(format!(
+                                    "cannot borrow `{}` as unique while it is borrowed as shared",
+                                    operand.value
+                                ))
+                                .with_code("E0302")
+                                .with_primary(span_at(block, index), "unique borrow here")
+                                .with_secondary(borrow.span, "shared borrow created here"),
+                            );
+                        }
+                    }
+                    step_live(&mut live, block, index, operands);
+                }
+            }
+        }
+        self.check_instructions();
+    }
+
+    /// Values live at the end of each block; phi operands count as live at
+    /// the end of the predecessor they flow in from
+    fn live_out(&self) -> Vec<HashSet<String>> {
+        let blocks = &self.func.blocks;
+        let mut live_in: Vec<HashSet<String>> = vec![HashSet::new(); blocks.len()];
+        let mut live_out: Vec<HashSet<String>> = vec![HashSet::new(); blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (b, block) in blocks.iter().enumerate().rev() {
+                let mut out = HashSet::new();
+                for &s in &self.succs[b] {
+                    let mut phi_dests = HashSet::new();
+                    for inst in &blocks[s].insts {
+                        if let IrInst::Phi { dest, incoming } = inst {
+                            phi_dests.insert(dest);
+                            out.extend(incoming.iter()
+                                .filter(|(label, _)| *label == block.label)
+                                .map(|(_, operand)| operand.value.clone()));
+                        }
+                    }
+                    out.extend(live_in[s].iter().filter(|v| !phi_dests.contains(v)).cloned());
+                }
+
+                let mut live = out.clone();
+                for index in (0..=block.insts.len()).rev() {
+                    step_live(&mut live, block, index, uses(block, index));
+                }
+                if out != live_out[b] || live != live_in[b] {
+                    live_out[b] = out;
+                    live_in[b] = live;
+                    changed = true;
+                }
+            }
+        }
+        live_out
+    }
+
+    /// Conflicts within a single instruction and stores through shared
+    /// references
+    fn check_instructions(&mut self) {
+        for block in &self.func.blocks {
+            for (index, inst) in block.insts.iter().enumerate() {
+                let span = span_at(block, index);
+                let operands = uses(block, index);
+                for (i, unique) in operands.iter().enumerate() {
+                    if unique.mode != Ownership::Unique {
+                        continue;
+                    }
+                    let aliased = operands.iter().enumerate().any(|(j, other)| {
+                        i != j && other.value == unique.value
+                            && matches!(other.mode, Ownership::Shared | Ownership::Unique)
+                    });
+                    if aliased {
+                        self.diagnostics.push(
+                            Diagnostic::error(format!(
+                                "`{}` is borrowed as unique and again in the same instruction",
+                                unique.value
+                            ))
+                            .with_code("E0302")
+                            .with_primary(span, "conflicting borrows here"),
+                        );
+                        break;
+                    }
+                }
+
+                if let IrInst::Store { base, .. } = inst {
+                    let through_shared = base.mode == Ownership::Shared
+                        || self.borrows.get(base.value.as_str())
+                            .is_some_and(|borrow| borrow.mode == Ownership::Shared);
+                    if through_shared {
+                        let mut diagnostic = Diagnostic::error(format!(
+                            "cannot store through `{}`, which is a shared borrow",
+                            base.value
+                        ))
+                        .with_code("E0303")
+                        .with_primary(span, "store through a shared borrow");
+                        if let Some(borrow) = self.borrows.get(base.value.as_str()) {
+                            diagnostic = diagnostic.with_secondary(borrow.span, "borrowed as shared here");
+                        }
+                        self.diagnostics.push(diagnostic.with_help("borrow the value as `&unique` to write to it"));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Move `live` from after the instruction at `index` to before it
+fn step_live(live: &mut HashSet<String>, block: &IrBlock, index: usize, operands: Vec<IrOperand>) {
+    if let Some(dest) = block.insts.get(index).and_then(IrInst::dest) {
+        live.remove(dest);
+    }
+    live.extend(operands.into_iter().map(|operand| operand.value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::{IrTerminator, IrType, IrValue};
+    use crate::{lower_module, Parser};
+
+    fn check(src: &str) -> Vec<Diagnostic> {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        check_ownership(&lower_module(&module))
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().filter_map(|d| d.code.as_deref()).collect()
+    }
+
+    /// Single-block function; instruction `i` gets a span on line `i + 1`
+    fn function(insts: Vec<IrInst>) -> IrFunction {
+        let spans = (0..insts.len()).map(|i| Span::new(i, i + 1, i as u32 + 1, 1)).collect();
+        IrFunction {
+            name: "f".to_string(),
+            span: Span::default(),
+            params: vec![("a".to_string(), IrType::Array(Box::new(IrType::INT)))],
+            returns: IrType::Unit,
+            capability: None,
+            blocks: vec![IrBlock {
+                label: "entry".to_string(),
+                insts,
+                spans,
+                terminator: IrTerminator::Return { value: None },
+                terminator_span: Span::default(),
+            }],
+            value_types: HashMap::new(),
+            value_names: HashMap::new(),
+        }
+    }
+
+    fn shared(value: &str) -> IrOperand {
+        IrOperand::new(value, Ownership::Shared)
+    }
+
+    fn unique(value: &str) -> IrOperand {
+        IrOperand::new(value, Ownership::Unique)
+    }
+
+    fn store(base: IrOperand) -> IrInst {
+        IrInst::Store { base, offset: IrOperand::copy("%0"), value: IrOperand::copy("%0") }
+    }
+
+    #[test]
+    fn test_lowered_arrays_are_accepted() {
+        let diagnostics = check("module m
+fn sum(xs: Array<Int>) -> Int { xs[0] + len(xs) }
+fn build(n: Int) -> Array<Array<Int>> {
+    let xs = [n, n];
+    let total = 0;
+    for x in xs { total = total + x; }
+    [xs, [sum(xs)]]
+}");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_use_after_move() {
+        let diagnostics = check("module m
+fn f() -> Int {
+    let a = [1];
+    let b = [a];
+    a[0]
+}");
+        assert_eq!(codes(&diagnostics), vec!["E0301"]);
+        assert_eq!(diagnostics[0].message, "use of moved value `a`");
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 5);
+        assert_eq!(diagnostics[0].labels[1].span.line, 4);
+    }
+
+    #[test]
+    fn test_move_in_loop_reaches_next_iteration() {
+        let diagnostics = check("module m
+fn f(n: Int) {
+    let a = [1];
+    let i = 0;
+    while i < n {
+        let b = [a];
+        i = i + 1;
+    }
+}");
+        assert_eq!(codes(&diagnostics), vec!["E0301"]);
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 6);
+    }
+
+    #[test]
+    fn test_reassignment_in_loop_replaces_moved_value() {
+        let diagnostics = check("module m
+fn f(n: Int) -> Int {
+    let a = [1];
+    let i = 0;
+    while i < n {
+        let b = [a];
+        a = [2];
+        i = i + 1;
+    }
+    a[0]
+}");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_unique_use_while_shared_borrow_live() {
+        let func = function(vec![
+            IrInst::Const { dest: "%0".to_string(), value: IrValue::Int(0) },
+            IrInst::Borrow { dest: "%r".to_string(), source: shared("%a") },
+            store(unique("%a")),
+            IrInst::Load { dest: "%1".to_string(), base: shared("%r"), offset: IrOperand::copy("%0") },
+        ]);
+        let diagnostics = check_function(&func);
+        assert_eq!(codes(&diagnostics), vec!["E0302"]);
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 3);
+        assert_eq!(diagnostics[0].labels[1].span.line, 2);
+    }
+
+    #[test]
+    fn test_unique_use_after_shared_borrow_ends() {
+        let func = function(vec![
+            IrInst::Const { dest: "%0".to_string(), value: IrValue::Int(0) },
+            IrInst::Borrow { dest: "%r".to_string(), source: shared("%a") },
+            IrInst::Load { dest: "%1".to_string(), base: shared("%r"), offset: IrOperand::copy("%0") },
+            store(unique("%a")),
+        ]);
+        assert!(check_function(&func).is_empty());
+    }
+
+    #[test]
+    fn test_unique_and_shared_in_one_instruction() {
+        let func = function(vec![
+            IrInst::Const { dest: "%0".to_string(), value: IrValue::Int(0) },
+            IrInst::Store { base: unique("%a"), offset: IrOperand::copy("%0"), value: shared("%a") },
+        ]);
+        assert_eq!(codes(&check_function(&func)), vec!["E0302"]);
+    }
+
+    #[test]
+    fn test_store_through_shared_borrow() {
+        let func = function(vec![
+            IrInst::Const { dest: "%0".to_string(), value: IrValue::Int(0) },
+            IrInst::Borrow { dest: "%r".to_string(), source: shared("%a") },
+            store(unique("%r")),
+            store(shared("%a")),
+        ]);
+        let diagnostics = check_function(&func);
+        assert_eq!(codes(&diagnostics), vec!["E0303", "E0303"]);
+        assert_eq!(diagnostics[0].message, "cannot store through `%r`, which is a shared borrow");
+        assert_eq!(diagnostics[0].labels[1].span.line, 2);
+        assert_eq!(diagnostics[1].primary_span().unwrap().line, 4);
+    }
+}
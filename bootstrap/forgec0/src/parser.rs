@@ -1,35 +1,255 @@
-/// Parser module for Forge Lang - Phase α
-/// 
-/// Recursive descent parser that builds AST from token stream
+//! Parser module for Forge Lang - Phase α
+//!
+//! Recursive descent parser that builds AST from token stream
+
+use std::collections::HashSet;
+use std::fmt;
 
 use crate::ast::*;
+use crate::diagnostics::Diagnostic;
 use crate::lexer::{Token, Lexer};
+use crate::span::Span;
 
 #[derive(Debug)]
 pub struct Parser {
     lexer: Lexer,
     current_token: Token,
+    current_span: Span,
+    // Span of the most recently consumed token
+    prev_span: Span,
+    // Whether to record errors and keep going instead of returning early
+    recovering: bool,
+    errors: Vec<ParseError>,
+    // Effects a capability may name: the built-in ones and those declared
+    // so far
+    effects: HashSet<String>,
+    // Effects named by the module header, which comes before any
+    // declaration; checked once the whole file is parsed
+    header_effects: Option<Vec<(String, Span)>>,
+    // Effect variables of the functions being parsed
+    effect_vars: Vec<String>,
+    // Every effect variable parsed, to check against the effects the whole
+    // file declares
+    effect_var_spans: Vec<(String, Span)>,
 }
 
 #[derive(Debug, Clone)]
 pub enum ParseError {
-    UnexpectedToken { expected: String, found: Token },
-    UnexpectedEof,
-    InvalidEffect(String),
-    InvalidResourceBudget,
+    UnexpectedToken { expected: String, found: Token, span: Span },
+    UnexpectedEof { span: Span },
+    InvalidEffect { name: String, span: Span },
+    /// Effect named by a capability above its `effect` declaration
+    EffectUsedBeforeDeclaration { name: String, span: Span, declaration: Span },
+    /// Effect variable repeated, or named like an effect
+    InvalidEffectVariable { name: String, reason: String, span: Span },
+    InvalidResourceBudget { reason: String, span: Span },
+    UnknownUnit { unit: String, resource: Resource, span: Span },
+    /// Numeric literal that is not an `Int`, being fractional or too large
+    InvalidNumber { literal: String, span: Span },
+}
+
+impl ParseError {
+    /// Source location the error points at
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEof { span }
+            | ParseError::InvalidEffect { span, .. }
+            | ParseError::EffectUsedBeforeDeclaration { span, .. }
+            | ParseError::InvalidEffectVariable { span, .. }
+            | ParseError::InvalidResourceBudget { span, .. }
+            | ParseError::UnknownUnit { span, .. }
+            | ParseError::InvalidNumber { span, .. } => *span,
+        }
+    }
+    
+    /// Stable error code used in rendered diagnostics
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken { .. } => "E0001",
+            ParseError::UnexpectedEof { .. } => "E0002",
+            ParseError::InvalidEffect { .. } => "E0003",
+            ParseError::EffectUsedBeforeDeclaration { .. } => "E0009",
+            ParseError::InvalidResourceBudget { .. } => "E0004",
+            ParseError::UnknownUnit { .. } => "E0006",
+            ParseError::InvalidNumber { .. } => "E0007",
+            ParseError::InvalidEffectVariable { .. } => "E0008",
+        }
+    }
+    
+    /// Convert into a diagnostic pointing at the offending token
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let diag = Diagnostic::error(self.to_string()).with_code(self.code());
+        match self {
+            ParseError::UnexpectedToken { expected, .. } => {
+                diag.with_primary(self.span(), format!("expected {}", expected))
+            }
+            ParseError::UnexpectedEof { .. } => diag.with_primary(self.span(), ""),
+            ParseError::InvalidEffect { .. } => diag
+                .with_primary(self.span(), "not a known effect or resource")
+                .with_help("effects are `pure`, `alloc`, `io`, `net` and those declared with \
+                            `effect name;`; resources are `tokens`, `latency` and `energy`"),
+            ParseError::EffectUsedBeforeDeclaration { declaration, .. } => diag
+                .with_primary(self.span(), "used here")
+                .with_secondary(*declaration, "declared here")
+                .with_help("move the declaration above the effect's first use"),
+            ParseError::InvalidResourceBudget { reason, .. } => diag.with_primary(self.span(), reason.as_str()),
+            ParseError::InvalidEffectVariable { reason, .. } => diag.with_primary(self.span(), reason.as_str()),
+            ParseError::UnknownUnit { resource, .. } => {
+                let units: Vec<String> = resource.units().iter().map(|(unit, _)| format!("`{}`", unit)).collect();
+                diag.with_primary(self.span(), format!("not a unit of {}", resource))
+                    .with_help(format!("{} can be written with {}", resource, units.join(", ")))
+            }
+            ParseError::InvalidNumber { .. } => {
+                diag.with_primary(self.span(), "integers are whole numbers that fit in 64 bits")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedToken { expected, found, .. } => {
+                write!(f, "expected {}, found {}", expected, describe(found))
+            }
+            ParseError::UnexpectedEof { .. } => write!(f, "unexpected end of file"),
+            ParseError::InvalidEffect { name, .. } => write!(f, "unknown effect `{}`", name),
+            ParseError::EffectUsedBeforeDeclaration { name, .. } => {
+                write!(f, "effect `{}` is used before it is declared", name)
+            }
+            ParseError::InvalidResourceBudget { .. } => write!(f, "invalid resource budget"),
+            ParseError::UnknownUnit { unit, resource, .. } => write!(f, "unknown {} unit `{}`", resource, unit),
+            ParseError::InvalidNumber { literal, .. } => write!(f, "`{}` is not a valid integer", literal),
+            ParseError::InvalidEffectVariable { name, .. } => write!(f, "invalid effect variable `{}`", name),
+        }
+    }
+}
+
+/// Binary operator a token stands for, if any
+fn binary_op(token: &Token) -> Option<BinOp> {
+    let op = match token {
+        Token::Plus => BinOp::Add,
+        Token::Minus => BinOp::Sub,
+        Token::Star => BinOp::Mul,
+        Token::Slash => BinOp::Div,
+        Token::Percent => BinOp::Rem,
+        Token::EqualEqual => BinOp::Eq,
+        Token::NotEqual => BinOp::Ne,
+        Token::Less => BinOp::Lt,
+        Token::LessThanEqual => BinOp::Le,
+        Token::Greater => BinOp::Gt,
+        Token::GreaterThanEqual => BinOp::Ge,
+        Token::AndAnd => BinOp::And,
+        Token::OrOr => BinOp::Or,
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// Human-readable description of a token for error messages
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Ident(name) => format!("identifier `{}`", name),
+        Token::Number(n) => format!("number `{}`", n),
+        Token::Numeral(text) => format!("number `{}`", text),
+        Token::String(_) => "string literal".to_string(),
+        Token::Eof => "end of file".to_string(),
+        Token::Unknown(c) => format!("unexpected character `{}`", c),
+        other => format!("`{}`", other),
+    }
 }
 
 type ParseResult<T> = Result<T, ParseError>;
 
+/// A parsed block entry: a statement, or the trailing value expression
+enum BlockItem {
+    Stmt(Box<Stmt>),
+    Tail(Expr),
+}
+
 impl Parser {
     pub fn new(input: &str) -> Self {
         let mut lexer = Lexer::new(input);
-        let current_token = lexer.next_token();
-        Parser { lexer, current_token }
+        let first = lexer.next_token();
+        let effects = BUILTIN_EFFECTS.iter().map(|(name, _)| name.to_string()).collect();
+        Parser {
+            lexer,
+            current_token: first.token,
+            current_span: first.span,
+            prev_span: Span::default(),
+            recovering: false,
+            errors: Vec::new(),
+            effects,
+            header_effects: None,
+            effect_vars: Vec::new(),
+            effect_var_spans: Vec::new(),
+        }
+    }
+    
+    /// Errors recorded so far while recovering
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
     }
     
     fn advance(&mut self) {
-        self.current_token = self.lexer.next_token();
+        let next = self.lexer.next_token();
+        self.prev_span = self.current_span;
+        self.current_token = next.token;
+        self.current_span = next.span;
+    }
+    
+    /// Span from `start` up to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.prev_span)
+    }
+    
+    fn unexpected(&self, expected: &str) -> ParseError {
+        ParseError::UnexpectedToken {
+            expected: expected.to_string(),
+            found: self.current_token.clone(),
+            span: self.current_span,
+        }
+    }
+    
+    /// Whether the current token is a point where parsing can resume
+    fn at_sync_point(&self) -> bool {
+        matches!(
+            self.current_token,
+            Token::Fn | Token::Module | Token::Use | Token::Enum | Token::RBrace | Token::Eof
+        ) || self.current_span.line > self.prev_span.line
+    }
+    
+    /// Panic-mode recovery: skip tokens until the next `fn`, `module`,
+    /// `use`, `}` or the start of a new line. A closing `}` is consumed.
+    fn synchronize(&mut self) {
+        while !self.at_sync_point() {
+            if self.current_token == Token::LBrace {
+                self.skip_braced();
+                continue;
+            }
+            self.advance();
+        }
+        if self.current_token == Token::RBrace {
+            self.advance();
+        }
+    }
+    
+    /// Skip a balanced `{ ... }` group, e.g. the body of a broken function
+    fn skip_braced(&mut self) {
+        let mut depth = 0usize;
+        loop {
+            match self.current_token {
+                Token::LBrace => depth += 1,
+                Token::RBrace => depth -= 1,
+                Token::Eof => return,
+                _ => {}
+            }
+            self.advance();
+            if depth == 0 {
+                return;
+            }
+        }
     }
     
     fn expect(&mut self, expected: Token) -> ParseResult<()> {
@@ -37,10 +257,7 @@ impl Parser {
             self.advance();
             Ok(())
         } else {
-            Err(ParseError::UnexpectedToken {
-                expected: format!("{:?}", expected),
-                found: self.current_token.clone(),
-            })
+            Err(self.unexpected(&format!("`{}`", expected)))
         }
     }
     
@@ -51,38 +268,58 @@ impl Parser {
                 self.advance();
                 Ok(name)
             }
-            _ => Err(ParseError::UnexpectedToken {
-                expected: "identifier".to_string(),
-                found: self.current_token.clone(),
-            })
+            _ => Err(self.unexpected("identifier")),
         }
     }
     
-    fn expect_number(&mut self) -> ParseResult<u32> {
-        match &self.current_token {
-            Token::Number(n) => {
-                let num = *n as u32;
+    /// Parse the bound in `resource <= bound`, a number with an optional
+    /// fractional part and unit, into canonical units
+    fn parse_budget(&mut self, resource: Resource) -> ParseResult<u64> {
+        let start = self.current_span;
+        let literal = match &self.current_token {
+            Token::Number(n) => n.to_string(),
+            Token::Numeral(text) => text.clone(),
+            _ => return Err(self.unexpected("number")),
+        };
+        self.advance();
+        let scale = match &self.current_token {
+            Token::Ident(unit) => {
+                let Some(&(_, scale)) = resource.units().iter().find(|(name, _)| name == unit) else {
+                    return Err(ParseError::UnknownUnit { unit: unit.clone(), resource, span: self.current_span });
+                };
                 self.advance();
-                Ok(num)
+                scale
             }
-            _ => Err(ParseError::UnexpectedToken {
-                expected: "number".to_string(),
-                found: self.current_token.clone(),
-            })
+            _ => resource.default_scale(),
+        };
+
+        // Exact arithmetic: the digits, scaled, over a power of ten
+        let span = self.span_from(start);
+        let invalid = |reason: String| ParseError::InvalidResourceBudget { reason, span };
+        let too_large = || invalid("budget does not fit in 64 bits".to_string());
+        let (whole, fraction) = literal.split_once('.').unwrap_or((&literal, ""));
+        let digits: u128 = format!("{}{}", whole, fraction).parse().map_err(|_| too_large())?;
+        let scaled = digits.checked_mul(u128::from(scale)).ok_or_else(too_large)?;
+        let divisor = 10u128.checked_pow(fraction.len() as u32).ok_or_else(too_large)?;
+        if !scaled.is_multiple_of(divisor) {
+            let unit = match resource {
+                Resource::Tokens => "tokens",
+                Resource::Latency => "microseconds",
+                Resource::Energy => "microjoules",
+            };
+            return Err(invalid(format!("budget must be a whole number of {}", unit)));
         }
+        u64::try_from(scaled / divisor).map_err(|_| too_large())
     }
     
     /// Parse capability annotation: !{effects, resource constraints}
     pub fn parse_capability(&mut self) -> ParseResult<Capability> {
+        let start = self.current_span;
         self.expect(Token::Bang)?;
         self.expect(Token::LBrace)?;
         
         let mut effects = Vec::new();
-        let mut budgets = ResourceBudget {
-            tokens: None,
-            latency_ms: None,
-            energy_mj: None,
-        };
+        let mut budgets = ResourceBudget::default();
         
         loop {
             match &self.current_token {
@@ -90,90 +327,117 @@ impl Parser {
                 Token::Ident(name) => {
                     match name.as_str() {
                         // Effects
-                        "pure" => {
-                            effects.push(Effect::Pure);
-                            self.advance();
-                        }
-                        "alloc" => {
-                            effects.push(Effect::Alloc);
-                            self.advance();
-                        }
-                        "io" => {
-                            effects.push(Effect::Io);
-                            self.advance();
-                        }
-                        "net" => {
-                            effects.push(Effect::Net);
+                        _ if name == "pure" || self.effects.contains(name) || self.effect_vars.contains(name) => {
+                            effects.push(Effect::named(name));
                             self.advance();
                         }
                         // Resource constraints
-                        "tokens" => {
+                        "tokens" | "latency" | "energy" => {
+                            let resource = Resource::from_name(name).expect("matched a resource name");
                             self.advance();
                             self.expect(Token::LessThanEqual)?;
-                            budgets.tokens = Some(self.expect_number()?);
-                        }
-                        "latency" => {
-                            self.advance();
-                            self.expect(Token::LessThanEqual)?;
-                            let ms = self.expect_number()?;
-                            // Skip optional "ms" suffix
-                            if let Token::Ident(suffix) = &self.current_token {
-                                if suffix == "ms" {
-                                    self.advance();
-                                }
+                            let bound = Some(self.parse_budget(resource)?);
+                            match resource {
+                                Resource::Tokens => budgets.tokens = bound,
+                                Resource::Latency => budgets.latency_us = bound,
+                                Resource::Energy => budgets.energy_uj = bound,
                             }
-                            budgets.latency_ms = Some(ms);
                         }
-                        "energy" => {
+                        // The module header may name effects declared below it
+                        _ if self.header_effects.is_some() => {
+                            let name = name.clone();
+                            effects.push(Effect::named(&name));
+                            self.header_effects.as_mut().unwrap().push((name, self.current_span));
                             self.advance();
-                            self.expect(Token::LessThanEqual)?;
-                            let mj = self.expect_number()?;
-                            // Skip optional "mJ" suffix
-                            if let Token::Ident(suffix) = &self.current_token {
-                                if suffix == "mJ" {
-                                    self.advance();
-                                }
-                            }
-                            budgets.energy_mj = Some(mj);
                         }
-                        _ => return Err(ParseError::InvalidEffect(name.clone())),
+                        _ => return Err(ParseError::InvalidEffect {
+                            name: name.clone(),
+                            span: self.current_span,
+                        }),
                     }
                 }
                 Token::Comma => {
                     self.advance();
                     continue;
                 }
-                _ => return Err(ParseError::UnexpectedToken {
-                    expected: "effect or resource constraint".to_string(),
-                    found: self.current_token.clone(),
-                }),
+                _ => return Err(self.unexpected("effect or resource constraint")),
             }
             
             // Check for comma or closing brace
             match &self.current_token {
                 Token::Comma => self.advance(),
                 Token::RBrace => break,
-                _ => return Err(ParseError::UnexpectedToken {
-                    expected: ", or }".to_string(),
-                    found: self.current_token.clone(),
-                }),
+                _ => return Err(self.unexpected(", or }")),
             }
         }
         
         self.expect(Token::RBrace)?;
         
-        Ok(Capability { effects, budgets })
+        Ok(Capability { effects, budgets, span: self.span_from(start) })
     }
     
     /// Parse type annotation
     pub fn parse_type(&mut self) -> ParseResult<Type> {
+        let start = self.current_span;
+        if self.current_token == Token::Fn {
+            return self.parse_function_type();
+        }
         let type_name = self.expect_ident()?;
         
-        match type_name.as_str() {
-            "Int" => Ok(Type::Int),
-            "Text" => Ok(Type::Text),
-            "Bool" => Ok(Type::Bool),
-            _ => Ok(Type::Custom(type_name)),
+        let kind = match type_name.as_str() {
+            "Int" => TypeKind::Int,
+            "Text" => TypeKind::Text,
+            "Bool" => TypeKind::Bool,
+            // `Vec<T>` is the prelude name for arrays
+            "Array" | "Vec" if self.current_token == Token::Less => {
+                self.advance();
+                let element = self.parse_type()?;
+                self.expect(Token::Greater)?;
+                TypeKind::Array(Box::new(element))
+            }
+            _ => TypeKind::Custom(type_name),
+        };
+        Ok(Type::new(kind, self.span_from(start)))
+    }
+    
+    /// Parse function type: fn(Int, Text) -> Bool !{io}
+    fn parse_function_type(&mut self) -> ParseResult<Type> {
+        let start = self.current_span;
+        self.expect(Token::Fn)?;
+        self.expect(Token::LParen)?;
+        
+        let mut params = Vec::new();
+        while self.current_token != Token::RParen {
+            params.push(self.parse_type()?);
+            match &self.current_token {
+                Token::Comma => self.advance(),
+                Token::RParen => break,
+                _ => return Err(self.unexpected(", or )")),
+            }
+        }
+        self.expect(Token::RParen)?;
+        
+        let returns = self.parse_return_type()?;
+        let capability = if self.current_token == Token::Bang {
+            Some(self.parse_capability()?)
+        } else {
+            None
+        };
+        
+        Ok(Type::new(TypeKind::Function {
+            params,
+            returns: Box::new(returns),
+            capability,
+        }, self.span_from(start)))
+    }
+    
+    /// Parse optional `-> Type`; a missing return type means `Unit`
+    fn parse_return_type(&mut self) -> ParseResult<Type> {
+        if self.current_token == Token::Arrow {
+            self.advance();
+            self.parse_type()
+        } else {
+            Ok(Type::new(TypeKind::Unit, self.prev_span))
         }
     }
     
@@ -183,18 +447,34 @@ impl Parser {
         let mut params = Vec::new();
         
         while self.current_token != Token::RParen {
-            let name = self.expect_ident()?;
-            self.expect(Token::Colon)?;
-            let ty = self.parse_type()?;
-            params.push((name, ty));
+            let start = self.current_span;
+            let name = match &self.current_token {
+                Token::Ident(name) => name.clone(),
+                _ => "_".to_string(),
+            };
+            match self.parse_param() {
+                Ok(param) => params.push(param),
+                Err(err) if self.recovering => {
+                    // Skip to the end of this parameter and keep a placeholder
+                    while !matches!(
+                        self.current_token,
+                        Token::Comma | Token::RParen | Token::LBrace | Token::Fn | Token::Eof
+                    ) {
+                        self.advance();
+                    }
+                    if !matches!(self.current_token, Token::Comma | Token::RParen) {
+                        return Err(err);
+                    }
+                    self.errors.push(err);
+                    params.push((name, Type::new(TypeKind::Error, self.span_from(start))));
+                }
+                Err(err) => return Err(err),
+            }
             
             match &self.current_token {
                 Token::Comma => self.advance(),
                 Token::RParen => break,
-                _ => return Err(ParseError::UnexpectedToken {
-                    expected: ", or )".to_string(),
-                    found: self.current_token.clone(),
-                }),
+                _ => return Err(self.unexpected(", or )")),
             }
         }
         
@@ -202,47 +482,568 @@ impl Parser {
         Ok(params)
     }
     
+    fn parse_param(&mut self) -> ParseResult<(String, Type)> {
+        let name = self.expect_ident()?;
+        self.expect(Token::Colon)?;
+        let ty = self.parse_type()?;
+        Ok((name, ty))
+    }
+    
     /// Parse function declaration
     pub fn parse_function(&mut self) -> ParseResult<Stmt> {
+        let start = self.current_span;
         self.expect(Token::Fn)?;
         let name = self.expect_ident()?;
-        let params = self.parse_params()?;
-        
-        // Return type
-        self.expect(Token::Arrow)?;
-        let returns = self.parse_type()?;
+        let effect_params = self.parse_effect_params()?;
         
-        // Optional capability
-        let capability = if self.current_token == Token::Bang {
-            Some(self.parse_capability()?)
-        } else {
-            None
+        // Capabilities in the signature and body may name the variables
+        let outer_vars = std::mem::replace(&mut self.effect_vars, effect_params.clone());
+        let mut parse_rest = || {
+            let params = self.parse_params()?;
+            
+            // Return type
+            let returns = self.parse_return_type()?;
+            
+            // Optional capability
+            let capability = if self.current_token == Token::Bang {
+                Some(self.parse_capability()?)
+            } else {
+                None
+            };
+            
+            // Optional body; declarations without one are host functions
+            let body = if self.current_token == Token::LBrace {
+                Some(self.parse_block()?)
+            } else {
+                None
+            };
+            Ok((params, returns, capability, body))
         };
+        let rest = parse_rest();
+        self.effect_vars = outer_vars;
+        let (params, returns, capability, body) = rest?;
         
-        // For now, empty body
-        let body = Vec::new();
-        
-        Ok(Stmt::Function {
+        Ok(Stmt::new(StmtKind::Function {
             name,
+            effect_params,
             params,
             returns,
             capability,
             body,
-        })
+        }, self.span_from(start)))
     }
     
-    /// Parse module declaration
-    pub fn parse_module(&mut self) -> ParseResult<Module> {
-        self.expect(Token::Module)?;
-        let name = self.expect_ident()?;
+    /// Parse effect variables: <e, f>, or nothing
+    fn parse_effect_params(&mut self) -> ParseResult<Vec<String>> {
+        let mut vars = Vec::new();
+        if self.current_token != Token::Less {
+            return Ok(vars);
+        }
+        self.advance();
+        while self.current_token != Token::Greater {
+            let span = self.current_span;
+            let name = self.expect_ident()?;
+            if vars.contains(&name) {
+                let err = ParseError::InvalidEffectVariable {
+                    name,
+                    reason: "declared twice in this signature".to_string(),
+                    span,
+                };
+                if !self.recovering {
+                    return Err(err);
+                }
+                self.errors.push(err);
+            } else {
+                self.effect_var_spans.push((name.clone(), span));
+                vars.push(name);
+            }
+            match self.current_token {
+                Token::Comma => self.advance(),
+                Token::Greater => {}
+                _ => return Err(self.unexpected(", or >")),
+            }
+        }
+        self.advance();
+        Ok(vars)
+    }
+    
+    /// Parse block: { stmt; stmt; tail }
+    pub fn parse_block(&mut self) -> ParseResult<Block> {
+        let start = self.current_span;
+        self.expect(Token::LBrace)?;
+        
+        let mut stmts = Vec::new();
+        let mut tail = None;
+        
+        while self.current_token != Token::RBrace {
+            let stmt_start = self.current_span;
+            match self.parse_block_item() {
+                Ok(BlockItem::Stmt(stmt)) => stmts.push(*stmt),
+                Ok(BlockItem::Tail(expr)) => {
+                    tail = Some(Box::new(expr));
+                    break;
+                }
+                Err(err) if self.recovering && self.current_token != Token::Eof => {
+                    self.errors.push(err);
+                    if self.current_span == stmt_start {
+                        self.advance();
+                    }
+                    self.synchronize_statement();
+                    stmts.push(Stmt::new(StmtKind::Error, self.span_from(stmt_start)));
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        
+        self.expect(Token::RBrace)?;
+        Ok(Block { stmts, tail, span: self.span_from(start) })
+    }
+    
+    /// Parse one statement of a block, or its trailing expression
+    fn parse_block_item(&mut self) -> ParseResult<BlockItem> {
+        if self.current_token == Token::Let {
+            let stmt = self.parse_let()?;
+            self.expect(Token::Semicolon)?;
+            return Ok(BlockItem::Stmt(Box::new(stmt)));
+        }
+        
+        // Block-like expressions end a statement without needing a `;`
+        if matches!(
+            self.current_token,
+            Token::LBrace | Token::If | Token::While | Token::For | Token::Match
+        ) {
+            let expr = self.parse_block_like()?;
+            return match self.current_token {
+                Token::RBrace => Ok(BlockItem::Tail(expr)),
+                _ => {
+                    if self.current_token == Token::Semicolon {
+                        self.advance();
+                    }
+                    let span = self.span_from(expr.span);
+                    Ok(BlockItem::Stmt(Box::new(Stmt::new(StmtKind::Expression(expr), span))))
+                }
+            };
+        }
         
-        // Handle dotted names (e.g., data.pipeline)
-        let mut full_name = name;
+        let expr = self.parse_expr()?;
+        
+        // Assignment: name = value;
+        if self.current_token == Token::Assign {
+            let ExprKind::Ident(name) = &expr.kind else {
+                return Err(self.unexpected("; or }"));
+            };
+            let name = name.clone();
+            self.advance();
+            let value = self.parse_expr_or_error()?;
+            self.expect(Token::Semicolon)?;
+            let span = self.span_from(expr.span);
+            return Ok(BlockItem::Stmt(Box::new(Stmt::new(StmtKind::Assign { name, value }, span))));
+        }
+        
+        match &self.current_token {
+            Token::Semicolon => {
+                self.advance();
+                let span = self.span_from(expr.span);
+                Ok(BlockItem::Stmt(Box::new(Stmt::new(StmtKind::Expression(expr), span))))
+            }
+            Token::RBrace => Ok(BlockItem::Tail(expr)),
+            _ => Err(self.unexpected("; or }")),
+        }
+    }
+    
+    /// Recovery inside a block: skip past the next `;`, or stop before the
+    /// closing `}` or at the start of a new line
+    fn synchronize_statement(&mut self) {
+        loop {
+            match self.current_token {
+                Token::Semicolon => {
+                    self.advance();
+                    return;
+                }
+                Token::RBrace | Token::Fn | Token::Eof => return,
+                _ if self.current_span.line > self.prev_span.line => return,
+                _ => self.advance(),
+            }
+        }
+    }
+    
+    /// Parse a possibly dotted name (e.g., data.pipeline)
+    fn parse_dotted_name(&mut self) -> ParseResult<String> {
+        let mut full_name = self.expect_ident()?;
         while let Token::Unknown('.') = self.current_token {
             self.advance();
             full_name.push('.');
             full_name.push_str(&self.expect_ident()?);
         }
+        Ok(full_name)
+    }
+    
+    /// Skip an optional `;` terminating a top-level item
+    fn skip_semicolon(&mut self) {
+        if self.current_token == Token::Semicolon {
+            self.advance();
+        }
+    }
+    
+    /// Parse expression
+    pub fn parse_expr(&mut self) -> ParseResult<Expr> {
+        self.parse_binary(0)
+    }
+    
+    /// Precedence climbing over binary operators; all operators are
+    /// left-associative
+    fn parse_binary(&mut self, min_precedence: u8) -> ParseResult<Expr> {
+        let mut lhs = self.parse_unary()?;
+        
+        while let Some(op) = binary_op(&self.current_token) {
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.advance();
+            let rhs = self.parse_binary(precedence + 1)?;
+            let span = lhs.span.to(rhs.span);
+            lhs = Expr::new(
+                ExprKind::Binary { op, lhs: Box::new(lhs), rhs: Box::new(rhs) },
+                span,
+            );
+        }
+        
+        Ok(lhs)
+    }
+    
+    /// Parse prefix `-` and `!`
+    ///
+    /// In expression position `!` is always boolean negation; capability
+    /// annotations only follow a signature's return type.
+    fn parse_unary(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span;
+        let op = match self.current_token {
+            Token::Minus => UnaryOp::Neg,
+            Token::Bang => UnaryOp::Not,
+            _ => return self.parse_postfix(),
+        };
+        self.advance();
+        
+        if op == UnaryOp::Not && self.current_token == Token::LBrace {
+            return Err(ParseError::UnexpectedToken {
+                expected: "expression after `!` (capabilities `!{...}` belong after a return type)"
+                    .to_string(),
+                found: Token::LBrace,
+                span: start.to(self.current_span),
+            });
+        }
+        
+        let operand = self.parse_unary()?;
+        Ok(Expr::new(
+            ExprKind::Unary { op, operand: Box::new(operand) },
+            self.span_from(start),
+        ))
+    }
+    
+    /// Parse expression; when recovering, a broken expression is recorded
+    /// and replaced by an `ExprKind::Error` placeholder
+    fn parse_expr_or_error(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span;
+        match self.parse_expr() {
+            Ok(expr) => Ok(expr),
+            Err(err) if self.recovering => {
+                self.errors.push(err);
+                while !matches!(
+                    self.current_token,
+                    Token::Semicolon | Token::RBrace | Token::Fn | Token::Eof
+                ) && self.current_span.line == self.prev_span.line {
+                    self.advance();
+                }
+                let span = if self.prev_span.end > start.start {
+                    self.span_from(start)
+                } else {
+                    start
+                };
+                Ok(Expr::new(ExprKind::Error, span))
+            }
+            Err(err) => Err(err),
+        }
+    }
+    
+    /// Parse calls applied to a primary expression: f(a, b)(c)
+    fn parse_postfix(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span;
+        let mut expr = self.parse_primary()?;
+        
+        loop {
+            match self.current_token {
+                Token::LParen => {
+                    self.advance();
+                    let args = self.parse_expr_list(Token::RParen)?;
+                    expr = Expr::new(
+                        ExprKind::Call { func: Box::new(expr), args },
+                        self.span_from(start),
+                    );
+                }
+                Token::LBracket => {
+                    self.advance();
+                    let index = self.parse_expr()?;
+                    self.expect(Token::RBracket)?;
+                    expr = Expr::new(
+                        ExprKind::Index { base: Box::new(expr), index: Box::new(index) },
+                        self.span_from(start),
+                    );
+                }
+                _ => break,
+            }
+        }
+        
+        Ok(expr)
+    }
+    
+    /// Parse comma-separated expressions up to and including `close`
+    fn parse_expr_list(&mut self, close: Token) -> ParseResult<Vec<Expr>> {
+        let mut items = Vec::new();
+        while self.current_token != close {
+            items.push(self.parse_expr()?);
+            match &self.current_token {
+                Token::Comma => self.advance(),
+                token if *token == close => break,
+                _ => return Err(self.unexpected(&format!(", or {}", close))),
+            }
+        }
+        self.expect(close)?;
+        Ok(items)
+    }
+    
+    /// Parse literal, identifier or parenthesized expression
+    fn parse_primary(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span;
+        let kind = match &self.current_token {
+            Token::Number(n) => ExprKind::Number(*n),
+            Token::Numeral(literal) => {
+                return Err(ParseError::InvalidNumber { literal: literal.clone(), span: start });
+            }
+            Token::String(value) => ExprKind::String(value.clone()),
+            Token::Ident(name) => {
+                let name = name.clone();
+                self.advance();
+                if self.current_token == Token::ColonColon {
+                    self.advance();
+                    let variant = self.expect_ident()?;
+                    let kind = ExprKind::Variant { enum_name: name, variant };
+                    return Ok(Expr::new(kind, self.span_from(start)));
+                }
+                return Ok(Expr::new(ExprKind::Ident(name), self.span_from(start)));
+            }
+            Token::True => ExprKind::Bool(true),
+            Token::False => ExprKind::Bool(false),
+            Token::LParen => {
+                self.advance();
+                let inner = self.parse_expr()?;
+                self.expect(Token::RParen)?;
+                return Ok(Expr::new(inner.kind, self.span_from(start)));
+            }
+            Token::LBracket => {
+                self.advance();
+                let items = self.parse_expr_list(Token::RBracket)?;
+                return Ok(Expr::new(ExprKind::Array(items), self.span_from(start)));
+            }
+            Token::LBrace | Token::If | Token::While | Token::For | Token::Match => {
+                return self.parse_block_like();
+            }
+            _ => return Err(self.unexpected("expression")),
+        };
+        self.advance();
+        Ok(Expr::new(kind, self.span_from(start)))
+    }
+    
+    /// Parse a block, `if`, `while`, `for` or `match` expression
+    fn parse_block_like(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span;
+        let kind = match self.current_token {
+            Token::LBrace => ExprKind::Block(self.parse_block()?),
+            Token::If => return self.parse_if(),
+            Token::While => {
+                self.advance();
+                let cond = self.parse_expr()?;
+                let body = self.parse_block()?;
+                ExprKind::While { cond: Box::new(cond), body }
+            }
+            Token::For => {
+                self.advance();
+                let var = self.expect_ident()?;
+                self.expect(Token::In)?;
+                let iter = self.parse_expr()?;
+                let body = self.parse_block()?;
+                ExprKind::For { var, iter: Box::new(iter), body }
+            }
+            Token::Match => return self.parse_match(),
+            _ => return Err(self.unexpected("block, `if`, `while`, `for` or `match`")),
+        };
+        Ok(Expr::new(kind, self.span_from(start)))
+    }
+    
+    /// Parse if expression: if cond { ... } else if cond { ... } else { ... }
+    fn parse_if(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span;
+        self.expect(Token::If)?;
+        let cond = self.parse_expr()?;
+        let then_branch = self.parse_block()?;
+        
+        let else_branch = if self.current_token == Token::Else {
+            self.advance();
+            let branch = if self.current_token == Token::If {
+                self.parse_if()?
+            } else {
+                let block_start = self.current_span;
+                let block = self.parse_block()?;
+                Expr::new(ExprKind::Block(block), self.span_from(block_start))
+            };
+            Some(Box::new(branch))
+        } else {
+            None
+        };
+        
+        Ok(Expr::new(ExprKind::If {
+            cond: Box::new(cond),
+            then_branch,
+            else_branch,
+        }, self.span_from(start)))
+    }
+    
+    /// Parse match expression: match value { pattern => expr, ... }
+    fn parse_match(&mut self) -> ParseResult<Expr> {
+        let start = self.current_span;
+        self.expect(Token::Match)?;
+        let scrutinee = self.parse_expr()?;
+        self.expect(Token::LBrace)?;
+        
+        let mut arms = Vec::new();
+        while self.current_token != Token::RBrace {
+            let arm_start = self.current_span;
+            let pattern = self.parse_pattern()?;
+            self.expect(Token::FatArrow)?;
+            let body = self.parse_expr()?;
+            let block_like = body.kind.is_block_like();
+            arms.push(MatchArm { pattern, body, span: self.span_from(arm_start) });
+            
+            match &self.current_token {
+                Token::Comma => self.advance(),
+                Token::RBrace => break,
+                _ if block_like => {}
+                _ => return Err(self.unexpected(", or }")),
+            }
+        }
+        self.expect(Token::RBrace)?;
+        
+        Ok(Expr::new(ExprKind::Match {
+            scrutinee: Box::new(scrutinee),
+            arms,
+        }, self.span_from(start)))
+    }
+    
+    /// Parse match pattern: literal, Enum::Variant, binding or `_`
+    pub fn parse_pattern(&mut self) -> ParseResult<Pattern> {
+        let start = self.current_span;
+        let kind = match &self.current_token {
+            Token::Ident(name) if name == "_" => PatternKind::Wildcard,
+            Token::Ident(name) => {
+                let name = name.clone();
+                self.advance();
+                if self.current_token == Token::ColonColon {
+                    self.advance();
+                    let variant = self.expect_ident()?;
+                    let kind = PatternKind::Variant { enum_name: name, variant };
+                    return Ok(Pattern { kind, span: self.span_from(start) });
+                }
+                return Ok(Pattern { kind: PatternKind::Binding(name), span: self.span_from(start) });
+            }
+            Token::Number(n) => PatternKind::Number(*n),
+            Token::Numeral(literal) => {
+                return Err(ParseError::InvalidNumber { literal: literal.clone(), span: start });
+            }
+            Token::Minus => {
+                self.advance();
+                match self.current_token {
+                    Token::Number(n) => PatternKind::Number(-n),
+                    _ => return Err(self.unexpected("number")),
+                }
+            }
+            Token::String(value) => PatternKind::String(value.clone()),
+            Token::True => PatternKind::Bool(true),
+            Token::False => PatternKind::Bool(false),
+            _ => return Err(self.unexpected("pattern")),
+        };
+        self.advance();
+        Ok(Pattern { kind, span: self.span_from(start) })
+    }
+    
+    /// Parse let binding: let name: Type = value
+    pub fn parse_let(&mut self) -> ParseResult<Stmt> {
+        let start = self.current_span;
+        self.expect(Token::Let)?;
+        let name = self.expect_ident()?;
+        
+        let ty = if self.current_token == Token::Colon {
+            self.advance();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+        
+        self.expect(Token::Assign)?;
+        let value = self.parse_expr_or_error()?;
+        
+        Ok(Stmt::new(StmtKind::Let { name, ty, value }, self.span_from(start)))
+    }
+    
+    /// Parse enum declaration: enum Color { Red, Green, Blue }
+    pub fn parse_enum(&mut self) -> ParseResult<Stmt> {
+        let start = self.current_span;
+        self.expect(Token::Enum)?;
+        let name = self.expect_ident()?;
+        self.expect(Token::LBrace)?;
+        
+        let mut variants = Vec::new();
+        while self.current_token != Token::RBrace {
+            variants.push(self.expect_ident()?);
+            match &self.current_token {
+                Token::Comma => self.advance(),
+                Token::RBrace => break,
+                _ => return Err(self.unexpected(", or }")),
+            }
+        }
+        self.expect(Token::RBrace)?;
+        
+        Ok(Stmt::new(StmtKind::Enum { name, variants }, self.span_from(start)))
+    }
+    
+    /// Parse effect declaration: effect fs <: io
+    pub fn parse_effect_decl(&mut self) -> ParseResult<EffectDecl> {
+        let start = self.current_span;
+        match &self.current_token {
+            Token::Ident(keyword) if keyword == "effect" => self.advance(),
+            _ => return Err(self.unexpected("`effect`")),
+        }
+        let name = self.expect_ident()?;
+        let parent = if self.current_token == Token::Less {
+            self.advance();
+            self.expect(Token::Colon)?;
+            Some(self.expect_ident()?)
+        } else {
+            None
+        };
+        self.effects.insert(name.clone());
+        Ok(EffectDecl { name, parent, span: self.span_from(start) })
+    }
+    
+    /// Parse import: use data.pipeline
+    pub fn parse_use(&mut self) -> ParseResult<String> {
+        self.expect(Token::Use)?;
+        self.parse_dotted_name()
+    }
+    
+    /// Parse module declaration
+    pub fn parse_module(&mut self) -> ParseResult<Module> {
+        self.expect(Token::Module)?;
+        let full_name = self.parse_dotted_name()?;
         
         // Optional capability
         let capability = if self.current_token == Token::Bang {
@@ -255,9 +1056,115 @@ impl Parser {
             name: full_name,
             capability,
             imports: Vec::new(),
+            effects: Vec::new(),
             statements: Vec::new(),
         })
     }
+    
+    /// Parse a whole `.fg` file: the module header, then `use` imports,
+    /// `effect` declarations, top-level `let` bindings and `fn` declarations
+    /// in any order
+    ///
+    /// Parsing recovers from errors instead of stopping at the first one.
+    /// Broken items are kept as `StmtKind::Error` placeholders so later
+    /// passes can still run over the rest of the module.
+    pub fn parse_program(&mut self) -> (Module, Vec<ParseError>) {
+        self.recovering = true;
+        
+        self.header_effects = Some(Vec::new());
+        let header = self.parse_module();
+        let header_effects = self.header_effects.take().unwrap_or_default();
+        let mut module = match header {
+            Ok(module) => module,
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize();
+                Module {
+                    name: String::new(),
+                    capability: None,
+                    imports: Vec::new(),
+                    effects: Vec::new(),
+                    statements: Vec::new(),
+                }
+            }
+        };
+        
+        while self.current_token != Token::Eof {
+            let start = self.current_span;
+            let is_function = self.current_token == Token::Fn;
+            let result = match self.current_token {
+                Token::Fn => self.parse_function().map(Some),
+                Token::Let => self.parse_let().map(Some),
+                Token::Enum => self.parse_enum().map(Some),
+                Token::Use => self.parse_use().map(|import| {
+                    module.imports.push(import);
+                    None
+                }),
+                Token::Ident(ref keyword) if keyword == "effect" => self.parse_effect_decl().map(|decl| {
+                    module.effects.push(decl);
+                    None
+                }),
+                Token::Module => Err(ParseError::UnexpectedToken {
+                    expected: "a single module header per file".to_string(),
+                    found: Token::Module,
+                    span: self.current_span,
+                }),
+                _ => Err(self.unexpected("`fn`, `let`, `enum`, `effect` or `use`")),
+            };
+            match result {
+                Ok(stmt) => {
+                    module.statements.extend(stmt);
+                    self.skip_semicolon();
+                }
+                Err(err) => {
+                    self.errors.push(err);
+                    // Always make progress past the offending token
+                    if self.current_span == start {
+                        self.advance();
+                    }
+                    self.synchronize();
+                    // A function whose header failed still has its body
+                    // ahead; skip it rather than report its `{` as well
+                    if is_function && self.current_token == Token::LBrace {
+                        self.skip_braced();
+                    }
+                    let span = self.span_from(start);
+                    module.statements.push(Stmt::new(StmtKind::Error, span));
+                }
+            }
+        }
+        
+        // Every declaration has been seen now
+        let unknown = header_effects.into_iter()
+            .filter(|(name, _)| !self.effects.contains(name))
+            .map(|(name, span)| ParseError::InvalidEffect { name, span });
+        // A variable named like an effect could stand for that effect, so
+        // instantiating it would hide what the function really does
+        let clashing = std::mem::take(&mut self.effect_var_spans).into_iter()
+            .filter(|(name, _)| name == "pure" || self.effects.contains(name))
+            .map(|(name, span)| ParseError::InvalidEffectVariable {
+                name,
+                reason: "already the name of an effect".to_string(),
+                span,
+            });
+        let mut errors: Vec<ParseError> = unknown.chain(std::mem::take(&mut self.errors)).chain(clashing).collect();
+        for error in &mut errors {
+            if let ParseError::InvalidEffect { name, span } = error {
+                if let Some(decl) = module.effects.iter().find(|decl| &decl.name == name) {
+                    *error = ParseError::EffectUsedBeforeDeclaration {
+                        name: name.clone(),
+                        span: *span,
+                        declaration: decl.span,
+                    };
+                }
+            }
+        }
+        
+        errors.sort_by_key(|error| error.span().start);
+        
+        self.recovering = false;
+        (module, errors)
+    }
 }
 
 #[cfg(test)]
@@ -268,7 +1175,7 @@ mod tests {
     fn test_parse_simple_capability() {
         let mut parser = Parser::new("!{net}");
         let cap = parser.parse_capability().unwrap();
-        assert_eq!(cap.effects, vec![Effect::Net]);
+        assert_eq!(cap.effects, vec![Effect::named("net")]);
         assert_eq!(cap.budgets.tokens, None);
     }
     
@@ -277,9 +1184,9 @@ mod tests {
         let mut parser = Parser::new("!{io, net, alloc}");
         let cap = parser.parse_capability().unwrap();
         assert_eq!(cap.effects.len(), 3);
-        assert!(cap.effects.contains(&Effect::Io));
-        assert!(cap.effects.contains(&Effect::Net));
-        assert!(cap.effects.contains(&Effect::Alloc));
+        assert!(cap.effects.contains(&Effect::named("io")));
+        assert!(cap.effects.contains(&Effect::named("net")));
+        assert!(cap.effects.contains(&Effect::named("alloc")));
     }
     
     #[test]
@@ -287,25 +1194,152 @@ mod tests {
         let mut parser = Parser::new("!{tokens ≤ 100, latency ≤ 50ms}");
         let cap = parser.parse_capability().unwrap();
         assert_eq!(cap.budgets.tokens, Some(100));
-        assert_eq!(cap.budgets.latency_ms, Some(50));
+        assert_eq!(cap.budgets.latency_us, Some(50_000));
+    }
+
+    #[test]
+    fn test_effect_declarations() {
+        let (module, errors) = Parser::new("module m !{gpu}
+effect fs <: io;
+fn read() -> Text !{fs}
+effect gpu").parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let decls: Vec<_> = module.effects.iter().map(|d| (d.name.as_str(), d.parent.as_deref())).collect();
+        assert_eq!(decls, vec![("fs", Some("io")), ("gpu", None)]);
+        assert_eq!(module.capability.unwrap().effects, vec![Effect::named("gpu")]);
+        match &module.statements[0].kind {
+            StmtKind::Function { capability, .. } => {
+                assert_eq!(capability.as_ref().unwrap().effects, vec![Effect::named("fs")]);
+            }
+            _ => panic!("Expected Function statement"),
+        }
+    }
+    
+    #[test]
+    fn test_effect_used_before_declaration() {
+        let (_, errors) = Parser::new("module m !{disk}
+fn read() -> Text !{fs}
+effect fs <: io;").parse_program();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(matches!(&errors[0], ParseError::InvalidEffect { name, .. } if name == "disk"));
+        assert_eq!(errors[1].to_string(), "effect `fs` is used before it is declared");
+        assert_eq!(errors[1].code(), "E0009");
+        assert!(matches!(&errors[1], ParseError::EffectUsedBeforeDeclaration { span, declaration, .. }
+            if span.line == 2 && declaration.line == 3));
+    }
+    
+    #[test]
+    fn test_effect_variables_scoped_to_function() {
+        let (module, errors) = Parser::new("module m
+fn map<e, g>(xs: Array<Int>, f: fn(Int) -> Int !{e}) -> Array<Int> !{alloc, e, g}
+fn leak() !{e}").parse_program();
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], ParseError::InvalidEffect { name, span } if name == "e" && span.line == 3));
+        match &module.statements[0].kind {
+            StmtKind::Function { effect_params, capability, .. } => {
+                assert_eq!(effect_params, &vec!["e".to_string(), "g".to_string()]);
+                assert_eq!(capability.as_ref().unwrap().effect().to_string(), "alloc, e, g");
+            }
+            _ => panic!("Expected Function statement"),
+        }
+    }
+    
+    #[test]
+    fn test_effect_variables_need_fresh_names() {
+        let (_, errors) = Parser::new("module m
+fn log(x: Int) -> Int !{io}
+fn apply<io>(f: fn(Int) -> Int !{io}, x: Int) -> Int !{io} { log(x) }
+fn twice<e, e>(f: fn(Int) -> Int !{e}) -> Int !{e}
+fn later<gpu>(f: fn(Int) -> Int !{gpu}) -> Int !{gpu}
+effect gpu;").parse_program();
+        let found: Vec<_> = errors.iter().map(|e| (e.code(), e.to_string(), e.span().line)).collect();
+        assert_eq!(found, vec![
+            ("E0008", "invalid effect variable `io`".to_string(), 3),
+            ("E0008", "invalid effect variable `e`".to_string(), 4),
+            ("E0008", "invalid effect variable `gpu`".to_string(), 5),
+        ]);
+    }
+    
+    #[test]
+    fn test_budget_units() {
+        let mut parser = Parser::new("!{tokens <= 1.5k, latency <= 2s, energy <= 250uJ}");
+        let budgets = parser.parse_capability().unwrap().budgets;
+        assert_eq!(budgets, ResourceBudget { tokens: Some(1_500), latency_us: Some(2_000_000), energy_uj: Some(250) });
+
+        let budgets = Parser::new("!{latency <= 0.25ms, energy <= 3}").parse_capability().unwrap().budgets;
+        assert_eq!((budgets.latency_us, budgets.energy_uj), (Some(250), Some(3_000)));
+
+        let err = Parser::new("!{latency <= 5min}").parse_capability().unwrap_err();
+        assert_eq!(err.to_string(), "unknown latency unit `min`");
+        assert_eq!(err.code(), "E0006");
+
+        for src in ["!{latency <= 0.5us}", "!{tokens <= 20000000000000M}", "!{energy <= 99999999999999999999999999999999999999999J}"] {
+            let err = Parser::new(src).parse_capability().unwrap_err();
+            assert!(matches!(err, ParseError::InvalidResourceBudget { .. }), "{}: {:?}", src, err);
+        }
+
+        let (_, errors) = Parser::new("module m\nfn f() -> Int { 9223372036854775808 }").parse_program();
+        assert_eq!(errors[0].code(), "E0007");
     }
     
     #[test]
     fn test_parse_function_with_capability() {
         let mut parser = Parser::new("fn process(data: Text) -> Int !{net, tokens ≤ 8}");
-        match parser.parse_function().unwrap() {
-            Stmt::Function { name, params, returns, capability, .. } => {
+        match parser.parse_function().unwrap().kind {
+            StmtKind::Function { name, params, returns, capability, .. } => {
                 assert_eq!(name, "process");
                 assert_eq!(params.len(), 1);
                 assert_eq!(params[0].0, "data");
-                matches!(params[0].1, Type::Text);
-                matches!(returns, Type::Int);
+                matches!(params[0].1.kind, TypeKind::Text);
+                matches!(returns.kind, TypeKind::Int);
                 
                 let cap = capability.unwrap();
-                assert!(cap.effects.contains(&Effect::Net));
+                assert!(cap.effects.contains(&Effect::named("net")));
                 assert_eq!(cap.budgets.tokens, Some(8));
             }
             _ => panic!("Expected Function statement"),
         }
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_node_spans() {
+        let src = "fn process(data: Text) -> Int !{net}";
+        let mut parser = Parser::new(src);
+        let stmt = parser.parse_function().unwrap();
+        assert_eq!(stmt.span, Span::new(0, src.len(), 1, 1));
+        
+        match stmt.kind {
+            StmtKind::Function { params, returns, capability, .. } => {
+                assert_eq!(&src[params[0].1.span.start..params[0].1.span.end], "Text");
+                assert_eq!(returns.span.col, 27);
+                let cap = capability.unwrap();
+                assert_eq!(&src[cap.span.start..cap.span.end], "!{net}");
+            }
+            _ => panic!("Expected Function statement"),
+        }
+    }
+    
+    #[test]
+    fn test_error_span() {
+        let mut parser = Parser::new("fn f(x Int) -> Int");
+        let err = parser.parse_function().unwrap_err();
+        assert!(matches!(err, ParseError::UnexpectedToken { .. }));
+        assert_eq!(err.span(), Span::new(7, 10, 1, 8));
+    }
+    
+    #[test]
+    fn test_error_diagnostic() {
+        use crate::diagnostics::{RenderStyle, SourceFile};
+        
+        let src = "fn f(x Int) -> Int";
+        let err = Parser::new(src).parse_function().unwrap_err();
+        let rendered = err.to_diagnostic().render(&SourceFile::new("f.fg", src), RenderStyle::Rich);
+        assert_eq!(rendered, "\
+error[E0001]: expected `:`, found identifier `Int`
+ --> f.fg:1:8
+  |
+1 | fn f(x Int) -> Int
+  |        ^^^ expected `:`
+");
+    }
+}
@@ -0,0 +1,54 @@
+//! Source span module for Forge Lang - Phase α
+//!
+//! Byte ranges with line/column information attached to tokens and AST nodes
+
+/// A region of source text
+///
+/// `start..end` is a byte range into the original input; `line` and `col`
+/// are the 1-based position of `start` (columns count characters, not bytes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: u32,
+    pub col: u32,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: u32, col: u32) -> Self {
+        Span { start, end, line, col }
+    }
+
+    /// Span covering `self` through the end of `other`
+    pub fn to(&self, other: Span) -> Span {
+        Span {
+            start: self.start,
+            end: other.end.max(self.end),
+            line: self.line,
+            col: self.col,
+        }
+    }
+
+    /// Length of the span in bytes
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_to() {
+        let a = Span::new(0, 2, 1, 1);
+        let b = Span::new(5, 9, 1, 6);
+        let joined = a.to(b);
+        assert_eq!(joined, Span::new(0, 9, 1, 1));
+        assert_eq!(joined.len(), 9);
+    }
+}
@@ -0,0 +1,755 @@
+//! Type checker - resolves `ast::Type` annotations and infers local types
+//!
+//! Checks every function body and top-level `let` against the declared
+//! signatures and records the result in a [`TypeTable`] side table, which
+//! lowering reads instead of re-deriving types from the AST.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::ast::*;
+use crate::desugar::LEN_BUILTIN;
+use crate::diagnostics::Diagnostic;
+use crate::effects::infer_effects;
+use crate::span::Span;
+
+/// Resolved type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ty {
+    Unit,
+    Int,
+    Text,
+    Bool,
+    Array(Box<Ty>),
+    Function {
+        params: Vec<Ty>,
+        returns: Box<Ty>,
+        effect: Effect,
+    },
+    /// Declared enum
+    Enum(String),
+    /// Type of an erroneous or not yet known expression; compatible with
+    /// everything so one mistake is reported once
+    Error,
+}
+
+impl Ty {
+    /// Whether an erroneous type occurs anywhere in `self`
+    pub fn contains_error(&self) -> bool {
+        match self {
+            Ty::Error => true,
+            Ty::Array(element) => element.contains_error(),
+            Ty::Function { params, returns, .. } => {
+                params.iter().any(Ty::contains_error) || returns.contains_error()
+            }
+            Ty::Unit | Ty::Int | Ty::Text | Ty::Bool | Ty::Enum(_) => false,
+        }
+    }
+    
+    /// Whether a value of type `self` can be used where `expected` is
+    /// required. Effects of function types are the effect checker's job.
+    pub fn fits(&self, expected: &Ty) -> bool {
+        match (self, expected) {
+            (Ty::Error, _) | (_, Ty::Error) => true,
+            (Ty::Array(a), Ty::Array(b)) => a.fits(b),
+            (
+                Ty::Function { params: pa, returns: ra, .. },
+                Ty::Function { params: pb, returns: rb, .. },
+            ) => {
+                pa.len() == pb.len()
+                    && pa.iter().zip(pb).all(|(a, b)| a.fits(b))
+                    && ra.fits(rb)
+            }
+            (a, b) => a == b,
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ty::Unit => write!(f, "Unit"),
+            Ty::Int => write!(f, "Int"),
+            Ty::Text => write!(f, "Text"),
+            Ty::Bool => write!(f, "Bool"),
+            Ty::Array(element) => write!(f, "Array<{}>", element),
+            Ty::Function { params, returns, effect } => {
+                write!(f, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") -> {}", returns)?;
+                if !effect.is_pure() {
+                    write!(f, " !{{{}}}", effect)?;
+                }
+                Ok(())
+            }
+            Ty::Enum(name) => write!(f, "{}", name),
+            Ty::Error => write!(f, "{{unknown}}"),
+        }
+    }
+}
+
+/// Resolved signature of a module-level function
+#[derive(Debug, Clone)]
+pub struct FnSig {
+    pub params: Vec<(String, Ty)>,
+    pub returns: Ty,
+    pub effect: Effect,
+}
+
+impl FnSig {
+    /// Type of the function used as a value
+    pub fn ty(&self) -> Ty {
+        Ty::Function {
+            params: self.params.iter().map(|(_, ty)| ty.clone()).collect(),
+            returns: Box::new(self.returns.clone()),
+            effect: self.effect.clone(),
+        }
+    }
+}
+
+/// Types computed by the checker
+#[derive(Debug, Default)]
+pub struct TypeTable {
+    pub functions: HashMap<String, FnSig>,
+    /// Type of every checked expression, by span
+    pub exprs: HashMap<Span, Ty>,
+    /// Type bound by each `let`, by statement span
+    pub bindings: HashMap<Span, Ty>,
+    pub globals: HashMap<String, Ty>,
+}
+
+impl TypeTable {
+    pub fn expr_type(&self, expr: &Expr) -> Option<&Ty> {
+        self.exprs.get(&expr.span)
+    }
+    
+    /// Whether checking left any signature or expression without a type
+    pub fn has_errors(&self) -> bool {
+        self.functions.values().any(|sig| sig.ty().contains_error())
+            || self.exprs.values().any(Ty::contains_error)
+    }
+}
+
+struct TypeChecker {
+    enums: HashMap<String, Vec<String>>,
+    table: TypeTable,
+    scopes: Vec<HashMap<String, Ty>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeChecker {
+    fn error(&mut self, code: &str, message: String, span: Span, label: String) {
+        self.diagnostics.push(Diagnostic::error(message).with_code(code).with_primary(span, label));
+    }
+
+    fn mismatch(&mut self, expected: &Ty, found: &Ty, span: Span, origin: Option<Span>) {
+        let mut diagnostic = Diagnostic::error("mismatched types")
+            .with_code("E0203")
+            .with_primary(span, format!("expected `{}`, found `{}`", expected, found));
+        if let Some(origin) = origin {
+            diagnostic = diagnostic.with_secondary(origin, "expected because of this");
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    /// Resolve a written type
+    fn resolve(&mut self, ty: &Type) -> Ty {
+        match &ty.kind {
+            TypeKind::Unit => Ty::Unit,
+            TypeKind::Int => Ty::Int,
+            TypeKind::Text => Ty::Text,
+            TypeKind::Bool => Ty::Bool,
+            TypeKind::Array(element) => Ty::Array(Box::new(self.resolve(element))),
+            TypeKind::Function { params, returns, capability } => Ty::Function {
+                params: params.iter().map(|p| self.resolve(p)).collect(),
+                returns: Box::new(self.resolve(returns)),
+                effect: capability.as_ref().map_or(Effect::pure(), Capability::effect),
+            },
+            TypeKind::Custom(name) if name == "Unit" => Ty::Unit,
+            TypeKind::Custom(name) if self.enums.contains_key(name) => Ty::Enum(name.clone()),
+            TypeKind::Custom(name) => {
+                self.error(
+                    "E0201",
+                    format!("cannot find type `{}`", name),
+                    ty.span,
+                    "not a built-in type or declared enum".to_string(),
+                );
+                Ty::Error
+            }
+            TypeKind::Error => Ty::Error,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Ty> {
+        for scope in self.scopes.iter().rev() {
+            if let Some(ty) = scope.get(name) {
+                return Some(ty.clone());
+            }
+        }
+        if let Some(ty) = self.table.globals.get(name) {
+            return Some(ty.clone());
+        }
+        self.table.functions.get(name).map(FnSig::ty)
+    }
+
+    fn bind(&mut self, name: &str, ty: Ty) {
+        self.scopes.last_mut().expect("a scope is open").insert(name.to_string(), ty);
+    }
+
+    /// Check `expr` against an expected type
+    fn expect(&mut self, expr: &Expr, expected: &Ty, origin: Option<Span>) -> Ty {
+        let found = self.check_expr(expr);
+        if !found.fits(expected) {
+            self.mismatch(expected, &found, expr.span, origin);
+        }
+        found
+    }
+
+    fn check_block(&mut self, block: &Block) -> Ty {
+        self.scopes.push(HashMap::new());
+        for stmt in &block.stmts {
+            self.check_stmt(stmt);
+        }
+        let ty = match &block.tail {
+            Some(tail) => self.check_expr(tail),
+            None => Ty::Unit,
+        };
+        self.scopes.pop();
+        ty
+    }
+
+    /// Check a `let`, returning the type it binds
+    fn check_let(&mut self, stmt: &Stmt, ty: &Option<Type>, value: &Expr) -> Ty {
+        let bound = match ty {
+            Some(annotation) => {
+                let declared = self.resolve(annotation);
+                self.expect(value, &declared, Some(annotation.span));
+                declared
+            }
+            None => self.check_expr(value),
+        };
+        self.table.bindings.insert(stmt.span, bound.clone());
+        bound
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Let { name, ty, value } => {
+                let bound = self.check_let(stmt, ty, value);
+                self.bind(name, bound);
+            }
+            StmtKind::Assign { name, value } => match self.lookup(name) {
+                Some(target) => {
+                    self.expect(value, &target, None);
+                }
+                None => {
+                    self.check_expr(value);
+                    self.error(
+                        "E0202",
+                        format!("cannot assign to unknown variable `{}`", name),
+                        stmt.span,
+                        "not found in this scope".to_string(),
+                    );
+                }
+            },
+            StmtKind::Expression(expr) => {
+                self.check_expr(expr);
+            }
+            StmtKind::Function { .. } | StmtKind::Enum { .. } | StmtKind::Error => {}
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr) -> Ty {
+        let ty = self.infer_expr(expr);
+        self.table.exprs.insert(expr.span, ty.clone());
+        ty
+    }
+
+    fn infer_expr(&mut self, expr: &Expr) -> Ty {
+        match &expr.kind {
+            ExprKind::Number(_) => Ty::Int,
+            ExprKind::String(_) => Ty::Text,
+            ExprKind::Bool(_) => Ty::Bool,
+            ExprKind::Ident(name) => match self.lookup(name) {
+                Some(ty) => ty,
+                None => {
+                    self.error(
+                        "E0202",
+                        format!("cannot find value `{}`", name),
+                        expr.span,
+                        "not found in this scope".to_string(),
+                    );
+                    Ty::Error
+                }
+            },
+            ExprKind::Binary { op, lhs, rhs } => self.check_binary(*op, lhs, rhs),
+            ExprKind::Unary { op, operand } => {
+                let expected = match op {
+                    UnaryOp::Neg => Ty::Int,
+                    UnaryOp::Not => Ty::Bool,
+                };
+                self.expect(operand, &expected, None);
+                expected
+            }
+            ExprKind::Call { func, args } => self.check_call(expr, func, args),
+            ExprKind::Array(items) => {
+                let mut element = Ty::Error;
+                for item in items {
+                    if element == Ty::Error {
+                        element = self.check_expr(item);
+                    } else {
+                        self.expect(item, &element.clone(), None);
+                    }
+                }
+                Ty::Array(Box::new(element))
+            }
+            ExprKind::Index { base, index } => {
+                let base_ty = self.check_expr(base);
+                self.expect(index, &Ty::Int, None);
+                match base_ty {
+                    Ty::Array(element) => *element,
+                    Ty::Error => Ty::Error,
+                    other => {
+                        self.error(
+                            "E0206",
+                            format!("cannot index into a value of type `{}`", other),
+                            base.span,
+                            "not an array".to_string(),
+                        );
+                        Ty::Error
+                    }
+                }
+            }
+            ExprKind::Variant { enum_name, variant } => {
+                match self.enums.get(enum_name) {
+                    Some(variants) if variants.contains(variant) => Ty::Enum(enum_name.clone()),
+                    Some(_) => {
+                        self.error(
+                            "E0202",
+                            format!("no variant `{}` in enum `{}`", variant, enum_name),
+                            expr.span,
+                            "variant not found".to_string(),
+                        );
+                        Ty::Error
+                    }
+                    None => {
+                        self.error(
+                            "E0201",
+                            format!("cannot find enum `{}`", enum_name),
+                            expr.span,
+                            "not declared in this module".to_string(),
+                        );
+                        Ty::Error
+                    }
+                }
+            }
+            ExprKind::Block(block) => self.check_block(block),
+            ExprKind::If { cond, then_branch, else_branch } => {
+                self.expect(cond, &Ty::Bool, None);
+                let then_ty = self.check_block(then_branch);
+                match else_branch {
+                    Some(else_branch) => {
+                        let origin = then_branch.tail.as_ref().map_or(then_branch.span, |t| t.span);
+                        self.expect(else_branch, &then_ty, Some(origin));
+                        then_ty
+                    }
+                    None => {
+                        if !then_ty.fits(&Ty::Unit) {
+                            let span = then_branch.tail.as_ref().map_or(then_branch.span, |t| t.span);
+                            self.mismatch(&Ty::Unit, &then_ty, span, None);
+                        }
+                        Ty::Unit
+                    }
+                }
+            }
+            ExprKind::While { cond, body } => {
+                self.expect(cond, &Ty::Bool, None);
+                self.check_block(body);
+                Ty::Unit
+            }
+            ExprKind::For { var, iter, body } => {
+                let element = match self.check_expr(iter) {
+                    Ty::Array(element) => *element,
+                    Ty::Error => Ty::Error,
+                    other => {
+                        self.error(
+                            "E0206",
+                            format!("cannot iterate over a value of type `{}`", other),
+                            iter.span,
+                            "not an array".to_string(),
+                        );
+                        Ty::Error
+                    }
+                };
+                self.scopes.push(HashMap::from([(var.clone(), element)]));
+                self.check_block(body);
+                self.scopes.pop();
+                Ty::Unit
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee_ty = self.check_expr(scrutinee);
+                let mut result: Option<(Ty, Span)> = None;
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    self.check_pattern(&arm.pattern, &scrutinee_ty);
+                    match &result {
+                        Some((expected, origin)) => {
+                            let (expected, origin) = (expected.clone(), *origin);
+                            self.expect(&arm.body, &expected, Some(origin));
+                        }
+                        None => {
+                            let ty = self.check_expr(&arm.body);
+                            result = Some((ty, arm.body.span));
+                        }
+                    }
+                    self.scopes.pop();
+                }
+                self.check_exhaustive(expr.span, &scrutinee_ty, arms);
+                result.map_or(Ty::Unit, |(ty, _)| ty)
+            }
+            // Intent blocks are resolved at run time
+            ExprKind::IntentBlock { .. } | ExprKind::Error => Ty::Error,
+        }
+    }
+
+    fn check_binary(&mut self, op: BinOp, lhs: &Expr, rhs: &Expr) -> Ty {
+        match op {
+            BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => {
+                let lhs_ty = self.check_expr(lhs);
+                // `+` also concatenates text
+                let operand = if op == BinOp::Add && lhs_ty == Ty::Text { Ty::Text } else { Ty::Int };
+                if !lhs_ty.fits(&operand) {
+                    self.mismatch(&operand, &lhs_ty, lhs.span, None);
+                }
+                self.expect(rhs, &operand, None);
+                operand
+            }
+            BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                self.expect(lhs, &Ty::Int, None);
+                self.expect(rhs, &Ty::Int, None);
+                Ty::Bool
+            }
+            BinOp::Eq | BinOp::Ne => {
+                let lhs_ty = self.check_expr(lhs);
+                self.expect(rhs, &lhs_ty, Some(lhs.span));
+                Ty::Bool
+            }
+            BinOp::And | BinOp::Or => {
+                self.expect(lhs, &Ty::Bool, None);
+                self.expect(rhs, &Ty::Bool, None);
+                Ty::Bool
+            }
+        }
+    }
+
+    fn check_call(&mut self, call: &Expr, func: &Expr, args: &[Expr]) -> Ty {
+        // `len` is a builtin unless something shadows it
+        if matches!(&func.kind, ExprKind::Ident(name) if name == LEN_BUILTIN && self.lookup(name).is_none()) {
+            if args.len() != 1 {
+                self.arity_error(call, LEN_BUILTIN, 1, args.len());
+            }
+            for arg in args {
+                let ty = self.check_expr(arg);
+                if !ty.fits(&Ty::Array(Box::new(Ty::Error))) {
+                    self.mismatch(&Ty::Array(Box::new(Ty::Error)), &ty, arg.span, None);
+                }
+            }
+            return Ty::Int;
+        }
+
+        let func_ty = self.check_expr(func);
+        let Ty::Function { params, returns, .. } = func_ty else {
+            if func_ty != Ty::Error {
+                self.error(
+                    "E0205",
+                    format!("expected a function, found `{}`", func_ty),
+                    func.span,
+                    "not callable".to_string(),
+                );
+            }
+            for arg in args {
+                self.check_expr(arg);
+            }
+            return Ty::Error;
+        };
+
+        if params.len() != args.len() {
+            let name = match &func.kind {
+                ExprKind::Ident(name) => name.clone(),
+                _ => "function".to_string(),
+            };
+            self.arity_error(call, &name, params.len(), args.len());
+        }
+        for (i, arg) in args.iter().enumerate() {
+            match params.get(i) {
+                Some(param) => {
+                    self.expect(arg, param, None);
+                }
+                None => {
+                    self.check_expr(arg);
+                }
+            }
+        }
+        *returns
+    }
+
+    fn arity_error(&mut self, call: &Expr, name: &str, expected: usize, found: usize) {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        self.error(
+            "E0204",
+            format!(
+                "`{}` takes {} argument{} but {} {} supplied",
+                name, expected, plural(expected), found,
+                if found == 1 { "was" } else { "were" }
+            ),
+            call.span,
+            format!("expected {} argument{}", expected, plural(expected)),
+        );
+    }
+
+    fn check_pattern(&mut self, pattern: &Pattern, expected: &Ty) {
+        let found = match &pattern.kind {
+            PatternKind::Wildcard => return,
+            PatternKind::Binding(name) => {
+                self.bind(name, expected.clone());
+                return;
+            }
+            PatternKind::Number(_) => Ty::Int,
+            PatternKind::String(_) => Ty::Text,
+            PatternKind::Bool(_) => Ty::Bool,
+            PatternKind::Variant { enum_name, variant } => {
+                let known = self.enums.get(enum_name).map(|variants| variants.contains(variant));
+                if known != Some(true) {
+                    self.error(
+                        "E0202",
+                        format!("no variant `{}::{}`", enum_name, variant),
+                        pattern.span,
+                        "variant not found".to_string(),
+                    );
+                    return;
+                }
+                Ty::Enum(enum_name.clone())
+            }
+        };
+        if !found.fits(expected) {
+            self.mismatch(expected, &found, pattern.span, None);
+        }
+    }
+
+    /// Report a match whose arms leave some value of the scrutinee
+    /// unhandled; there is no implicit fallback arm
+    fn check_exhaustive(&mut self, span: Span, scrutinee_ty: &Ty, arms: &[MatchArm]) {
+        let irrefutable = arms.iter().any(|arm| {
+            matches!(arm.pattern.kind, PatternKind::Wildcard | PatternKind::Binding(_))
+        });
+        if irrefutable || scrutinee_ty.contains_error() {
+            return;
+        }
+        let patterns: Vec<&PatternKind> = arms.iter().map(|arm| &arm.pattern.kind).collect();
+        let missing: Vec<String> = match scrutinee_ty {
+            Ty::Enum(enum_name) => self.enums[enum_name].iter()
+                .filter(|v| !patterns.iter().any(|p| matches!(p, PatternKind::Variant { variant, .. } if variant == *v)))
+                .map(|v| format!("`{}::{}`", enum_name, v))
+                .collect(),
+            Ty::Bool => [true, false].into_iter()
+                .filter(|b| !patterns.iter().any(|p| matches!(p, PatternKind::Bool(value) if value == b)))
+                .map(|b| format!("`{}`", b))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let label = match missing.as_slice() {
+            [] if matches!(scrutinee_ty, Ty::Enum(_) | Ty::Bool) => return,
+            [] => format!("not every `{}` value is covered", scrutinee_ty),
+            _ => format!("{} not covered", missing.join(", ")),
+        };
+        self.diagnostics.push(
+            Diagnostic::error("non-exhaustive match")
+                .with_code("E0207")
+                .with_primary(span, label)
+                .with_help("add the missing arms, or a `_` arm to handle everything else"),
+        );
+    }
+
+    fn check_function(&mut self, name: &str, returns: &Type, body: &Block) {
+        let sig = self.table.functions[name].clone();
+        self.scopes.push(sig.params.iter().cloned().collect());
+        let body_ty = self.check_block(body);
+        self.scopes.pop();
+
+        if !body_ty.fits(&sig.returns) {
+            let span = body.tail.as_ref().map_or(body.span, |tail| tail.span);
+            self.mismatch(&sig.returns, &body_ty, span, Some(returns.span));
+        }
+    }
+}
+
+/// Type check a module, returning the side table and any type errors
+pub fn check_types(module: &Module) -> (TypeTable, Vec<Diagnostic>) {
+    let mut checker = TypeChecker {
+        enums: HashMap::new(),
+        table: TypeTable::default(),
+        scopes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    for stmt in &module.statements {
+        if let StmtKind::Enum { name, variants } = &stmt.kind {
+            checker.enums.insert(name.clone(), variants.clone());
+        }
+    }
+
+    // Signatures first, so bodies can call functions declared later
+    let effects = infer_effects(module);
+    for stmt in &module.statements {
+        if let StmtKind::Function { name, params, returns, .. } = &stmt.kind {
+            let sig = FnSig {
+                params: params.iter().map(|(n, t)| (n.clone(), checker.resolve(t))).collect(),
+                returns: checker.resolve(returns),
+                effect: effects.get(name).cloned().unwrap_or(Effect::pure()),
+            };
+            checker.table.functions.insert(name.clone(), sig);
+        }
+    }
+
+    // Top-level lets are visible to every function body
+    for stmt in &module.statements {
+        if let StmtKind::Let { name, ty, value } = &stmt.kind {
+            checker.scopes.push(HashMap::new());
+            let bound = checker.check_let(stmt, ty, value);
+            checker.scopes.pop();
+            checker.table.globals.insert(name.clone(), bound);
+        }
+    }
+
+    for stmt in &module.statements {
+        if let StmtKind::Function { name, returns, body: Some(body), .. } = &stmt.kind {
+            checker.check_function(name, returns, body);
+        }
+    }
+
+    (checker.table, checker.diagnostics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Parser;
+
+    fn check(src: &str) -> (TypeTable, Vec<Diagnostic>) {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        check_types(&module)
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().filter_map(|d| d.code.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_well_typed_module() {
+        let (table, diagnostics) = check("module m
+enum Color { Red, Green }
+let limit = 10;
+fn scale(x: Int, by: Int) -> Int { x * by }
+fn total(xs: Array<Int>) -> Int {
+    let sum = 0;
+    for x in xs { sum = sum + scale(x, 2); }
+    if sum > limit { limit } else { sum }
+}
+fn name(c: Color) -> Text {
+    match c { Color::Red => \"red\", _ => \"other\" }
+}
+fn apply(f: fn(Int, Int) -> Int, n: Int) -> Int { f(n, len([1, 2])) }");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(table.functions["total"].params[0].1, Ty::Array(Box::new(Ty::Int)));
+        assert_eq!(table.globals["limit"], Ty::Int);
+        assert_eq!(table.functions["apply"].params[0].1.to_string(), "fn(Int, Int) -> Int");
+    }
+
+    #[test]
+    fn test_let_annotation_mismatch() {
+        let (_, diagnostics) = check("module m
+fn f() -> Int {
+    let x: Int = \"text\";
+    x
+}");
+        assert_eq!(codes(&diagnostics), vec!["E0203"]);
+        assert_eq!(diagnostics[0].labels[0].message, "expected `Int`, found `Text`");
+        assert_eq!(diagnostics[0].labels[1].span.line, 3);
+    }
+
+    #[test]
+    fn test_inferred_local_types() {
+        let (table, diagnostics) = check("module m
+fn f(flag: Bool) -> Int {
+    let xs = [1, 2, 3];
+    let first = xs[0];
+    let negated = !flag;
+    first
+}");
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let mut bound: Vec<String> = table.bindings.values().map(Ty::to_string).collect();
+        bound.sort();
+        assert_eq!(bound, vec!["Array<Int>", "Bool", "Int"]);
+    }
+
+    #[test]
+    fn test_call_arguments() {
+        let (_, diagnostics) = check("module m
+fn add(x: Int, y: Int) -> Int { x + y }
+fn f() -> Int {
+    add(1, true);
+    add(1)
+}");
+        assert_eq!(codes(&diagnostics), vec!["E0203", "E0204"]);
+        assert_eq!(diagnostics[1].message, "`add` takes 2 arguments but 1 was supplied");
+    }
+
+    #[test]
+    fn test_function_typed_argument() {
+        let (_, diagnostics) = check("module m
+fn is_even(x: Int) -> Bool { x % 2 == 0 }
+fn apply(f: fn(Int) -> Int, x: Int) -> Int { f(x) }
+fn main() -> Int { apply(is_even, 1) }");
+        assert_eq!(codes(&diagnostics), vec!["E0203"]);
+        assert_eq!(
+            diagnostics[0].labels[0].message,
+            "expected `fn(Int) -> Int`, found `fn(Int) -> Bool`"
+        );
+    }
+
+    #[test]
+    fn test_unknown_names_and_types() {
+        let (_, diagnostics) = check("module m
+fn f(c: Config) -> Int { missing + 1 }
+fn g() -> Int { let n = 1; n(2) }");
+        assert_eq!(codes(&diagnostics), vec!["E0201", "E0202", "E0205"]);
+    }
+
+    #[test]
+    fn test_return_type_mismatch() {
+        let (_, diagnostics) = check("module m
+fn f(x: Int) -> Text { x }
+fn g() -> Int { if true { 1 } else { false } }");
+        assert_eq!(codes(&diagnostics), vec!["E0203", "E0203"]);
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 2);
+    }
+
+    #[test]
+    fn test_non_exhaustive_match() {
+        let (_, diagnostics) = check("module m
+enum Shape { Circle, Square }
+fn f(n: Int) -> Int { match n { 0 => 1, 1 => 2 } }
+fn g(s: Shape) -> Int { match s { Shape::Circle => 1 } }
+fn h(b: Bool) -> Int { match b { true => 1, false => 0 } }
+fn k(s: Shape) -> Int { match s { Shape::Square => 1, Shape::Circle => 2 } }
+fn l(n: Int) -> Int { match n { 0 => 1, other => other } }");
+        assert_eq!(codes(&diagnostics), vec!["E0207", "E0207"]);
+        assert_eq!(diagnostics[0].labels[0].message, "not every `Int` value is covered");
+        assert_eq!(diagnostics[1].labels[0].message, "`Shape::Square` not covered");
+    }
+}
@@ -0,0 +1,670 @@
+//! IR verifier - structural validation of lowered IR
+//!
+//! Checks the invariants later passes and backends rely on:
+//!
+//! * blocks have unique labels, jump to existing blocks, keep phis first
+//!   and the entry block has no predecessors (E0503);
+//! * every SSA value is defined exactly once (E0501) and its definition
+//!   dominates each use (E0502), with phi operands used at the end of the
+//!   predecessor they flow in from;
+//! * phis have one input per predecessor edge (E0504);
+//! * called functions exist and get as many arguments as they take (E0505);
+//! * every value has an IR type and operands have the types their
+//!   instructions expect (E0506).
+//!
+//! Every block ends in exactly one terminator by construction of `IrBlock`.
+//! Findings are compiler bugs rather than user errors; lowering runs the
+//! verifier on its output in debug builds, as should every later pass (see
+//! `debug_verify`).
+
+use std::collections::HashMap;
+
+use crate::ast::BinOp;
+use crate::diagnostics::Diagnostic;
+use crate::ir::*;
+use crate::ir_text::print_module;
+use crate::span::Span;
+
+/// Verify every function in `module`
+pub fn verify_module(module: &IrModule) -> Vec<Diagnostic> {
+    module.functions.iter()
+        .flat_map(|func| verify_function(module, func))
+        .collect()
+}
+
+/// Verify one function of `module`, resolving callees in the module
+pub fn verify_function(module: &IrModule, func: &IrFunction) -> Vec<Diagnostic> {
+    // External declarations have nothing to check
+    if func.blocks.is_empty() {
+        return Vec::new();
+    }
+    let mut verifier = Verifier::new(module, func);
+    verifier.check_structure();
+    if verifier.diagnostics.is_empty() {
+        verifier.compute_dominators();
+        verifier.check_definitions();
+        verifier.check_uses();
+        verifier.check_types();
+    }
+    verifier.diagnostics
+}
+
+/// Panic with the verifier's findings if `module` is invalid, in debug
+/// builds only; `after` names the pass that produced `module`
+pub fn debug_verify(module: &IrModule, after: &str) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    let diagnostics = verify_module(module);
+    if !diagnostics.is_empty() {
+        let messages: Vec<String> = diagnostics.iter().map(|d| d.message.clone()).collect();
+        panic!("invalid IR after {}:\n{}\n\n{}", after, messages.join("\n"), print_module(module));
+    }
+}
+
+/// Whether a value of type `actual` can be used as `expected`; effects of
+/// function types are the effect checker's job
+fn compatible(actual: &IrType, expected: &IrType) -> bool {
+    match (actual, expected) {
+        (IrType::Array(a), IrType::Array(b)) => compatible(a, b),
+        (
+            IrType::Function { params: pa, returns: ra, .. },
+            IrType::Function { params: pb, returns: rb, .. },
+        ) => {
+            pa.len() == pb.len()
+                && pa.iter().zip(pb).all(|(a, b)| compatible(a, b))
+                && compatible(ra, rb)
+        }
+        _ => actual == expected,
+    }
+}
+
+/// Instruction position: block index, and instruction index within it,
+/// where the terminator is at `insts.len()`
+type Position = (usize, usize);
+
+struct Verifier<'a> {
+    module: &'a IrModule,
+    func: &'a IrFunction,
+    labels: HashMap<&'a str, usize>,
+    preds: Vec<Vec<usize>>,
+    /// Immediate dominator of each block; `None` for the entry block and
+    /// unreachable blocks
+    idom: Vec<Option<usize>>,
+    reachable: Vec<bool>,
+    /// Where each value is defined; parameters are defined before the
+    /// entry block's first instruction
+    defs: HashMap<String, (usize, Option<usize>)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(module: &'a IrModule, func: &'a IrFunction) -> Self {
+        let n = func.blocks.len();
+        Verifier {
+            module,
+            func,
+            labels: HashMap::new(),
+            preds: vec![Vec::new(); n],
+            idom: vec![None; n],
+            reachable: vec![false; n],
+            defs: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, code: &str, span: Span, message: String, label: &str) {
+        self.diagnostics.push(
+            Diagnostic::error(format!("invalid IR in `{}`: {}", self.func.name, message))
+                .with_code(code)
+                .with_primary(span, label),
+        );
+    }
+
+    fn span(&self, (block, index): Position) -> Span {
+        let block = &self.func.blocks[block];
+        block.spans.get(index).copied().unwrap_or(block.terminator_span)
+    }
+
+    // -- Control flow ---------------------------------------------------
+
+    fn check_structure(&mut self) {
+        let func = self.func;
+        for (b, block) in func.blocks.iter().enumerate() {
+            if self.labels.contains_key(block.label.as_str()) {
+                self.error("E0503", block.terminator_span,
+                    format!("block `{}` is defined more than once", block.label), "duplicate block");
+            } else {
+                self.labels.insert(&block.label, b);
+            }
+            if block.spans.len() != block.insts.len() {
+                self.error("E0503", block.terminator_span,
+                    format!("block `{}` has {} spans for {} instructions",
+                        block.label, block.spans.len(), block.insts.len()),
+                    "spans out of step");
+            }
+            let first_non_phi = block.insts.iter()
+                .position(|inst| !matches!(inst, IrInst::Phi { .. }))
+                .unwrap_or(block.insts.len());
+            if let Some(offset) = block.insts[first_non_phi..].iter().position(|i| matches!(i, IrInst::Phi { .. })) {
+                let span = self.span((b, first_non_phi + offset));
+                self.error("E0504", span,
+                    format!("phi in block `{}` follows other instructions", block.label),
+                    "phis must start their block");
+            }
+        }
+
+        for (b, block) in func.blocks.iter().enumerate() {
+            for target in block.terminator.successors() {
+                match self.labels.get(target) {
+                    Some(&s) => self.preds[s].push(b),
+                    None => self.error("E0503", block.terminator_span,
+                        format!("jump to unknown block `{}`", target), "no such block"),
+                }
+            }
+        }
+        if !self.preds[0].is_empty() {
+            self.error("E0503", func.blocks[0].terminator_span,
+                format!("entry block `{}` has predecessors", func.blocks[0].label), "entry block");
+        }
+    }
+
+    /// Iterative dominators of Cooper, Harvey and Kennedy, "A Simple, Fast
+    /// Dominance Algorithm"
+    fn compute_dominators(&mut self) {
+        // Reverse postorder of the reachable blocks
+        let mut postorder = Vec::new();
+        let mut stack = vec![(0, 0)];
+        self.reachable[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            let succs = self.func.blocks[block].terminator.successors();
+            if let Some(succ) = succs.get(next) {
+                stack.push((block, next + 1));
+                let s = self.labels[succ];
+                if !self.reachable[s] {
+                    self.reachable[s] = true;
+                    stack.push((s, 0));
+                }
+            } else {
+                postorder.push(block);
+            }
+        }
+        let mut order = vec![usize::MAX; self.func.blocks.len()];
+        for (i, &block) in postorder.iter().enumerate() {
+            order[block] = i;
+        }
+
+        let mut idom: Vec<Option<usize>> = vec![None; self.func.blocks.len()];
+        idom[0] = Some(0);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in postorder.iter().rev().skip(1) {
+                let mut new_idom: Option<usize> = None;
+                for &pred in &self.preds[block] {
+                    if idom[pred].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => pred,
+                        Some(mut a) => {
+                            let mut b = pred;
+                            while a != b {
+                                while order[a] < order[b] {
+                                    a = idom[a].unwrap();
+                                }
+                                while order[b] < order[a] {
+                                    b = idom[b].unwrap();
+                                }
+                            }
+                            a
+                        }
+                    });
+                }
+                if new_idom.is_some() && idom[block] != new_idom {
+                    idom[block] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        idom[0] = None;
+        self.idom = idom;
+    }
+
+    fn dominates(&self, (def_block, def_index): (usize, Option<usize>), (use_block, use_index): Position) -> bool {
+        if def_block == use_block {
+            return def_index.is_none_or(|def| def < use_index);
+        }
+        let mut block = use_block;
+        while let Some(parent) = self.idom[block] {
+            if parent == def_block {
+                return true;
+            }
+            block = parent;
+        }
+        false
+    }
+
+    // -- Values ---------------------------------------------------------
+
+    fn check_definitions(&mut self) {
+        let func = self.func;
+        for (name, _) in &func.params {
+            let value = format!("%{}", name);
+            if !func.value_types.contains_key(&value) {
+                self.error("E0506", func.blocks[0].terminator_span,
+                    format!("parameter `{}` has no type", value), "untyped parameter");
+            }
+            self.defs.insert(value, (0, None));
+        }
+
+        for (b, block) in func.blocks.iter().enumerate() {
+            for (i, inst) in block.insts.iter().enumerate() {
+                let Some(dest) = inst.dest() else {
+                    continue;
+                };
+                if !is_value(dest) || dest == UNDEF {
+                    self.error("E0501", self.span((b, i)),
+                        format!("`{}` cannot be defined", dest), "not an SSA value name");
+                } else if self.defs.insert(dest.to_string(), (b, Some(i))).is_some() {
+                    self.error("E0501", self.span((b, i)),
+                        format!("`{}` is defined more than once", dest), "redefined here");
+                }
+                if !func.value_types.contains_key(dest) {
+                    self.error("E0506", self.span((b, i)),
+                        format!("`{}` has no type", dest), "untyped value");
+                }
+            }
+        }
+    }
+
+    /// Check that `operand` is defined where it is used at `at`
+    fn check_use(&mut self, operand: &str, at: Position, span: Span) {
+        if operand == UNDEF {
+            return;
+        }
+        if !is_value(operand) {
+            if !self.module.functions.iter().any(|f| f.name == operand) {
+                self.error("E0505", span, format!("unknown function `{}`", operand), "not defined");
+            }
+            return;
+        }
+        match self.defs.get(operand) {
+            None => self.error("E0502", span, format!("`{}` is never defined", operand), "undefined value"),
+            Some(&def) if !self.dominates(def, at) => self.error("E0502", span,
+                format!("definition of `{}` does not dominate this use", operand), "used here"),
+            Some(_) => {}
+        }
+    }
+
+    fn check_uses(&mut self) {
+        let func = self.func;
+        for (b, block) in func.blocks.iter().enumerate() {
+            if !self.reachable[b] {
+                continue;
+            }
+            for (i, inst) in block.insts.iter().enumerate() {
+                let span = self.span((b, i));
+                match inst {
+                    IrInst::Phi { incoming, .. } => self.check_phi(b, incoming, span),
+                    IrInst::Call { func: callee, .. } => {
+                        self.check_use(callee, (b, i), span);
+                        // The callee of an indirect call is an operand too
+                        for operand in inst.operands().iter().skip(is_value(callee) as usize) {
+                            self.check_use(&operand.value, (b, i), span);
+                        }
+                    }
+                    _ => {
+                        for operand in inst.operands() {
+                            self.check_use(&operand.value, (b, i), span);
+                        }
+                    }
+                }
+            }
+            for operand in block.terminator.operands() {
+                self.check_use(&operand.value, (b, block.insts.len()), block.terminator_span);
+            }
+        }
+    }
+
+    fn check_phi(&mut self, block: usize, incoming: &[(String, IrOperand)], span: Span) {
+        let mut expected: Vec<&str> = self.preds[block].iter()
+            .map(|&p| self.func.blocks[p].label.as_str())
+            .collect();
+        let mut found: Vec<&str> = incoming.iter().map(|(label, _)| label.as_str()).collect();
+        expected.sort_unstable();
+        found.sort_unstable();
+        if expected != found {
+            self.error("E0504", span,
+                format!("phi inputs from [{}] do not match predecessors [{}]", found.join(", "), expected.join(", ")),
+                "one input per predecessor expected");
+        }
+        for (label, operand) in incoming {
+            if let Some(&pred) = self.labels.get(label.as_str()) {
+                let end = self.func.blocks[pred].insts.len();
+                self.check_use(&operand.value, (pred, end), span);
+            }
+        }
+    }
+
+    // -- Types ----------------------------------------------------------
+
+    fn type_of(&self, value: &str) -> Option<IrType> {
+        if value == UNDEF {
+            None
+        } else if is_value(value) {
+            self.func.value_type(value).cloned()
+        } else {
+            self.module.functions.iter().find(|f| f.name == value).map(IrFunction::ty)
+        }
+    }
+
+    /// Report `value` unless its type satisfies `ok`; untyped and unknown
+    /// values were reported already
+    fn expect(&mut self, value: &str, ok: impl Fn(&IrType) -> bool, expected: &str, span: Span) {
+        if let Some(ty) = self.type_of(value) {
+            if !ok(&ty) {
+                self.error("E0506", span,
+                    format!("`{}` has type `{}`, expected {}", value, ty, expected), "mismatched types");
+            }
+        }
+    }
+
+    fn expect_type(&mut self, value: &str, expected: &IrType, span: Span) {
+        self.expect(value, |ty| compatible(ty, expected), &format!("`{}`", expected), span);
+    }
+
+    fn check_types(&mut self) {
+        let func = self.func;
+        for (b, block) in func.blocks.iter().enumerate() {
+            for (i, inst) in block.insts.iter().enumerate() {
+                self.check_inst_types(inst, self.span((b, i)));
+            }
+            let span = block.terminator_span;
+            match &block.terminator {
+                IrTerminator::Branch { cond, .. } => self.expect_type(&cond.value, &IrType::Bool, span),
+                IrTerminator::Return { value: Some(value) } => {
+                    self.expect_type(&value.value, &func.returns, span);
+                }
+                IrTerminator::Return { value: None } if func.returns != IrType::Unit => {
+                    self.error("E0506", span,
+                        format!("return without a value from a function returning `{}`", func.returns),
+                        "missing return value");
+                }
+                IrTerminator::Return { value: None } | IrTerminator::Jump { .. } => {}
+            }
+        }
+    }
+
+    fn check_inst_types(&mut self, inst: &IrInst, span: Span) {
+        let dest_ty = inst.dest().and_then(|dest| self.type_of(dest));
+        let Some(dest_ty) = dest_ty else {
+            if let IrInst::Store { base, offset, value } = inst {
+                self.check_memory(&base.value, &offset.value, &value.value, span);
+            }
+            return;
+        };
+        let dest = inst.dest().unwrap_or_default();
+        let is_int = |ty: &IrType| matches!(ty, IrType::Int(_));
+        match inst {
+            IrInst::Const { value, .. } => {
+                let expected = match value {
+                    IrValue::Int(_) => ("an integer type", is_int(&dest_ty)),
+                    IrValue::Text(_) => ("`text`", dest_ty == IrType::Text),
+                    IrValue::Bool(_) => ("`bool`", dest_ty == IrType::Bool),
+                };
+                if !expected.1 {
+                    self.error("E0506", span,
+                        format!("`{}` has type `{}`, expected {} for its constant", dest, dest_ty, expected.0),
+                        "mismatched types");
+                }
+            }
+            IrInst::Call { func, args, .. } => {
+                let Some(callee_ty) = self.type_of(func) else {
+                    return;
+                };
+                let IrType::Function { params, returns, .. } = callee_ty else {
+                    self.error("E0506", span,
+                        format!("`{}` has type `{}`, expected a function", func, callee_ty), "not callable");
+                    return;
+                };
+                if params.len() != args.len() {
+                    let plural = if params.len() == 1 { "" } else { "s" };
+                    self.error("E0505", span,
+                        format!("`{}` takes {} argument{} but is called with {}", func, params.len(), plural, args.len()),
+                        "wrong number of arguments");
+                }
+                for (arg, param) in args.iter().zip(&params) {
+                    self.expect_type(&arg.value, param, span);
+                }
+                self.expect_type(dest, &returns, span);
+            }
+            IrInst::Alloc { .. } => {
+                self.expect(dest, |ty| matches!(ty, IrType::Array(_)), "an array type", span);
+            }
+            IrInst::Load { base, offset, .. } => {
+                self.check_memory(&base.value, &offset.value, dest, span);
+            }
+            IrInst::Store { base, offset, value } => {
+                self.check_memory(&base.value, &offset.value, &value.value, span);
+            }
+            IrInst::Borrow { source, .. } => self.expect_type(&source.value, &dest_ty, span),
+            IrInst::Binary { op, lhs, rhs, .. } => {
+                match op {
+                    BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Rem => {
+                        let text = *op == BinOp::Add && dest_ty == IrType::Text;
+                        if !is_int(&dest_ty) && !text {
+                            self.expect(dest, is_int, "an integer type", span);
+                        }
+                        self.expect_type(&lhs.value, &dest_ty, span);
+                        self.expect_type(&rhs.value, &dest_ty, span);
+                    }
+                    BinOp::Eq | BinOp::Ne | BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                        self.expect_type(dest, &IrType::Bool, span);
+                        if let Some(lhs_ty) = self.type_of(&lhs.value) {
+                            if matches!(op, BinOp::Eq | BinOp::Ne) {
+                                self.expect_type(&rhs.value, &lhs_ty, span);
+                            } else {
+                                self.expect(&lhs.value, is_int, "an integer type", span);
+                                if is_int(&lhs_ty) {
+                                    self.expect_type(&rhs.value, &lhs_ty, span);
+                                }
+                            }
+                        }
+                    }
+                    BinOp::And | BinOp::Or => {
+                        for value in [dest, &lhs.value, &rhs.value] {
+                            self.expect_type(value, &IrType::Bool, span);
+                        }
+                    }
+                }
+            }
+            IrInst::Unary { op, operand, .. } => {
+                if matches!(op, crate::ast::UnaryOp::Neg) {
+                    self.expect(dest, is_int, "an integer type", span);
+                } else {
+                    self.expect_type(dest, &IrType::Bool, span);
+                }
+                self.expect_type(&operand.value, &dest_ty, span);
+            }
+            IrInst::Phi { incoming, .. } => {
+                for (_, value) in incoming {
+                    self.expect_type(&value.value, &dest_ty, span);
+                }
+            }
+        }
+    }
+
+    /// Cells of an array hold its elements, except for the `i64` length
+    /// cell at offset 0
+    fn check_memory(&mut self, base: &str, offset: &str, cell: &str, span: Span) {
+        self.expect(offset, |ty| matches!(ty, IrType::Int(_)), "an integer type", span);
+        let Some(base_ty) = self.type_of(base) else {
+            return;
+        };
+        let IrType::Array(element) = &base_ty else {
+            self.error("E0506", span,
+                format!("`{}` has type `{}`, expected an array type", base, base_ty), "not memory");
+            return;
+        };
+        let expected = format!("`{}` or the `i64` length", element);
+        self.expect(cell, |ty| compatible(ty, element) || *ty == IrType::INT, &expected, span);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lower_module, parse_module, Parser};
+
+    fn verify(text: &str) -> Vec<Diagnostic> {
+        verify_module(&parse_module(text).unwrap_or_else(|e| panic!("{}", e)))
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().filter_map(|d| d.code.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_lowered_module_is_valid() {
+        let (module, errors) = Parser::new("module m
+enum Shape { Circle, Square }
+fn log(x: Int) !{io}
+fn area(s: Shape, n: Int) -> Int { match s { Shape::Circle => n * n * 3, _ => n * n } }
+fn apply(f: fn(Int) -> Int, x: Int) -> Int { f(x) }
+fn double(x: Int) -> Int { x * 2 }
+fn run(xs: Array<Int>) -> Array<Text> !{io} {
+    let total = 0;
+    for x in xs {
+        if x > 0 && x != 3 { total = total + apply(double, x); } else { log(x); }
+    }
+    let i = 0;
+    while i < len(xs) || !(total == 0) { i = i + 1; total = total - 1; }
+    [\"a\" + \"b\", \"c\"]
+}").parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let ir_module = lower_module(&module);
+        let diagnostics = verify_module(&ir_module);
+        assert!(diagnostics.is_empty(), "{:?}\n{}", diagnostics, print_module(&ir_module));
+    }
+
+    #[test]
+    fn test_redefinition_and_dominance() {
+        let diagnostics = verify("module m
+
+fn f(%c: bool) -> i64 {
+entry:
+  %0: i64 = const 1
+  %0: i64 = const 2
+  branch %c, then, merge
+then:
+  %1: i64 = const 3
+  jump merge
+merge:
+  %2: i64 = add %0, %1
+  return %2
+}
+");
+        assert_eq!(codes(&diagnostics), vec!["E0501", "E0502"]);
+        assert_eq!(diagnostics[0].primary_span().unwrap().line, 6);
+        assert_eq!(diagnostics[1].message, "invalid IR in `f`: definition of `%1` does not dominate this use");
+    }
+
+    #[test]
+    fn test_phi_inputs_and_undefined_values() {
+        let diagnostics = verify("module m
+
+fn f(%c: bool) -> i64 {
+entry:
+  %0: i64 = const 1
+  branch %c, left, join
+left:
+  jump join
+join:
+  %1: i64 = phi [entry: %0], [right: %0]
+  %2: i64 = add %1, %9
+  return %2
+}
+");
+        assert_eq!(codes(&diagnostics), vec!["E0504", "E0502"]);
+        assert_eq!(diagnostics[0].message,
+            "invalid IR in `f`: phi inputs from [entry, right] do not match predecessors [entry, left]");
+        assert_eq!(diagnostics[1].message, "invalid IR in `f`: `%9` is never defined");
+    }
+
+    #[test]
+    fn test_control_flow_structure() {
+        let diagnostics = verify("module m
+
+fn f() -> unit {
+entry:
+  jump nowhere
+entry:
+  %0: i64 = const 1
+  %1: i64 = phi [entry: %0]
+  jump entry
+}
+");
+        assert_eq!(codes(&diagnostics), vec!["E0503", "E0504", "E0503", "E0503"]);
+    }
+
+    #[test]
+    fn test_calls() {
+        let diagnostics = verify("module m
+
+fn g(%x: i64) -> i64
+
+fn f(%h: fn(i64) -> bool !{pure}) -> bool {
+entry:
+  %0: i64 = call g()
+  %1: i64 = call missing(%0)
+  %2: bool = call %h(%0)
+  %3: bool = call %0(%0)
+  return %2
+}
+");
+        assert_eq!(codes(&diagnostics), vec!["E0505", "E0505", "E0506"]);
+        assert_eq!(diagnostics[0].message, "invalid IR in `f`: unknown function `missing`");
+        assert_eq!(diagnostics[1].message, "invalid IR in `f`: `g` takes 1 argument but is called with 0");
+    }
+
+    #[test]
+    fn test_types() {
+        let diagnostics = verify("module m
+
+fn f(%xs: [text], %n: i32) -> i64 {
+entry:
+  %0: i64 = const true
+  %1: i64 = add %n, %0
+  %2: i64 = load &shared %xs, %0
+  %3: text = load &shared %xs, %0
+  %4 = not %3
+  store &unique %n, %0, %0
+  branch %0, done, done
+done:
+  %5: bool = phi [entry: %0], [entry: %0]
+  return %3
+}
+");
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(messages, vec![
+            "invalid IR in `f`: `%4` has no type",
+            "invalid IR in `f`: `%0` has type `i64`, expected `bool` for its constant",
+            "invalid IR in `f`: `%n` has type `i32`, expected `i64`",
+            "invalid IR in `f`: `%n` has type `i32`, expected an array type",
+            "invalid IR in `f`: `%0` has type `i64`, expected `bool`",
+            "invalid IR in `f`: `%0` has type `i64`, expected `bool`",
+            "invalid IR in `f`: `%0` has type `i64`, expected `bool`",
+            "invalid IR in `f`: `%3` has type `text`, expected `i64`",
+        ]);
+        assert!(codes(&diagnostics).iter().all(|code| *code == "E0506"));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid IR after test pass")]
+    #[cfg(debug_assertions)]
+    fn test_debug_verify_panics() {
+        let module = parse_module("module m\n\nfn f() -> i64 {\nentry:\n  return %0\n}\n").unwrap();
+        debug_verify(&module, "test pass");
+    }
+}
@@ -0,0 +1,1442 @@
+//! WebAssembly backend - Phase α
+//!
+//! Compiles an `IrModule` to a binary `.wasm` module, or to the same module
+//! in text format (`.wat`) for reading:
+//!
+//! * functions with a body become module functions exported under their
+//!   Forge name; bodiless functions become imports from a module named
+//!   after their effect (`io`, `net`, ...), or effects joined by `+`, so
+//!   the capability surface shows up in the import section;
+//! * every non-unit SSA value gets its own local. `i64` stays `i64`; the
+//!   narrower integers, `bool`, and anything living in memory (text,
+//!   arrays, function references) are `i32`;
+//! * control flow is a `loop` around a `br_table` on a block-index local,
+//!   so arbitrary CFGs need no structuring. Phis become local copies on
+//!   the incoming edge;
+//! * `Alloc` bumps a heap pointer through linear memory, which is exported
+//!   as `memory` for hosts, so no Forge function may be called `memory`.
+//!   Cells are 8 bytes. Text is a 4-byte length
+//!   followed by UTF-8 bytes, with literals in a data segment;
+//! * every function is also in the table at its function index, so
+//!   function values are table indices and indirect calls
+//!   `call_indirect`.
+//!
+//! Arithmetic wraps on overflow and traps on division by zero, where the
+//! interpreter reports errors. Nor are effects and budgets enforced at run
+//! time as the interpreter does: there are no meters, and imports are
+//! called directly, so a host wanting limits must meter its imports itself.
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::ast::{BinOp, Effect, EffectLattice, UnaryOp};
+use crate::diagnostics::Diagnostic;
+use crate::ir::*;
+use crate::ir_text::{binary_mnemonic, unary_mnemonic};
+use crate::span::Span;
+
+/// Bytes per memory cell
+const CELL_SIZE: i32 = 8;
+
+/// Where the data segment starts; 0 stays free so no object is at null
+const DATA_START: u32 = 8;
+
+/// Global holding the next free heap address
+const HEAP_GLOBAL: u32 = 0;
+
+/// Export name of the linear memory
+const MEMORY_EXPORT: &str = "memory";
+
+/// IR the backend cannot compile
+#[derive(Debug, Clone, PartialEq)]
+pub struct WasmError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl WasmError {
+    fn new(message: impl Into<String>, span: Span) -> Self {
+        WasmError { message: message.into(), span }
+    }
+
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.message.clone())
+            .with_code("E0701")
+            .with_primary(self.span, "")
+    }
+}
+
+impl fmt::Display for WasmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// WebAssembly value type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValType {
+    I32,
+    I64,
+}
+
+impl ValType {
+    fn name(self) -> &'static str {
+        match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+        }
+    }
+
+    fn encode(self) -> u8 {
+        match self {
+            ValType::I32 => 0x7F,
+            ValType::I64 => 0x7E,
+        }
+    }
+}
+
+/// Value type an IR type is represented by, `None` for unit
+pub fn val_type(ty: &IrType) -> Option<ValType> {
+    match ty {
+        IrType::Unit => None,
+        IrType::Int(IntWidth::I64) => Some(ValType::I64),
+        IrType::Int(_)
+        | IrType::Bool
+        | IrType::Text
+        | IrType::Array(_)
+        | IrType::Function { .. }
+        | IrType::Struct(_) => Some(ValType::I32),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FuncType {
+    params: Vec<ValType>,
+    results: Vec<ValType>,
+}
+
+impl FuncType {
+    fn of(params: &[IrType], returns: &IrType) -> Self {
+        FuncType {
+            params: params.iter().filter_map(val_type).collect(),
+            results: val_type(returns).into_iter().collect(),
+        }
+    }
+}
+
+/// Numeric instruction without immediates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumOp {
+    I32Eqz,
+    I32Eq,
+    I32Ne,
+    I32LtS,
+    I32GtS,
+    I32LeS,
+    I32LeU,
+    I32GeS,
+    I32GeU,
+    I64Eq,
+    I64Ne,
+    I64LtS,
+    I64GtS,
+    I64LeS,
+    I64GeS,
+    I32Add,
+    I32Sub,
+    I32Mul,
+    I32DivS,
+    I32RemS,
+    I32And,
+    I32Or,
+    I32Shl,
+    I32ShrU,
+    I64Add,
+    I64Sub,
+    I64Mul,
+    I64DivS,
+    I64RemS,
+    I32WrapI64,
+}
+
+impl NumOp {
+    fn opcode(self) -> u8 {
+        match self {
+            NumOp::I32Eqz => 0x45,
+            NumOp::I32Eq => 0x46,
+            NumOp::I32Ne => 0x47,
+            NumOp::I32LtS => 0x48,
+            NumOp::I32GtS => 0x4A,
+            NumOp::I32LeS => 0x4C,
+            NumOp::I32LeU => 0x4D,
+            NumOp::I32GeS => 0x4E,
+            NumOp::I32GeU => 0x4F,
+            NumOp::I64Eq => 0x51,
+            NumOp::I64Ne => 0x52,
+            NumOp::I64LtS => 0x53,
+            NumOp::I64GtS => 0x55,
+            NumOp::I64LeS => 0x57,
+            NumOp::I64GeS => 0x59,
+            NumOp::I32Add => 0x6A,
+            NumOp::I32Sub => 0x6B,
+            NumOp::I32Mul => 0x6C,
+            NumOp::I32DivS => 0x6D,
+            NumOp::I32RemS => 0x6F,
+            NumOp::I32And => 0x71,
+            NumOp::I32Or => 0x72,
+            NumOp::I32Shl => 0x74,
+            NumOp::I32ShrU => 0x76,
+            NumOp::I64Add => 0x7C,
+            NumOp::I64Sub => 0x7D,
+            NumOp::I64Mul => 0x7E,
+            NumOp::I64DivS => 0x7F,
+            NumOp::I64RemS => 0x81,
+            NumOp::I32WrapI64 => 0xA7,
+        }
+    }
+    fn name(self) -> &'static str {
+        match self {
+            NumOp::I32Eqz => "i32.eqz",
+            NumOp::I32Eq => "i32.eq",
+            NumOp::I32Ne => "i32.ne",
+            NumOp::I32LtS => "i32.lt_s",
+            NumOp::I32GtS => "i32.gt_s",
+            NumOp::I32LeS => "i32.le_s",
+            NumOp::I32LeU => "i32.le_u",
+            NumOp::I32GeS => "i32.ge_s",
+            NumOp::I32GeU => "i32.ge_u",
+            NumOp::I64Eq => "i64.eq",
+            NumOp::I64Ne => "i64.ne",
+            NumOp::I64LtS => "i64.lt_s",
+            NumOp::I64GtS => "i64.gt_s",
+            NumOp::I64LeS => "i64.le_s",
+            NumOp::I64GeS => "i64.ge_s",
+            NumOp::I32Add => "i32.add",
+            NumOp::I32Sub => "i32.sub",
+            NumOp::I32Mul => "i32.mul",
+            NumOp::I32DivS => "i32.div_s",
+            NumOp::I32RemS => "i32.rem_s",
+            NumOp::I32And => "i32.and",
+            NumOp::I32Or => "i32.or",
+            NumOp::I32Shl => "i32.shl",
+            NumOp::I32ShrU => "i32.shr_u",
+            NumOp::I64Add => "i64.add",
+            NumOp::I64Sub => "i64.sub",
+            NumOp::I64Mul => "i64.mul",
+            NumOp::I64DivS => "i64.div_s",
+            NumOp::I64RemS => "i64.rem_s",
+            NumOp::I32WrapI64 => "i32.wrap_i64",
+        }
+    }
+}
+
+/// Memory access: `(load|store, width)`; offsets are in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MemOp {
+    I32Load,
+    I64Load,
+    I32Load8U,
+    I32Store,
+    I64Store,
+}
+
+impl MemOp {
+    fn name(self) -> &'static str {
+        match self {
+            MemOp::I32Load => "i32.load",
+            MemOp::I64Load => "i64.load",
+            MemOp::I32Load8U => "i32.load8_u",
+            MemOp::I32Store => "i32.store",
+            MemOp::I64Store => "i64.store",
+        }
+    }
+
+    fn opcode(self) -> u8 {
+        match self {
+            MemOp::I32Load => 0x28,
+            MemOp::I64Load => 0x29,
+            MemOp::I32Load8U => 0x2D,
+            MemOp::I32Store => 0x36,
+            MemOp::I64Store => 0x37,
+        }
+    }
+
+    /// log2 of the natural alignment
+    fn align(self) -> u32 {
+        match self {
+            MemOp::I32Load8U => 0,
+            MemOp::I32Load | MemOp::I32Store => 2,
+            MemOp::I64Load | MemOp::I64Store => 3,
+        }
+    }
+}
+
+/// Instruction as generated, before encoding
+#[derive(Debug, Clone, PartialEq)]
+enum Instr {
+    Unreachable,
+    /// Blocks never produce values here
+    Block,
+    Loop,
+    If,
+    Else,
+    End,
+    Br(u32),
+    BrIf(u32),
+    BrTable(Vec<u32>, u32),
+    Return,
+    Call(u32),
+    /// Type index; the table is always table 0
+    CallIndirect(u32),
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    GlobalGet(u32),
+    GlobalSet(u32),
+    Mem(MemOp, u32),
+    MemorySize,
+    MemoryGrow,
+    MemoryCopy,
+    I32Const(i32),
+    I64Const(i64),
+    Num(NumOp),
+}
+
+/// Function with a body, ready to encode
+struct Body {
+    name: String,
+    type_index: u32,
+    /// Locals beyond the parameters
+    locals: Vec<ValType>,
+    /// Names of the parameters and then the locals, if they have any
+    local_names: Vec<String>,
+    code: Vec<Instr>,
+    /// Comments for the text format, placed before the instruction at the
+    /// given index
+    notes: Vec<(usize, String)>,
+}
+
+/// Module as compiled, before it is written out in either format
+struct Compiled {
+    name: String,
+    types: Vec<FuncType>,
+    /// `(module, name, type index)`
+    imports: Vec<(String, String, u32)>,
+    bodies: Vec<Body>,
+    /// Name of every function by function index
+    function_names: Vec<String>,
+    /// Function indices of the Forge functions with bodies
+    exports: Vec<u32>,
+    /// Forge functions, all in the table at their function index
+    table_size: u32,
+    heap_start: u32,
+    data: Vec<u8>,
+}
+
+/// Helpers every module carries, placed after the Forge functions. Their
+/// names start with `rt:`, which no Forge identifier can
+#[derive(Debug, Clone, Copy)]
+enum Helper {
+    /// `(bytes: i32) -> i32`: bump-allocate, growing memory as needed
+    Alloc,
+    /// `(a: i32, b: i32) -> i32`: concatenate two texts
+    Concat,
+    /// `(a: i32, b: i32) -> i32`: compare two texts byte by byte
+    TextEq,
+}
+
+const HELPERS: [Helper; 3] = [Helper::Alloc, Helper::Concat, Helper::TextEq];
+
+/// Compile a module to WebAssembly binary format
+pub fn emit_wasm(module: &IrModule) -> Result<Vec<u8>, WasmError> {
+    Ok(ModuleCompiler::new(module).compile()?.encode())
+}
+
+/// Compile a module to WebAssembly text format. Comments name the Forge
+/// function, block and source line each stretch of code comes from.
+pub fn emit_wat(module: &IrModule) -> Result<String, WasmError> {
+    Ok(ModuleCompiler::new(module).compile()?.to_wat())
+}
+
+struct ModuleCompiler<'a> {
+    module: &'a IrModule,
+    types: Vec<FuncType>,
+    /// Function index of each Forge function
+    functions: HashMap<&'a str, u32>,
+    imports: Vec<&'a IrFunction>,
+    defined: Vec<&'a IrFunction>,
+    /// Address of each text literal
+    texts: HashMap<String, u32>,
+    data: Vec<u8>,
+}
+
+impl<'a> ModuleCompiler<'a> {
+    fn new(module: &'a IrModule) -> Self {
+        let (imports, defined): (Vec<_>, Vec<_>) = module.functions.iter().partition(|f| f.blocks.is_empty());
+        let functions = imports.iter().chain(&defined).enumerate()
+            .map(|(i, f)| (f.name.as_str(), i as u32))
+            .collect();
+        ModuleCompiler {
+            module,
+            types: Vec::new(),
+            functions,
+            imports,
+            defined,
+            texts: HashMap::new(),
+            data: Vec::new(),
+        }
+    }
+
+    fn type_index(&mut self, ty: FuncType) -> u32 {
+        match self.types.iter().position(|t| *t == ty) {
+            Some(i) => i as u32,
+            None => {
+                self.types.push(ty);
+                self.types.len() as u32 - 1
+            }
+        }
+    }
+
+    fn helper_index(&self, helper: Helper) -> u32 {
+        (self.imports.len() + self.defined.len()) as u32 + helper as u32
+    }
+
+    /// Address of a text literal, added to the data segment on first use
+    fn text(&mut self, text: &str) -> u32 {
+        if let Some(&address) = self.texts.get(text) {
+            return address;
+        }
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+        let address = DATA_START + self.data.len() as u32;
+        self.data.extend_from_slice(&(text.len() as u32).to_le_bytes());
+        self.data.extend_from_slice(text.as_bytes());
+        self.texts.insert(text.to_string(), address);
+        address
+    }
+
+    fn compile(mut self) -> Result<Compiled, WasmError> {
+        let lattice = self.module.effect_lattice();
+        let mut imports = Vec::new();
+        for func in self.imports.clone() {
+            let params: Vec<IrType> = func.params.iter().map(|(_, ty)| ty.clone()).collect();
+            let type_index = self.type_index(FuncType::of(&params, &func.returns));
+            imports.push((import_module(&lattice, func), func.name.clone(), type_index));
+        }
+        // Exports share one namespace with the memory
+        if let Some(func) = self.defined.iter().find(|f| f.name == MEMORY_EXPORT) {
+            return Err(WasmError::new(
+                format!("`{}` cannot be exported: hosts find the module's memory under that name", func.name),
+                func.span,
+            ));
+        }
+        let mut bodies = Vec::new();
+        for func in self.defined.clone() {
+            bodies.push(FunctionCompiler::new(&mut self, func).compile()?);
+        }
+        for helper in HELPERS {
+            bodies.push(self.helper(helper));
+        }
+        let function_names = self.imports.iter().map(|f| f.name.clone())
+            .chain(bodies.iter().map(|body| body.name.clone()))
+            .collect();
+        Ok(Compiled {
+            name: self.module.name.clone(),
+            types: self.types,
+            imports,
+            bodies,
+            function_names,
+            exports: self.defined.iter().map(|f| self.functions[f.name.as_str()]).collect(),
+            table_size: (self.imports.len() + self.defined.len()) as u32,
+            heap_start: (DATA_START + self.data.len() as u32).next_multiple_of(CELL_SIZE as u32),
+            data: self.data,
+        })
+    }
+
+    fn helper(&mut self, helper: Helper) -> Body {
+        use Instr::*;
+        use NumOp::*;
+        let i32 = ValType::I32;
+        let (name, params, locals, code) = match helper {
+            // Locals: 0 bytes, 1 result
+            Helper::Alloc => ("rt:alloc", vec![i32], vec![i32], vec![
+                    GlobalGet(HEAP_GLOBAL), LocalSet(1),
+                    GlobalGet(HEAP_GLOBAL), LocalGet(0), Num(I32Add),
+                    I32Const(CELL_SIZE - 1), Num(I32Add), I32Const(-CELL_SIZE), Num(I32And),
+                    GlobalSet(HEAP_GLOBAL),
+                    Block,
+                    GlobalGet(HEAP_GLOBAL), MemorySize, I32Const(16), Num(I32Shl), Num(I32LeU), BrIf(0),
+                    GlobalGet(HEAP_GLOBAL), MemorySize, I32Const(16), Num(I32Shl), Num(I32Sub),
+                    I32Const(65535), Num(I32Add), I32Const(16), Num(I32ShrU),
+                    MemoryGrow, I32Const(-1), Num(I32Ne), BrIf(0),
+                    Unreachable,
+                    End,
+                    LocalGet(1),
+                ]),
+            // Locals: 0 a, 1 b, 2 len(a), 3 len(b), 4 result
+            Helper::Concat => ("rt:concat", vec![i32, i32], vec![i32, i32, i32], vec![
+                    LocalGet(0), Mem(MemOp::I32Load, 0), LocalSet(2),
+                    LocalGet(1), Mem(MemOp::I32Load, 0), LocalSet(3),
+                    I32Const(4), LocalGet(2), Num(I32Add), LocalGet(3), Num(I32Add),
+                    Call(self.helper_index(Helper::Alloc)), LocalSet(4),
+                    LocalGet(4), LocalGet(2), LocalGet(3), Num(I32Add), Mem(MemOp::I32Store, 0),
+                    LocalGet(4), I32Const(4), Num(I32Add),
+                    LocalGet(0), I32Const(4), Num(I32Add), LocalGet(2), MemoryCopy,
+                    LocalGet(4), I32Const(4), Num(I32Add), LocalGet(2), Num(I32Add),
+                    LocalGet(1), I32Const(4), Num(I32Add), LocalGet(3), MemoryCopy,
+                    LocalGet(4),
+                ]),
+            // Locals: 0 a, 1 b, 2 length, 3 index
+            Helper::TextEq => ("rt:text_eq", vec![i32, i32], vec![i32, i32], vec![
+                    LocalGet(0), Mem(MemOp::I32Load, 0), LocalTee(2),
+                    LocalGet(1), Mem(MemOp::I32Load, 0), Num(I32Ne),
+                    If, I32Const(0), Return, End,
+                    Block, Loop,
+                    LocalGet(3), LocalGet(2), Num(I32GeU), BrIf(1),
+                    LocalGet(0), LocalGet(3), Num(I32Add), Mem(MemOp::I32Load8U, 4),
+                    LocalGet(1), LocalGet(3), Num(I32Add), Mem(MemOp::I32Load8U, 4),
+                    Num(I32Ne), If, I32Const(0), Return, End,
+                    LocalGet(3), I32Const(1), Num(I32Add), LocalSet(3),
+                    Br(0),
+                    End, End,
+                    I32Const(1),
+                ]),
+        };
+        Body {
+            name: name.to_string(),
+            type_index: self.type_index(FuncType { params, results: vec![i32] }),
+            locals,
+            local_names: Vec::new(),
+            code,
+            notes: vec![(0, "runtime helper".to_string())],
+        }
+    }
+}
+
+/// Host functions are imported from a module named after the effect they
+/// need, as the module's effect hierarchy folds it together
+fn import_module(lattice: &EffectLattice, func: &IrFunction) -> String {
+    let effect = func.capability.iter()
+        .flat_map(|cap| &cap.effects)
+        .fold(Effect::pure(), |acc, effect| acc.union(effect));
+    let effect = lattice.normalize(&effect);
+    if effect.is_pure() {
+        return effect.to_string();
+    }
+    effect.names().collect::<Vec<_>>().join("+")
+}
+
+struct FunctionCompiler<'m, 'a> {
+    module: &'m mut ModuleCompiler<'a>,
+    func: &'a IrFunction,
+    /// Local index of each non-unit value
+    locals: HashMap<String, u32>,
+    local_types: Vec<ValType>,
+    local_names: Vec<String>,
+    blocks: HashMap<&'a str, u32>,
+    /// Local holding the index of the block to run next
+    next_block: u32,
+    code: Vec<Instr>,
+    notes: Vec<(usize, String)>,
+    /// Source line of the code emitted last, to note where it changes
+    line: u32,
+}
+
+impl<'m, 'a> FunctionCompiler<'m, 'a> {
+    fn new(module: &'m mut ModuleCompiler<'a>, func: &'a IrFunction) -> Self {
+        let mut locals = HashMap::new();
+        let mut local_types = Vec::new();
+        let mut local_names = Vec::new();
+        let params = func.params.iter().map(|(param, ty)| (format!("%{}", param), ty));
+        let dests = func.blocks.iter().flat_map(|b| &b.insts).filter_map(|inst| {
+            let dest = inst.dest()?;
+            Some((dest.to_string(), func.value_type(dest)?))
+        });
+        for (value, ty) in params.chain(dests) {
+            if let Some(ty) = val_type(ty) {
+                locals.insert(value.clone(), local_types.len() as u32);
+                local_types.push(ty);
+                local_names.push(value);
+            }
+        }
+        let next_block = local_types.len() as u32;
+        local_types.push(ValType::I32);
+        local_names.push("block".to_string());
+        let blocks = func.blocks.iter().enumerate().map(|(i, b)| (b.label.as_str(), i as u32)).collect();
+        FunctionCompiler {
+            module,
+            func,
+            locals,
+            local_types,
+            local_names,
+            blocks,
+            next_block,
+            code: Vec::new(),
+            notes: Vec::new(),
+            line: 0,
+        }
+    }
+
+    fn compile(mut self) -> Result<Body, WasmError> {
+        let func = self.func;
+        let params: Vec<IrType> = func.params.iter().map(|(_, ty)| ty.clone()).collect();
+        let type_index = self.module.type_index(FuncType::of(&params, &func.returns));
+
+        // Block i's code follows the end of the i-th innermost `block`, so
+        // `br_table` reaches it at depth i and the dispatch loop is at
+        // depth `n - 1 - i` from inside it
+        let n = func.blocks.len() as u32;
+        let note = match func.span.line {
+            0 => format!("fn {}", func.name),
+            line => format!("fn {} at line {}", func.name, line),
+        };
+        self.notes.push((0, note));
+        self.code.push(Instr::Loop);
+        for _ in 0..n {
+            self.code.push(Instr::Block);
+        }
+        self.code.push(Instr::LocalGet(self.next_block));
+        self.code.push(Instr::BrTable((0..n).collect(), n - 1));
+        for (i, block) in func.blocks.iter().enumerate() {
+            self.code.push(Instr::End);
+            self.notes.push((self.code.len(), format!("block {}", block.label)));
+            self.line = 0;
+            for (inst, &span) in block.insts.iter().zip(&block.spans) {
+                self.note_line(span);
+                self.inst(inst, span)?;
+            }
+            self.note_line(block.terminator_span);
+            self.terminator(block, n - 1 - i as u32)?;
+        }
+        self.code.push(Instr::End);
+        self.code.push(Instr::Unreachable);
+
+        let params = func.params.iter().filter(|(_, ty)| val_type(ty).is_some()).count();
+        Ok(Body {
+            name: func.name.clone(),
+            type_index,
+            locals: self.local_types.split_off(params),
+            local_names: self.local_names,
+            code: self.code,
+            notes: self.notes,
+        })
+    }
+
+    fn note_line(&mut self, span: Span) {
+        if span.line > 0 && span.line != self.line {
+            self.line = span.line;
+            self.notes.push((self.code.len(), format!("line {}", span.line)));
+        }
+    }
+
+    fn type_of(&self, value: &str, span: Span) -> Result<IrType, WasmError> {
+        if value == UNDEF {
+            return Err(WasmError::new(
+                format!("cannot compile `{}`: it uses an undefined value", self.func.name), span));
+        }
+        if is_value(value) {
+            return self.func.value_type(value).cloned().ok_or_else(|| WasmError::new(
+                format!("`{}` in `{}` has no type", value, self.func.name), span));
+        }
+        match self.module.module.functions.iter().find(|f| f.name == value) {
+            Some(callee) => Ok(callee.ty()),
+            None => Err(WasmError::new(format!("unknown function `{}`", value), span)),
+        }
+    }
+
+    /// Push an operand; unit values push nothing
+    fn push(&mut self, operand: &IrOperand, span: Span) -> Result<(), WasmError> {
+        let value = operand.value.as_str();
+        self.type_of(value, span)?;
+        if is_value(value) {
+            if let Some(&local) = self.locals.get(value) {
+                self.code.push(Instr::LocalGet(local));
+            }
+        } else {
+            self.code.push(Instr::I32Const(self.module.functions[value] as i32));
+        }
+        Ok(())
+    }
+
+    /// Pop the top of the stack into `dest`, if it has a local
+    fn set(&mut self, dest: &str) {
+        if let Some(&local) = self.locals.get(dest) {
+            self.code.push(Instr::LocalSet(local));
+        }
+    }
+
+    /// Push the byte address of cell `base + offset`
+    fn address(&mut self, base: &IrOperand, offset: &IrOperand, span: Span) -> Result<(), WasmError> {
+        self.push(base, span)?;
+        self.push(offset, span)?;
+        if val_type(&self.type_of(&offset.value, span)?) == Some(ValType::I64) {
+            self.code.push(Instr::Num(NumOp::I32WrapI64));
+        }
+        self.code.push(Instr::I32Const(CELL_SIZE.trailing_zeros() as i32));
+        self.code.push(Instr::Num(NumOp::I32Shl));
+        self.code.push(Instr::Num(NumOp::I32Add));
+        Ok(())
+    }
+
+    fn inst(&mut self, inst: &IrInst, span: Span) -> Result<(), WasmError> {
+        match inst {
+            IrInst::Const { dest, value } => {
+                let instr = match value {
+                    IrValue::Int(n) => match self.func.value_type(dest).and_then(val_type) {
+                        Some(ValType::I64) => Instr::I64Const(*n),
+                        _ => Instr::I32Const(*n as i32),
+                    },
+                    IrValue::Bool(b) => Instr::I32Const(*b as i32),
+                    IrValue::Text(text) => Instr::I32Const(self.module.text(text) as i32),
+                };
+                self.code.push(instr);
+                self.set(dest);
+            }
+            IrInst::Call { dest, func, args, .. } => {
+                for arg in args {
+                    self.push(arg, span)?;
+                }
+                if is_value(func) {
+                    let (params, returns) = match self.type_of(func, span)? {
+                        IrType::Function { params, returns, .. } => (params, returns),
+                        other => return Err(WasmError::new(
+                            format!("cannot call `{}` of type `{}`", func, other), span)),
+                    };
+                    self.push(&IrOperand::copy(func.clone()), span)?;
+                    let type_index = self.module.type_index(FuncType::of(&params, &returns));
+                    self.code.push(Instr::CallIndirect(type_index));
+                } else {
+                    self.type_of(func, span)?;
+                    self.code.push(Instr::Call(self.module.functions[func.as_str()]));
+                }
+                self.set(dest);
+            }
+            IrInst::Alloc { dest, size } => {
+                self.code.push(Instr::I32Const(*size as i32 * CELL_SIZE));
+                self.code.push(Instr::Call(self.module.helper_index(Helper::Alloc)));
+                self.set(dest);
+            }
+            IrInst::Load { dest, base, offset } => {
+                let load = match self.func.value_type(dest).and_then(val_type) {
+                    Some(ValType::I64) => MemOp::I64Load,
+                    Some(ValType::I32) => MemOp::I32Load,
+                    None => return Ok(()),
+                };
+                self.address(base, offset, span)?;
+                self.code.push(Instr::Mem(load, 0));
+                self.set(dest);
+            }
+            IrInst::Store { base, offset, value } => {
+                let store = match val_type(&self.type_of(&value.value, span)?) {
+                    Some(ValType::I64) => MemOp::I64Store,
+                    Some(ValType::I32) => MemOp::I32Store,
+                    None => return Ok(()),
+                };
+                self.address(base, offset, span)?;
+                self.push(value, span)?;
+                self.code.push(Instr::Mem(store, 0));
+            }
+            IrInst::Borrow { dest, source } => {
+                self.push(source, span)?;
+                self.set(dest);
+            }
+            IrInst::Binary { dest, op, lhs, rhs } => {
+                let ty = self.type_of(&lhs.value, span)?;
+                self.push(lhs, span)?;
+                self.push(rhs, span)?;
+                self.binary(*op, &ty, span)?;
+                self.set(dest);
+            }
+            IrInst::Unary { dest, op, operand } => {
+                let ty = self.type_of(&operand.value, span)?;
+                match (op, val_type(&ty)) {
+                    (UnaryOp::Neg, Some(ValType::I64)) => {
+                        self.code.push(Instr::I64Const(0));
+                        self.push(operand, span)?;
+                        self.code.push(Instr::Num(NumOp::I64Sub));
+                    }
+                    (UnaryOp::Neg, Some(ValType::I32)) => {
+                        self.code.push(Instr::I32Const(0));
+                        self.push(operand, span)?;
+                        self.code.push(Instr::Num(NumOp::I32Sub));
+                    }
+                    (UnaryOp::Not, Some(ValType::I32)) => {
+                        self.push(operand, span)?;
+                        self.code.push(Instr::Num(NumOp::I32Eqz));
+                    }
+                    _ => return Err(WasmError::new(
+                        format!("cannot apply `{}` to `{}`", unary_mnemonic(*op), ty), span)),
+                }
+                self.set(dest);
+            }
+            // Set on the edges into the block
+            IrInst::Phi { .. } => {}
+        }
+        Ok(())
+    }
+
+    fn binary(&mut self, op: BinOp, ty: &IrType, span: Span) -> Result<(), WasmError> {
+        use NumOp::*;
+        let num = match (ty, op) {
+            (IrType::Text, BinOp::Add) => {
+                self.code.push(Instr::Call(self.module.helper_index(Helper::Concat)));
+                return Ok(());
+            }
+            (IrType::Text, BinOp::Eq | BinOp::Ne) => {
+                self.code.push(Instr::Call(self.module.helper_index(Helper::TextEq)));
+                if op == BinOp::Ne {
+                    self.code.push(Instr::Num(I32Eqz));
+                }
+                return Ok(());
+            }
+            (IrType::Int(IntWidth::I64), _) => match op {
+                BinOp::Add => I64Add,
+                BinOp::Sub => I64Sub,
+                BinOp::Mul => I64Mul,
+                BinOp::Div => I64DivS,
+                BinOp::Rem => I64RemS,
+                BinOp::Eq => I64Eq,
+                BinOp::Ne => I64Ne,
+                BinOp::Lt => I64LtS,
+                BinOp::Le => I64LeS,
+                BinOp::Gt => I64GtS,
+                BinOp::Ge => I64GeS,
+                BinOp::And | BinOp::Or => return Err(WasmError::new(
+                    format!("cannot apply `{}` to `{}`", binary_mnemonic(op), ty), span)),
+            },
+            (IrType::Int(_) | IrType::Bool, _) => match op {
+                BinOp::Add => I32Add,
+                BinOp::Sub => I32Sub,
+                BinOp::Mul => I32Mul,
+                BinOp::Div => I32DivS,
+                BinOp::Rem => I32RemS,
+                BinOp::Eq => I32Eq,
+                BinOp::Ne => I32Ne,
+                BinOp::Lt => I32LtS,
+                BinOp::Le => I32LeS,
+                BinOp::Gt => I32GtS,
+                BinOp::Ge => I32GeS,
+                BinOp::And => I32And,
+                BinOp::Or => I32Or,
+            },
+            // Anything else in memory compares by address
+            (_, BinOp::Eq) if val_type(ty).is_some() => I32Eq,
+            (_, BinOp::Ne) if val_type(ty).is_some() => I32Ne,
+            _ => return Err(WasmError::new(format!("cannot apply `{}` to `{}`", binary_mnemonic(op), ty), span)),
+        };
+        self.code.push(Instr::Num(num));
+        Ok(())
+    }
+
+    /// Copy the phi inputs for the edge `from -> to`. Everything is read
+    /// before anything is written, since phis may read each other.
+    fn edge(&mut self, from: &str, to: &str, span: Span) -> Result<(), WasmError> {
+        let target = &self.func.blocks[self.block_index(to, span)? as usize];
+        let mut dests = Vec::new();
+        for inst in &target.insts {
+            if let IrInst::Phi { dest, incoming } = inst {
+                if let Some((_, operand)) = incoming.iter().find(|(label, _)| label == from) {
+                    if self.locals.contains_key(dest.as_str()) {
+                        self.push(operand, span)?;
+                        dests.push(dest.as_str());
+                    }
+                }
+            }
+        }
+        for dest in dests.into_iter().rev() {
+            self.set(dest);
+        }
+        let index = self.block_index(to, span)?;
+        self.code.push(Instr::I32Const(index as i32));
+        self.code.push(Instr::LocalSet(self.next_block));
+        Ok(())
+    }
+
+    fn block_index(&self, label: &str, span: Span) -> Result<u32, WasmError> {
+        self.blocks.get(label).copied().ok_or_else(|| WasmError::new(
+            format!("unknown block `{}` in `{}`", label, self.func.name), span))
+    }
+
+    /// `depth` is the branch depth of the dispatch loop
+    fn terminator(&mut self, block: &IrBlock, depth: u32) -> Result<(), WasmError> {
+        let span = block.terminator_span;
+        match &block.terminator {
+            IrTerminator::Jump { target } => {
+                self.edge(&block.label, target, span)?;
+                self.code.push(Instr::Br(depth));
+            }
+            IrTerminator::Branch { cond, then_target, else_target } => {
+                self.push(cond, span)?;
+                self.code.push(Instr::If);
+                self.edge(&block.label, then_target, span)?;
+                self.code.push(Instr::Else);
+                self.edge(&block.label, else_target, span)?;
+                self.code.push(Instr::End);
+                self.code.push(Instr::Br(depth));
+            }
+            IrTerminator::Return { value } => {
+                if let Some(value) = value {
+                    self.push(value, span)?;
+                }
+                self.code.push(Instr::Return);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Compiled {
+    fn encode(&self) -> Vec<u8> {
+        let mut out = b"\0asm".to_vec();
+        out.extend_from_slice(&1u32.to_le_bytes());
+
+        let mut types = Vec::new();
+        vec_len(&mut types, self.types.len());
+        for ty in &self.types {
+            types.push(0x60);
+            vec_len(&mut types, ty.params.len());
+            types.extend(ty.params.iter().map(|t| t.encode()));
+            vec_len(&mut types, ty.results.len());
+            types.extend(ty.results.iter().map(|t| t.encode()));
+        }
+        section(&mut out, 1, types);
+
+        let mut imports = Vec::new();
+        vec_len(&mut imports, self.imports.len());
+        for (module, func, type_index) in &self.imports {
+            name(&mut imports, module);
+            name(&mut imports, func);
+            imports.push(0x00);
+            uleb(&mut imports, *type_index as u64);
+        }
+        section(&mut out, 2, imports);
+
+        let mut functions = Vec::new();
+        vec_len(&mut functions, self.bodies.len());
+        for body in &self.bodies {
+            uleb(&mut functions, body.type_index as u64);
+        }
+        section(&mut out, 3, functions);
+
+        let mut table = vec![1, 0x70, 0x01];
+        uleb(&mut table, self.table_size as u64);
+        uleb(&mut table, self.table_size as u64);
+        section(&mut out, 4, table);
+
+        let mut memory = vec![1, 0x00];
+        uleb(&mut memory, self.pages() as u64);
+        section(&mut out, 5, memory);
+
+        let mut globals = vec![1, ValType::I32.encode(), 0x01];
+        encode_instr(&mut globals, &Instr::I32Const(self.heap_start as i32));
+        globals.push(0x0B);
+        section(&mut out, 6, globals);
+
+        let mut exports = Vec::new();
+        vec_len(&mut exports, self.exports.len() + 1);
+        for &index in &self.exports {
+            name(&mut exports, &self.function_names[index as usize]);
+            exports.push(0x00);
+            uleb(&mut exports, index as u64);
+        }
+        name(&mut exports, MEMORY_EXPORT);
+        exports.push(0x02);
+        uleb(&mut exports, 0);
+        section(&mut out, 7, exports);
+
+        let mut elements = vec![1, 0x00];
+        encode_instr(&mut elements, &Instr::I32Const(0));
+        elements.push(0x0B);
+        vec_len(&mut elements, self.table_size as usize);
+        for i in 0..self.table_size {
+            uleb(&mut elements, i as u64);
+        }
+        section(&mut out, 9, elements);
+
+        let mut code = Vec::new();
+        vec_len(&mut code, self.bodies.len());
+        for body in &self.bodies {
+            let mut bytes = Vec::new();
+            let mut runs: Vec<(u32, ValType)> = Vec::new();
+            for &ty in &body.locals {
+                match runs.last_mut() {
+                    Some((count, last)) if *last == ty => *count += 1,
+                    _ => runs.push((1, ty)),
+                }
+            }
+            vec_len(&mut bytes, runs.len());
+            for (count, ty) in runs {
+                uleb(&mut bytes, count as u64);
+                bytes.push(ty.encode());
+            }
+            for instr in &body.code {
+                encode_instr(&mut bytes, instr);
+            }
+            bytes.push(0x0B);
+            vec_len(&mut code, bytes.len());
+            code.extend(bytes);
+        }
+        section(&mut out, 10, code);
+
+        if !self.data.is_empty() {
+            let mut data = vec![1, 0x00];
+            encode_instr(&mut data, &Instr::I32Const(DATA_START as i32));
+            data.push(0x0B);
+            vec_len(&mut data, self.data.len());
+            data.extend_from_slice(&self.data);
+            section(&mut out, 11, data);
+        }
+        out
+    }
+
+    /// Initial memory size in 64 KiB pages
+    fn pages(&self) -> u32 {
+        self.heap_start.div_ceil(65536).max(1)
+    }
+
+    fn to_wat(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "(module {}", id(&self.name)).unwrap();
+        for (i, ty) in self.types.iter().enumerate() {
+            writeln!(out, "  (type (;{};) (func{}))", i, signature(ty, &[])).unwrap();
+        }
+        for (module, func, type_index) in &self.imports {
+            writeln!(out, "  (import {} {} (func {} (type {})))",
+                quote(module.as_bytes()), quote(func.as_bytes()), id(func), type_index).unwrap();
+        }
+        writeln!(out, "  (table {} {} funcref)", self.table_size, self.table_size).unwrap();
+        writeln!(out, "  (memory {})", self.pages()).unwrap();
+        writeln!(out, "  (global $heap (mut i32) (i32.const {}))", self.heap_start).unwrap();
+        for &index in &self.exports {
+            let func = &self.function_names[index as usize];
+            writeln!(out, "  (export {} (func {}))", quote(func.as_bytes()), id(func)).unwrap();
+        }
+        writeln!(out, "  (export {} (memory 0))", quote(MEMORY_EXPORT.as_bytes())).unwrap();
+        let table: Vec<String> = self.function_names[..self.table_size as usize].iter()
+            .map(|func| id(func))
+            .collect();
+        writeln!(out, "  (elem (i32.const 0) func {})", table.join(" ")).unwrap();
+        for body in &self.bodies {
+            self.print_body(&mut out, body);
+        }
+        if !self.data.is_empty() {
+            writeln!(out, "  (data (i32.const {}) {})", DATA_START, quote(&self.data)).unwrap();
+        }
+        out.push_str(")\n");
+        out
+    }
+
+    fn print_body(&self, out: &mut String, body: &Body) {
+        let ty = &self.types[body.type_index as usize];
+        let names = &body.local_names;
+        let mut notes = body.notes.iter().peekable();
+        while let Some((_, note)) = notes.next_if(|(at, _)| *at == 0) {
+            writeln!(out, "  ;; {}", note).unwrap();
+        }
+        writeln!(out, "  (func {} (type {}){}", id(&body.name), body.type_index, signature(ty, names)).unwrap();
+        for (i, local) in body.locals.iter().enumerate() {
+            match names.get(ty.params.len() + i) {
+                Some(name) => writeln!(out, "    (local {} {})", id(name), local.name()).unwrap(),
+                None => writeln!(out, "    (local {})", local.name()).unwrap(),
+            }
+        }
+        let local = |index: u32| match names.get(index as usize) {
+            Some(name) => id(name),
+            None => index.to_string(),
+        };
+        let mut depth = 2;
+        for (i, instr) in body.code.iter().enumerate() {
+            if matches!(instr, Instr::End | Instr::Else) {
+                depth -= 1;
+            }
+            while let Some((_, note)) = notes.next_if(|(at, _)| *at == i) {
+                writeln!(out, "{:indent$};; {}", "", note, indent = depth * 2).unwrap();
+            }
+            let text = match instr {
+                Instr::Unreachable => "unreachable".to_string(),
+                Instr::Block => "block".to_string(),
+                Instr::Loop => "loop".to_string(),
+                Instr::If => "if".to_string(),
+                Instr::Else => "else".to_string(),
+                Instr::End => "end".to_string(),
+                Instr::Br(label) => format!("br {}", label),
+                Instr::BrIf(label) => format!("br_if {}", label),
+                Instr::BrTable(labels, default) => {
+                    let labels: Vec<String> = labels.iter().chain([default]).map(u32::to_string).collect();
+                    format!("br_table {}", labels.join(" "))
+                }
+                Instr::Return => "return".to_string(),
+                Instr::Call(index) => format!("call {}", id(&self.function_names[*index as usize])),
+                Instr::CallIndirect(type_index) => format!("call_indirect (type {})", type_index),
+                Instr::LocalGet(index) => format!("local.get {}", local(*index)),
+                Instr::LocalSet(index) => format!("local.set {}", local(*index)),
+                Instr::LocalTee(index) => format!("local.tee {}", local(*index)),
+                Instr::GlobalGet(_) => "global.get $heap".to_string(),
+                Instr::GlobalSet(_) => "global.set $heap".to_string(),
+                Instr::Mem(op, 0) => op.name().to_string(),
+                Instr::Mem(op, offset) => format!("{} offset={}", op.name(), offset),
+                Instr::MemorySize => "memory.size".to_string(),
+                Instr::MemoryGrow => "memory.grow".to_string(),
+                Instr::MemoryCopy => "memory.copy".to_string(),
+                Instr::I32Const(n) => format!("i32.const {}", n),
+                Instr::I64Const(n) => format!("i64.const {}", n),
+                Instr::Num(op) => op.name().to_string(),
+            };
+            writeln!(out, "{:indent$}{}", "", text, indent = depth * 2).unwrap();
+            if matches!(instr, Instr::Block | Instr::Loop | Instr::If | Instr::Else) {
+                depth += 1;
+            }
+        }
+        out.push_str("  )\n");
+    }
+}
+
+/// Parameters, named if `names` has them, and results
+fn signature(ty: &FuncType, names: &[String]) -> String {
+    let mut out = String::new();
+    for (i, param) in ty.params.iter().enumerate() {
+        match names.get(i) {
+            Some(name) => write!(out, " (param {} {})", id(name), param.name()).unwrap(),
+            None => write!(out, " (param {})", param.name()).unwrap(),
+        }
+    }
+    for result in &ty.results {
+        write!(out, " (result {})", result.name()).unwrap();
+    }
+    out
+}
+
+/// WAT identifier for a name: plain `$name` when every character may
+/// appear in one, quoted `$"name"` otherwise
+fn id(name: &str) -> String {
+    let plain = !name.is_empty() && name.chars().all(|c| {
+        c.is_ascii_graphic() && !matches!(c, '"' | ',' | ';' | '(' | ')' | '[' | ']' | '{' | '}')
+    });
+    if plain {
+        format!("${}", name)
+    } else {
+        format!("${}", quote(name.as_bytes()))
+    }
+}
+
+/// WAT string literal; anything but printable ASCII is escaped
+fn quote(bytes: &[u8]) -> String {
+    let mut out = String::from("\"");
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{}", byte as char).unwrap(),
+            0x20..=0x7E => out.push(byte as char),
+            _ => write!(out, "\\{:02x}", byte).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn uleb(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn vec_len(out: &mut Vec<u8>, len: usize) {
+    uleb(out, len as u64);
+}
+
+fn name(out: &mut Vec<u8>, name: &str) {
+    vec_len(out, name.len());
+    out.extend_from_slice(name.as_bytes());
+}
+
+fn section(out: &mut Vec<u8>, id: u8, contents: Vec<u8>) {
+    out.push(id);
+    vec_len(out, contents.len());
+    out.extend(contents);
+}
+
+fn encode_instr(out: &mut Vec<u8>, instr: &Instr) {
+    match instr {
+        Instr::Unreachable => out.push(0x00),
+        Instr::Block => out.extend([0x02, 0x40]),
+        Instr::Loop => out.extend([0x03, 0x40]),
+        Instr::If => out.extend([0x04, 0x40]),
+        Instr::Else => out.push(0x05),
+        Instr::End => out.push(0x0B),
+        Instr::Br(depth) => {
+            out.push(0x0C);
+            uleb(out, *depth as u64);
+        }
+        Instr::BrIf(depth) => {
+            out.push(0x0D);
+            uleb(out, *depth as u64);
+        }
+        Instr::BrTable(targets, default) => {
+            out.push(0x0E);
+            vec_len(out, targets.len());
+            for target in targets {
+                uleb(out, *target as u64);
+            }
+            uleb(out, *default as u64);
+        }
+        Instr::Return => out.push(0x0F),
+        Instr::Call(index) => {
+            out.push(0x10);
+            uleb(out, *index as u64);
+        }
+        Instr::CallIndirect(type_index) => {
+            out.push(0x11);
+            uleb(out, *type_index as u64);
+            out.push(0x00);
+        }
+        Instr::LocalGet(index) | Instr::LocalSet(index) | Instr::LocalTee(index)
+        | Instr::GlobalGet(index) | Instr::GlobalSet(index) => {
+            out.push(match instr {
+                Instr::LocalGet(_) => 0x20,
+                Instr::LocalSet(_) => 0x21,
+                Instr::LocalTee(_) => 0x22,
+                Instr::GlobalGet(_) => 0x23,
+                _ => 0x24,
+            });
+            uleb(out, *index as u64);
+        }
+        Instr::Mem(op, offset) => {
+            out.push(op.opcode());
+            uleb(out, op.align() as u64);
+            uleb(out, *offset as u64);
+        }
+        Instr::MemorySize => out.extend([0x3F, 0x00]),
+        Instr::MemoryGrow => out.extend([0x40, 0x00]),
+        Instr::MemoryCopy => out.extend([0xFC, 0x0A, 0x00, 0x00]),
+        Instr::I32Const(n) => {
+            out.push(0x41);
+            sleb(out, *n as i64);
+        }
+        Instr::I64Const(n) => {
+            out.push(0x42);
+            sleb(out, *n);
+        }
+        Instr::Num(op) => out.push(op.opcode()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lower_module, parse_module, Parser};
+    use wasmparser::{Parser as WasmParser, Payload, TypeRef, Validator};
+
+    fn compile(src: &str) -> Vec<u8> {
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let bytes = emit_wasm(&lower_module(&module)).unwrap_or_else(|e| panic!("{}", e));
+        if let Err(e) = Validator::new().validate_all(&bytes) {
+            panic!("invalid module: {}", e);
+        }
+        bytes
+    }
+
+    fn imports(bytes: &[u8]) -> Vec<(String, String)> {
+        let mut imports = Vec::new();
+        for payload in WasmParser::new(0).parse_all(bytes) {
+            if let Payload::ImportSection(reader) = payload.unwrap() {
+                for import in reader.into_imports() {
+                    let import = import.unwrap();
+                    assert!(matches!(import.ty, TypeRef::Func(_)));
+                    imports.push((import.module.to_string(), import.name.to_string()));
+                }
+            }
+        }
+        imports
+    }
+
+    fn exports(bytes: &[u8]) -> Vec<String> {
+        let mut exports = Vec::new();
+        for payload in WasmParser::new(0).parse_all(bytes) {
+            if let Payload::ExportSection(reader) = payload.unwrap() {
+                exports.extend(reader.into_iter().map(|e| e.unwrap().name.to_string()));
+            }
+        }
+        exports
+    }
+
+    fn bodies(bytes: &[u8]) -> Vec<Vec<u8>> {
+        let mut bodies = Vec::new();
+        for payload in WasmParser::new(0).parse_all(bytes) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                bodies.push(bytes[body.range()].to_vec());
+            }
+        }
+        bodies
+    }
+
+    #[test]
+    fn test_leb128() {
+        let mut out = Vec::new();
+        uleb(&mut out, 624485);
+        assert_eq!(out, [0xE5, 0x8E, 0x26]);
+        out.clear();
+        sleb(&mut out, -123456);
+        assert_eq!(out, [0xC0, 0xBB, 0x78]);
+        out.clear();
+        sleb(&mut out, 64);
+        assert_eq!(out, [0xC0, 0x00]);
+    }
+
+    #[test]
+    fn test_host_functions_are_imports() {
+        let bytes = compile("module m
+fn log(x: Int) !{io}
+fn fetch(url: Text) -> Text !{net}
+fn main() -> Text !{net} { log(1); fetch(\"a\" + \"b\") }");
+        assert_eq!(imports(&bytes), [
+            ("io".to_string(), "log".to_string()),
+            ("net".to_string(), "fetch".to_string()),
+        ]);
+        assert_eq!(exports(&bytes), ["main", "memory"]);
+    }
+
+    #[test]
+    fn test_import_modules_follow_declared_effects() {
+        let bytes = compile("module m
+effect fs <: io;
+effect clock;
+fn read(p: Text) -> Text !{fs, io}
+fn now() -> Int !{clock, fs}
+fn main() -> Int !{io, clock} { read(\"a\"); now() }");
+        assert_eq!(imports(&bytes), [
+            ("io".to_string(), "read".to_string()),
+            ("clock+fs".to_string(), "now".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_control_flow_memory_and_indirect_calls() {
+        compile("module m
+enum Shape { Circle, Square }
+fn log(x: Int) !{io}
+fn area(s: Shape, n: Int) -> Int { match s { Shape::Circle => n * n * 3, _ => n * n } }
+fn apply(f: fn(Int) -> Int, x: Int) -> Int { f(x) }
+fn double(x: Int) -> Int { x * 2 }
+fn run(xs: Array<Int>) -> Array<Text> !{io} {
+    let total = 0;
+    for x in xs {
+        if x > 0 && x != 3 { total = total + apply(double, x); } else { log(x); }
+    }
+    let i = 0;
+    while i < len(xs) || !(total == 0) { i = i + 1; total = total - 1; }
+    [\"a\" + \"b\", \"c\"]
+}");
+    }
+
+    #[test]
+    fn test_phis_swapping_values() {
+        let module = parse_module("module m
+
+fn swap(%n: i64) -> i64 {
+entry:
+  %a0: i64 = const 1
+  %b0: i64 = const 2
+  jump loop
+loop:
+  %a: i64 = phi [entry: %a0], [loop: %b]
+  %b: i64 = phi [entry: %b0], [loop: %a]
+  %c: bool = lt %a, %n
+  branch %c, loop, done
+done:
+  return %b
+}").unwrap();
+        let bytes = emit_wasm(&module).unwrap();
+        Validator::new().validate_all(&bytes).unwrap();
+    }
+
+    #[test]
+    fn test_undefined_values_are_rejected() {
+        let module = parse_module("module m
+
+fn f() -> i64 {
+entry:
+  return %undef
+}").unwrap();
+        let err = emit_wasm(&module).unwrap_err();
+        assert!(err.message.contains("undefined value"), "{}", err);
+    }
+
+    #[test]
+    fn test_wat_identifiers_are_quoted_when_needed() {
+        let src = "module m
+fn café(crème: Int) -> Int { crème + 1 }
+fn __alloc(n: Int) -> Int { café(n) }
+fn main() -> Text { __alloc(1); \"a\" + \"b\" }";
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let ir_module = lower_module(&module);
+        let wat = emit_wat(&ir_module).unwrap();
+        let from_text = wat::parse_str(&wat).unwrap_or_else(|e| panic!("{}\n{}", e, wat));
+        Validator::new().validate_all(&from_text).unwrap();
+        assert_eq!(bodies(&from_text), bodies(&emit_wasm(&ir_module).unwrap()), "{}", wat);
+        assert!(wat.contains("(func $\"caf\\c3\\a9\" (type 0) (param $\"%cr\\c3\\a8me\" i64)"), "{}", wat);
+        assert!(wat.contains("(func $__alloc"), "{}", wat);
+        assert!(wat.contains("(func $rt:alloc"), "{}", wat);
+    }
+
+    #[test]
+    fn test_function_named_memory_is_rejected() {
+        let (module, errors) = Parser::new("module m
+fn memory() -> Int { 1 }").parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let err = emit_wasm(&lower_module(&module)).unwrap_err();
+        assert!(err.message.starts_with("`memory` cannot be exported"), "{}", err);
+        assert_eq!(err.span.line, 2);
+    }
+
+    #[test]
+    fn test_wat_matches_binary() {
+        let src = "module m
+fn log(x: Int) !{io}
+fn pick(xs: Array<Int>, i: Int) -> Int { xs[i] }
+fn main(n: Int) -> Text !{io} {
+    let i = 0;
+    while i < n {
+        log(pick([1, 2], i));
+        i = i + 1;
+    }
+    if n == 2 { \"two\" + \"\\\"\" } else { \"other\" }
+}";
+        let (module, errors) = Parser::new(src).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let ir_module = lower_module(&module);
+        let wat = emit_wat(&ir_module).unwrap();
+        let from_text = wat::parse_str(&wat).unwrap_or_else(|e| panic!("{}\n{}", e, wat));
+        Validator::new().validate_all(&from_text).unwrap();
+        assert_eq!(bodies(&from_text), bodies(&emit_wasm(&ir_module).unwrap()), "{}", wat);
+
+        assert!(wat.contains("(import \"io\" \"log\" (func $log (type 0)))"), "{}", wat);
+        assert!(wat.contains(";; fn pick at line 3\n  (func $pick"), "{}", wat);
+        assert!(wat.contains(";; fn main at line 4\n"), "{}", wat);
+        assert!(wat.contains(";; line 7\n"), "{}", wat);
+        assert!(wat.contains(";; block entry\n"), "{}", wat);
+        assert!(wat.contains("local.get $%n"), "{}", wat);
+        assert!(wat.contains("call $rt:concat"), "{}", wat);
+        assert!(wat.contains("\\03\\00\\00\\00two\\00\\01\\00\\00\\00\\\"\\00"), "{}", wat);
+    }
+}
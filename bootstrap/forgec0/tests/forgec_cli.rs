@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Fresh directory holding the given source files
+fn workspace(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("forgec-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (file, text) in files {
+        fs::write(dir.join(file), text).unwrap();
+    }
+    dir
+}
+
+fn forgec(dir: &PathBuf, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_forgec"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+const OK: &str = "module ok
+fn print(t: Text) !{io}
+fn main() -> Int !{io} { print(\"hi\"); 6 * 7 }
+";
+
+const BAD: &str = "module bad
+fn f() -> Int { \"x\" }
+";
+
+#[test]
+fn test_check_exit_codes() {
+    let dir = workspace("check", &[("ok.fg", OK), ("bad.fg", BAD)]);
+    assert_eq!(forgec(&dir, &["check", "ok.fg"]).status.code(), Some(0));
+
+    let output = forgec(&dir, &["check", "--plain", "ok.fg", "bad.fg"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("bad.fg:2:17: error[E0203]"), "{}", stderr(&output));
+
+    let output = forgec(&dir, &["check", "missing.fg"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("cannot read `missing.fg`"));
+}
+
+#[test]
+fn test_check_and_run_report_ownership_errors() {
+    let moved = "module moved\nfn main() -> Int { let a = [1]; let b = [a]; a[0] }\n";
+    let dir = workspace("ownership", &[("moved.fg", moved)]);
+    for command in ["check", "run", "build"] {
+        let output = forgec(&dir, &[command, "moved.fg"]);
+        assert_eq!(output.status.code(), Some(1), "{}", command);
+        assert!(stderr(&output).contains("error[E0301]"), "{}: {}", command, stderr(&output));
+        assert_eq!(stdout(&output), "", "{}", command);
+    }
+}
+
+#[test]
+fn test_usage_errors() {
+    let dir = workspace("usage", &[("ok.fg", OK)]);
+    for args in [&[][..], &["frob", "ok.fg"], &["check"], &["check", "--nope", "ok.fg"], &["run", "ok.fg", "-o", "x"]] {
+        let output = forgec(&dir, args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr(&output).contains("usage: forgec"), "{:?}", args);
+    }
+    let output = forgec(&dir, &["--help"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("emit-tokens"));
+}
+
+#[test]
+fn test_run() {
+    let dir = workspace("run", &[
+        ("ok.fg", OK),
+        ("boom.fg", "module boom\nfn main() -> Int { 1 / 0 }\n"),
+        ("forever.fg", "module forever\nfn main() -> Int { main() }\n"),
+    ]);
+    let output = forgec(&dir, &["run", "ok.fg"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "hi\n42\n");
+
+    let output = forgec(&dir, &["run", "boom.fg"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("error[E0605]"));
+
+    let output = forgec(&dir, &["run", "forever.fg"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("error[E0609]: call to `main` nests too deeply"), "{}", stderr(&output));
+
+    let output = forgec(&dir, &["run", "--entry", "nope", "ok.fg"]);
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn test_build() {
+    let dir = workspace("build", &[("ok.fg", OK), ("two.fg", "module two\nfn two() -> Int { 2 }\n")]);
+    assert_eq!(forgec(&dir, &["build", "ok.fg"]).status.code(), Some(0));
+    assert!(fs::read(dir.join("ok.wasm")).unwrap().starts_with(b"\0asm"));
+
+    assert_eq!(forgec(&dir, &["build", "--wat", "ok.fg", "-o", "ok.txt"]).status.code(), Some(0));
+    assert!(fs::read_to_string(dir.join("ok.txt")).unwrap().starts_with("(module $ok"));
+
+    assert_eq!(forgec(&dir, &["build", "ok.fg", "two.fg", "-o", "out"]).status.code(), Some(0));
+    assert!(dir.join("out/ok.wasm").exists());
+    assert!(dir.join("out/two.wasm").exists());
+}
+
+#[test]
+fn test_emit() {
+    let dir = workspace("emit", &[("ok.fg", OK), ("bad.fg", BAD)]);
+    let output = forgec(&dir, &["emit-tokens", "ok.fg"]);
+    assert!(stdout(&output).starts_with("1:1: module\n1:8: Ident(ok)\n"), "{}", stdout(&output));
+
+    let output = forgec(&dir, &["emit-ast", "bad.fg"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).contains("name: \"bad\""));
+
+    // IR is only emitted for modules that check
+    let output = forgec(&dir, &["emit-ir", "bad.fg"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).is_empty());
+
+    assert_eq!(forgec(&dir, &["emit-ir", "ok.fg", "-o", "ok.ir"]).status.code(), Some(0));
+    let ir = fs::read_to_string(dir.join("ok.ir")).unwrap();
+    assert!(forgec0::parse_module(&ir).is_ok(), "{}", ir);
+}
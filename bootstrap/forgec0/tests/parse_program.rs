@@ -0,0 +1,112 @@
+use forgec0::{Parser, StmtKind, ExprKind, TypeKind, Effect};
+
+#[test]
+fn test_parse_whole_file() {
+    let input = "// Pipeline stages
+module data.pipeline !{energy ≤ 10mJ}
+
+use std.io;
+use text
+
+let limit: Int = 42;
+let greeting = \"hello\"
+
+// Multi-line declaration with a trailing comma
+fn process(
+    data: Text,
+    config: Config,
+) -> Int
+    !{net, io, tokens ≤ 100}
+
+let answer = compute(limit, 1)
+fn local_only() -> Text !{io}  // trailing comment
+";
+    let mut parser = Parser::new(input);
+    let (module, errors) = parser.parse_program();
+
+    assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+    assert_eq!(module.name, "data.pipeline");
+    assert_eq!(module.capability.unwrap().budgets.energy_uj, Some(10_000));
+    assert_eq!(module.imports, vec!["std.io", "text"]);
+    assert_eq!(module.statements.len(), 5);
+
+    match &module.statements[0].kind {
+        StmtKind::Let { name, ty, value } => {
+            assert_eq!(name, "limit");
+            assert!(matches!(ty.as_ref().unwrap().kind, TypeKind::Int));
+            assert!(matches!(value.kind, ExprKind::Number(42)));
+        }
+        _ => panic!("Expected Let statement"),
+    }
+    match &module.statements[1].kind {
+        StmtKind::Let { value, .. } => {
+            assert!(matches!(&value.kind, ExprKind::String(s) if s == "hello"));
+        }
+        _ => panic!("Expected Let statement"),
+    }
+    match &module.statements[2].kind {
+        StmtKind::Function { name, params, capability, .. } => {
+            assert_eq!(name, "process");
+            assert_eq!(params.len(), 2);
+            let cap = capability.as_ref().unwrap();
+            assert_eq!(cap.effects, vec![Effect::named("net"), Effect::named("io")]);
+            assert_eq!(module.statements[2].span.line, 11);
+        }
+        _ => panic!("Expected Function statement"),
+    }
+    match &module.statements[3].kind {
+        StmtKind::Let { value, .. } => match &value.kind {
+            ExprKind::Call { func, args } => {
+                assert!(matches!(&func.kind, ExprKind::Ident(f) if f == "compute"));
+                assert_eq!(args.len(), 2);
+            }
+            _ => panic!("Expected Call expression"),
+        },
+        _ => panic!("Expected Let statement"),
+    }
+    assert!(matches!(module.statements[4].kind, StmtKind::Function { .. }));
+}
+
+#[test]
+fn test_parse_demo_file() {
+    let input = include_str!("../../../examples/cap_demo.fg");
+    let (module, errors) = Parser::new(input).parse_program();
+
+    assert!(errors.is_empty());
+    assert_eq!(module.name, "demo.capabilities");
+    assert_eq!(module.statements.len(), 5);
+}
+
+#[test]
+fn test_errors_in_let_and_use() {
+    let input = "module m
+use
+let x = ;
+let y: Int = 1
+fn f() -> Int";
+    let (module, errors) = Parser::new(input).parse_program();
+
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].span().line, 3);
+    assert_eq!(errors[1].span().line, 3);
+    assert!(module.imports.is_empty());
+    // The broken initializer leaves a placeholder so `x` stays bound
+    let lets: Vec<_> = module.statements.iter()
+        .filter_map(|s| match &s.kind {
+            StmtKind::Let { name, value, .. } => Some((name.as_str(), &value.kind)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(lets.len(), 2);
+    assert_eq!(lets[0].0, "x");
+    assert!(matches!(lets[0].1, ExprKind::Error));
+    assert!(matches!(module.statements.last().unwrap().kind, StmtKind::Function { .. }));
+}
+
+#[test]
+fn test_duplicate_module_header() {
+    let (module, errors) = Parser::new("module a\nmodule b\nfn f() -> Int").parse_program();
+    assert_eq!(module.name, "a");
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].span().line, 2);
+}
@@ -0,0 +1,144 @@
+use forgec0::{Parser, Block, Stmt, StmtKind, ExprKind, TypeKind, Effect};
+
+fn function_body(stmt: &Stmt) -> Option<&Block> {
+    match &stmt.kind {
+        StmtKind::Function { body, .. } => body.as_ref(),
+        _ => panic!("Expected Function statement"),
+    }
+}
+
+#[test]
+fn test_rfc_sequence_example() {
+    let mut parser = Parser::new("fn h() { f(); g() }");
+    let func = parser.parse_function().unwrap();
+
+    match &func.kind {
+        StmtKind::Function { returns, capability, .. } => {
+            assert!(matches!(returns.kind, TypeKind::Unit));
+            assert!(capability.is_none());
+        }
+        _ => panic!("Expected Function statement"),
+    }
+
+    let body = function_body(&func).unwrap();
+    assert_eq!(body.stmts.len(), 1);
+    match &body.stmts[0].kind {
+        StmtKind::Expression(expr) => match &expr.kind {
+            ExprKind::Call { func, args } => {
+                assert!(matches!(&func.kind, ExprKind::Ident(f) if f == "f"));
+                assert!(args.is_empty());
+            }
+            _ => panic!("Expected Call expression"),
+        },
+        _ => panic!("Expected Expression statement"),
+    }
+    let tail = body.tail.as_ref().unwrap();
+    assert!(matches!(&tail.kind, ExprKind::Call { .. }));
+}
+
+#[test]
+fn test_let_statements_and_tail() {
+    let input = "fn f(x: Int) -> Int {
+    let y: Int = g(x);
+    let z = y;
+    z
+}";
+    let func = Parser::new(input).parse_function().unwrap();
+    let body = function_body(&func).unwrap();
+
+    assert_eq!(body.stmts.len(), 2);
+    match &body.stmts[0].kind {
+        StmtKind::Let { name, ty, value } => {
+            assert_eq!(name, "y");
+            assert!(matches!(ty.as_ref().unwrap().kind, TypeKind::Int));
+            assert!(matches!(value.kind, ExprKind::Call { .. }));
+        }
+        _ => panic!("Expected Let statement"),
+    }
+    assert!(matches!(&body.tail.as_ref().unwrap().kind, ExprKind::Ident(z) if z == "z"));
+    assert_eq!(body.span.line, 1);
+    assert_eq!(body.stmts[1].span.line, 3);
+}
+
+#[test]
+fn test_declaration_and_empty_body() {
+    let decl = Parser::new("fn fetch() -> Text !{net}").parse_function().unwrap();
+    assert!(function_body(&decl).is_none());
+
+    let empty = Parser::new("fn noop() !{pure} {}").parse_function().unwrap();
+    let body = function_body(&empty).unwrap();
+    assert!(body.stmts.is_empty());
+    assert!(body.tail.is_none());
+}
+
+#[test]
+fn test_function_typed_parameter() {
+    let input = "fn process(f: fn(Int, Int) -> Int !{io}) !{io} {
+    f(1, 2)
+}";
+    let func = Parser::new(input).parse_function().unwrap();
+    match &func.kind {
+        StmtKind::Function { params, capability, .. } => {
+            match &params[0].1.kind {
+                TypeKind::Function { params, returns, capability } => {
+                    assert_eq!(params.len(), 2);
+                    assert!(matches!(returns.kind, TypeKind::Int));
+                    assert_eq!(capability.as_ref().unwrap().effects, vec![Effect::named("io")]);
+                }
+                _ => panic!("Expected function type"),
+            }
+            assert_eq!(capability.as_ref().unwrap().effects, vec![Effect::named("io")]);
+        }
+        _ => panic!("Expected Function statement"),
+    }
+}
+
+#[test]
+fn test_missing_semicolon_is_an_error() {
+    let err = Parser::new("fn f() { g() h() }").parse_function().unwrap_err();
+    assert_eq!(err.to_string(), "expected ; or }, found identifier `h`");
+}
+
+#[test]
+fn test_body_recovery() {
+    let input = "module m
+fn f() -> Int {
+    let x = ;
+    g(;
+    x
+}
+fn k() {}";
+    let (module, errors) = Parser::new(input).parse_program();
+
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].span().line, 3);
+    assert_eq!(errors[1].span().line, 4);
+    assert_eq!(module.statements.len(), 2);
+
+    let body = function_body(&module.statements[0]).unwrap();
+    assert_eq!(body.stmts.len(), 2);
+    match &body.stmts[0].kind {
+        StmtKind::Let { name, value, .. } => {
+            assert_eq!(name, "x");
+            assert!(matches!(value.kind, ExprKind::Error));
+        }
+        _ => panic!("Expected Let statement"),
+    }
+    assert!(matches!(body.stmts[1].kind, StmtKind::Error));
+    assert!(body.tail.is_some());
+    assert!(function_body(&module.statements[1]).is_some());
+}
+
+#[test]
+fn test_broken_header_skips_body() {
+    let input = "module m
+fn f( -> Int {
+    let x = 1;
+    x
+}
+fn k() {}";
+    let (module, errors) = Parser::new(input).parse_program();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(module.statements.len(), 2);
+    assert!(matches!(module.statements[0].kind, StmtKind::Error));
+}
@@ -1,4 +1,4 @@
-use forgec0::{Parser, Stmt, Module, Effect, Type};
+use forgec0::{Parser, StmtKind, Effect, TypeKind};
 
 #[test]
 fn test_parse_module_with_capability() {
@@ -9,30 +9,30 @@ fn test_parse_module_with_capability() {
     assert!(module.capability.is_some());
     
     let cap = module.capability.unwrap();
-    assert_eq!(cap.budgets.energy_mj, Some(10));
+    assert_eq!(cap.budgets.energy_uj, Some(10_000));
 }
 
 #[test]
 fn test_parse_function_multiple_params() {
     let mut parser = Parser::new("fn transform(input: Vec, config: Config) -> Result !{io, alloc}");
     
-    match parser.parse_function().unwrap() {
-        Stmt::Function { name, params, returns, capability, .. } => {
+    match parser.parse_function().unwrap().kind {
+        StmtKind::Function { name, params, returns, capability, .. } => {
             assert_eq!(name, "transform");
             assert_eq!(params.len(), 2);
             
             assert_eq!(params[0].0, "input");
-            matches!(params[0].1, Type::Custom(ref s) if s == "Vec");
+            matches!(params[0].1.kind, TypeKind::Custom(ref s) if s == "Vec");
             
             assert_eq!(params[1].0, "config");
-            matches!(params[1].1, Type::Custom(ref s) if s == "Config");
+            matches!(params[1].1.kind, TypeKind::Custom(ref s) if s == "Config");
             
-            matches!(returns, Type::Custom(ref s) if s == "Result");
+            matches!(returns.kind, TypeKind::Custom(ref s) if s == "Result");
             
             let cap = capability.unwrap();
             assert_eq!(cap.effects.len(), 2);
-            assert!(cap.effects.contains(&Effect::Io));
-            assert!(cap.effects.contains(&Effect::Alloc));
+            assert!(cap.effects.contains(&Effect::named("io")));
+            assert!(cap.effects.contains(&Effect::named("alloc")));
         }
         _ => panic!("Expected Function statement"),
     }
@@ -45,32 +45,32 @@ fn test_parse_complex_capability() {
     
     // Check effects
     assert_eq!(cap.effects.len(), 2);
-    assert!(cap.effects.contains(&Effect::Net));
-    assert!(cap.effects.contains(&Effect::Io));
+    assert!(cap.effects.contains(&Effect::named("net")));
+    assert!(cap.effects.contains(&Effect::named("io")));
     
     // Check resource budgets
     assert_eq!(cap.budgets.tokens, Some(100));
-    assert_eq!(cap.budgets.latency_ms, Some(200));
-    assert_eq!(cap.budgets.energy_mj, Some(5));
+    assert_eq!(cap.budgets.latency_us, Some(200_000));
+    assert_eq!(cap.budgets.energy_uj, Some(5_000));
 }
 
 #[test]
 fn test_parse_pure_effect() {
     let mut parser = Parser::new("!{pure}");
     let cap = parser.parse_capability().unwrap();
-    assert_eq!(cap.effects, vec![Effect::Pure]);
+    assert_eq!(cap.effects, vec![Effect::pure()]);
 }
 
 #[test]
 fn test_parse_function_without_capability() {
     let mut parser = Parser::new("fn add(x: Int, y: Int) -> Int");
     
-    match parser.parse_function().unwrap() {
-        Stmt::Function { name, params, returns, capability, .. } => {
+    match parser.parse_function().unwrap().kind {
+        StmtKind::Function { name, params, returns, capability, .. } => {
             assert_eq!(name, "add");
             assert_eq!(params.len(), 2);
             assert!(capability.is_none());
-            matches!(returns, Type::Int);
+            matches!(returns.kind, TypeKind::Int);
         }
         _ => panic!("Expected Function statement"),
     }
@@ -82,8 +82,8 @@ fn test_empty_capability() {
     let cap = parser.parse_capability().unwrap();
     assert!(cap.effects.is_empty());
     assert!(cap.budgets.tokens.is_none());
-    assert!(cap.budgets.latency_ms.is_none());
-    assert!(cap.budgets.energy_mj.is_none());
+    assert!(cap.budgets.latency_us.is_none());
+    assert!(cap.budgets.energy_uj.is_none());
 }
 
 #[test]
@@ -100,8 +100,8 @@ fn test_all_effects() {
     let cap = parser.parse_capability().unwrap();
     
     assert_eq!(cap.effects.len(), 4);
-    assert!(cap.effects.contains(&Effect::Pure));
-    assert!(cap.effects.contains(&Effect::Alloc));
-    assert!(cap.effects.contains(&Effect::Io));
-    assert!(cap.effects.contains(&Effect::Net));
+    assert!(cap.effects.contains(&Effect::pure()));
+    assert!(cap.effects.contains(&Effect::named("alloc")));
+    assert!(cap.effects.contains(&Effect::named("io")));
+    assert!(cap.effects.contains(&Effect::named("net")));
 }
\ No newline at end of file
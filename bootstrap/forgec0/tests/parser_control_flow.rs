@@ -0,0 +1,144 @@
+use forgec0::{Parser, Block, Stmt, StmtKind, ExprKind, PatternKind, BinOp};
+
+fn function_body(stmt: &Stmt) -> &Block {
+    match &stmt.kind {
+        StmtKind::Function { body, .. } => body.as_ref().unwrap(),
+        _ => panic!("Expected Function statement"),
+    }
+}
+
+#[test]
+fn test_if_else_chain() {
+    let input = "fn sign(x: Int) -> Int {
+    if x < 0 { -1 } else if x == 0 { 0 } else { 1 }
+}";
+    let func = Parser::new(input).parse_function().unwrap();
+    let tail = function_body(&func).tail.as_ref().unwrap();
+
+    match &tail.kind {
+        ExprKind::If { cond, then_branch, else_branch } => {
+            assert!(matches!(cond.kind, ExprKind::Binary { op: BinOp::Lt, .. }));
+            assert!(then_branch.tail.is_some());
+            match &else_branch.as_ref().unwrap().kind {
+                ExprKind::If { else_branch, .. } => {
+                    assert!(matches!(else_branch.as_ref().unwrap().kind, ExprKind::Block(_)));
+                }
+                _ => panic!("Expected else-if"),
+            }
+        }
+        _ => panic!("Expected If expression"),
+    }
+}
+
+#[test]
+fn test_while_with_assignment() {
+    let input = "fn count(n: Int) -> Int {
+    let i = 0;
+    while i < n {
+        i = i + 1;
+    }
+    i
+}";
+    let func = Parser::new(input).parse_function().unwrap();
+    let body = function_body(&func);
+
+    // The loop needs no trailing `;` to be a statement
+    assert_eq!(body.stmts.len(), 2);
+    match &body.stmts[1].kind {
+        StmtKind::Expression(expr) => match &expr.kind {
+            ExprKind::While { body, .. } => {
+                assert!(matches!(&body.stmts[0].kind, StmtKind::Assign { name, .. } if name == "i"));
+            }
+            _ => panic!("Expected While expression"),
+        },
+        _ => panic!("Expected Expression statement"),
+    }
+    assert!(matches!(&body.tail.as_ref().unwrap().kind, ExprKind::Ident(i) if i == "i"));
+}
+
+#[test]
+fn test_for_over_array() {
+    let input = "fn total() -> Int {
+    let xs = [1, 2, 3];
+    let sum = 0;
+    for x in xs { sum = sum + x; }
+    sum + xs[0]
+}";
+    let func = Parser::new(input).parse_function().unwrap();
+    let body = function_body(&func);
+
+    match &body.stmts[0].kind {
+        StmtKind::Let { value, .. } => {
+            assert!(matches!(&value.kind, ExprKind::Array(items) if items.len() == 3));
+        }
+        _ => panic!("Expected Let statement"),
+    }
+    match &body.stmts[2].kind {
+        StmtKind::Expression(expr) => match &expr.kind {
+            ExprKind::For { var, iter, .. } => {
+                assert_eq!(var, "x");
+                assert!(matches!(&iter.kind, ExprKind::Ident(xs) if xs == "xs"));
+            }
+            _ => panic!("Expected For expression"),
+        },
+        _ => panic!("Expected Expression statement"),
+    }
+    match &body.tail.as_ref().unwrap().kind {
+        ExprKind::Binary { rhs, .. } => assert!(matches!(rhs.kind, ExprKind::Index { .. })),
+        _ => panic!("Expected Binary expression"),
+    }
+}
+
+#[test]
+fn test_match_patterns() {
+    let input = "module m
+enum Color { Red, Green, Blue }
+fn name(c: Color) -> Text {
+    match c {
+        Color::Red => \"red\",
+        Color::Green => { \"green\" }
+        _ => \"other\",
+    }
+}
+fn small(n: Int) -> Bool {
+    match n { 0 => true, -1 => true, other => false }
+}";
+    let (module, errors) = Parser::new(input).parse_program();
+    assert!(errors.is_empty(), "unexpected errors: {:?}", errors);
+    assert_eq!(module.statements.len(), 3);
+
+    match &module.statements[0].kind {
+        StmtKind::Enum { name, variants } => {
+            assert_eq!(name, "Color");
+            assert_eq!(variants, &vec!["Red", "Green", "Blue"]);
+        }
+        _ => panic!("Expected Enum statement"),
+    }
+
+    let arms = |stmt| match &function_body(stmt).tail.as_ref().unwrap().kind {
+        ExprKind::Match { arms, .. } => arms.iter().map(|a| a.pattern.kind.clone()).collect::<Vec<_>>(),
+        _ => panic!("Expected Match expression"),
+    };
+    let color = arms(&module.statements[1]);
+    assert!(matches!(&color[0], PatternKind::Variant { enum_name, variant }
+        if enum_name == "Color" && variant == "Red"));
+    assert!(matches!(color[2], PatternKind::Wildcard));
+
+    let small = arms(&module.statements[2]);
+    assert!(matches!(small[0], PatternKind::Number(0)));
+    assert!(matches!(small[1], PatternKind::Number(-1)));
+    assert!(matches!(&small[2], PatternKind::Binding(name) if name == "other"));
+}
+
+#[test]
+fn test_variant_expression() {
+    let expr = Parser::new("Color::Blue").parse_expr().unwrap();
+    assert!(matches!(&expr.kind, ExprKind::Variant { enum_name, variant }
+        if enum_name == "Color" && variant == "Blue"));
+}
+
+#[test]
+fn test_missing_arrow_in_match() {
+    let err = Parser::new("match x { 1 2 }").parse_expr().unwrap_err();
+    assert_eq!(err.to_string(), "expected `=>`, found number `2`");
+}
@@ -0,0 +1,105 @@
+use forgec0::{Parser, Expr, ExprKind, StmtKind, TypeKind, Effect};
+
+/// Fully parenthesized rendering of an expression
+fn sexpr(expr: &Expr) -> String {
+    match &expr.kind {
+        ExprKind::Ident(name) => name.clone(),
+        ExprKind::Number(n) => n.to_string(),
+        ExprKind::Bool(b) => b.to_string(),
+        ExprKind::String(s) => format!("{:?}", s),
+        ExprKind::Binary { op, lhs, rhs } => {
+            format!("({} {} {})", op.symbol(), sexpr(lhs), sexpr(rhs))
+        }
+        ExprKind::Unary { op, operand } => format!("({} {})", op.symbol(), sexpr(operand)),
+        ExprKind::Call { func, args } => {
+            let args: Vec<String> = args.iter().map(sexpr).collect();
+            format!("(call {} {})", sexpr(func), args.join(" "))
+        }
+        other => panic!("unexpected expression {:?}", other),
+    }
+}
+
+fn parse(input: &str) -> String {
+    sexpr(&Parser::new(input).parse_expr().unwrap())
+}
+
+#[test]
+fn test_arithmetic_precedence() {
+    assert_eq!(parse("x + y"), "(+ x y)");
+    assert_eq!(parse("1 + 2 * 3"), "(+ 1 (* 2 3))");
+    assert_eq!(parse("(1 + 2) * 3"), "(* (+ 1 2) 3)");
+    assert_eq!(parse("a - b - c"), "(- (- a b) c)");
+    assert_eq!(parse("a / b % c * d"), "(* (% (/ a b) c) d)");
+}
+
+#[test]
+fn test_comparison_and_logic() {
+    assert_eq!(parse("a + 1 < b * 2"), "(< (+ a 1) (* b 2))");
+    assert_eq!(parse("a == b && c != d || e"), "(|| (&& (== a b) (!= c d)) e)");
+    assert_eq!(parse("x ≤ 10 && y >= 0"), "(&& (<= x 10) (>= y 0))");
+    assert_eq!(parse("a || b && c"), "(|| a (&& b c))");
+}
+
+#[test]
+fn test_unary_operators() {
+    assert_eq!(parse("-x * y"), "(* (- x) y)");
+    assert_eq!(parse("!done && ready"), "(&& (! done) ready)");
+    assert_eq!(parse("!!true"), "(! (! true))");
+    assert_eq!(parse("-f(x) + 1"), "(+ (- (call f x)) 1)");
+    assert_eq!(parse("a - -b"), "(- a (- b))");
+}
+
+#[test]
+fn test_operator_spans() {
+    let src = "a + b * c";
+    let expr = Parser::new(src).parse_expr().unwrap();
+    assert_eq!((expr.span.start, expr.span.end), (0, src.len()));
+    match &expr.kind {
+        ExprKind::Binary { rhs, .. } => assert_eq!(&src[rhs.span.start..rhs.span.end], "b * c"),
+        _ => panic!("Expected Binary expression"),
+    }
+}
+
+#[test]
+fn test_rfc_pure_add() {
+    let input = "fn pure_add(x: Int, y: Int) -> Int { x + y }";
+    match Parser::new(input).parse_function().unwrap().kind {
+        StmtKind::Function { body, .. } => {
+            let tail = body.unwrap().tail.unwrap();
+            assert_eq!(sexpr(&tail), "(+ x y)");
+        }
+        _ => panic!("Expected Function statement"),
+    }
+}
+
+#[test]
+fn test_negation_vs_capability() {
+    // `!` after the return type is a capability, inside the body it negates
+    let input = "fn check(flag: Bool) -> Bool !{pure} { !flag }";
+    match Parser::new(input).parse_function().unwrap().kind {
+        StmtKind::Function { capability, body, .. } => {
+            assert_eq!(capability.unwrap().effects, vec![Effect::pure()]);
+            assert_eq!(sexpr(&body.unwrap().tail.unwrap()), "(! flag)");
+        }
+        _ => panic!("Expected Function statement"),
+    }
+
+    // A capability in expression position is rejected with a clear message
+    let err = Parser::new("!{io}").parse_expr().unwrap_err();
+    assert!(err.to_string().contains("capabilities `!{...}` belong after a return type"));
+}
+
+#[test]
+fn test_array_types() {
+    let mut parser = Parser::new("fn sum(xs: Array<Int>, grid: Vec<Array<Int>>) -> Int");
+    match parser.parse_function().unwrap().kind {
+        StmtKind::Function { params, .. } => {
+            assert!(matches!(&params[0].1.kind, TypeKind::Array(inner) if matches!(inner.kind, TypeKind::Int)));
+            match &params[1].1.kind {
+                TypeKind::Array(inner) => assert!(matches!(inner.kind, TypeKind::Array(_))),
+                _ => panic!("Expected array type"),
+            }
+        }
+        _ => panic!("Expected Function statement"),
+    }
+}
@@ -0,0 +1,104 @@
+use forgec0::{Parser, ParseError, StmtKind, TypeKind};
+
+#[test]
+fn test_reports_every_broken_declaration() {
+    let input = "module demo !{io}
+fn ok(x: Int) -> Int !{pure}
+fn broken( -> Int
+fn also_ok() -> Text
+fn bad_effect() -> Int !{disk}
+fn last() -> Bool !{io}";
+    let mut parser = Parser::new(input);
+    let (module, errors) = parser.parse_program();
+
+    assert_eq!(module.name, "demo");
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].span().line, 3);
+    assert!(matches!(&errors[1], ParseError::InvalidEffect { name, .. } if name == "disk"));
+
+    let kinds: Vec<_> = module.statements.iter().map(|s| match &s.kind {
+        StmtKind::Function { name, .. } => name.as_str(),
+        StmtKind::Error => "<error>",
+        _ => "<other>",
+    }).collect();
+    assert_eq!(kinds, vec!["ok", "<error>", "also_ok", "<error>", "last"]);
+}
+
+#[test]
+fn test_bad_parameter_keeps_function() {
+    let input = "module demo
+fn f(x Int, y: Text) -> Int";
+    let mut parser = Parser::new(input);
+    let (module, errors) = parser.parse_program();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(module.statements.len(), 1);
+    match &module.statements[0].kind {
+        StmtKind::Function { params, .. } => {
+            assert_eq!(params.len(), 2);
+            assert_eq!(params[0].0, "x");
+            assert!(matches!(params[0].1.kind, TypeKind::Error));
+            assert!(matches!(params[1].1.kind, TypeKind::Text));
+        }
+        _ => panic!("Expected Function statement"),
+    }
+}
+
+#[test]
+fn test_skips_stray_tokens_to_next_line() {
+    let input = "module demo
+} ) 42
+fn f() -> Int";
+    let mut parser = Parser::new(input);
+    let (module, errors) = parser.parse_program();
+
+    // One error for the stray `}`; the rest of its line is skipped
+    assert_eq!(errors.len(), 1);
+    assert_eq!(module.statements.len(), 2);
+    assert!(matches!(module.statements[0].kind, StmtKind::Error));
+    assert!(matches!(module.statements[1].kind, StmtKind::Function { .. }));
+}
+
+#[test]
+fn test_missing_module_header() {
+    let mut parser = Parser::new("fn f() -> Int\nfn g() -> Int");
+    let (module, errors) = parser.parse_program();
+
+    assert_eq!(errors.len(), 1);
+    assert!(module.name.is_empty());
+    assert_eq!(module.statements.len(), 2);
+}
+
+#[test]
+fn test_valid_module_has_no_errors() {
+    let mut parser = Parser::new("module a.b !{energy ≤ 3}\nfn f() -> Int !{pure}");
+    let (module, errors) = parser.parse_program();
+    assert!(errors.is_empty());
+    assert_eq!(module.name, "a.b");
+    assert_eq!(module.statements.len(), 1);
+}
+
+#[test]
+fn test_broken_capability_skips_function_body() {
+    let input = "module demo
+fn read() -> Int !{disk} {
+    let x = 1;
+    x
+}
+fn budget() -> Int !{tokens <= 5min} { 2 }
+fn after() -> Int { 3 }";
+    let mut parser = Parser::new(input);
+    let (module, errors) = parser.parse_program();
+
+    // One diagnostic per mistake; neither body is reported as stray tokens
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(matches!(&errors[0], ParseError::InvalidEffect { name, .. } if name == "disk"));
+    assert_eq!(errors[1].code(), "E0006");
+
+    let kinds: Vec<_> = module.statements.iter().map(|s| match &s.kind {
+        StmtKind::Function { name, .. } => name.as_str(),
+        StmtKind::Error => "<error>",
+        _ => "<other>",
+    }).collect();
+    assert_eq!(kinds, vec!["<error>", "<error>", "after"]);
+}